@@ -0,0 +1,126 @@
+use crate::framebuffer::OutputFormat;
+use crate::validation::Validator;
+use crate::vec3;
+use glam::Vec3A;
+
+use serde::{Deserialize, Serialize};
+
+// pixels darker than this are held to the error of a pixel this bright.
+const MIN_LUMINANCE: f32 = 0.01;
+
+fn default_threshold() -> f32 {
+    0.01
+}
+
+fn default_max_samples() -> u32 {
+    1024
+}
+
+/// Keeps sampling noisy pixels and stops early on converged ones. Pixels are sampled in rounds of
+/// `settings.samples` until the standard error of their luminance drops below `threshold` times
+/// their luminance, or they reach `max_samples`. The sampler is stratified over one round, every
+/// further round is stratified on its own.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdaptiveSettings {
+    #[serde(default = "default_threshold")]
+    pub threshold: f32,
+    #[serde(default = "default_max_samples")]
+    pub max_samples: u32,
+    /// Where to save an image of the samples taken per pixel, white is `max_samples`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_map: Option<String>,
+}
+
+impl Default for AdaptiveSettings {
+    fn default() -> Self {
+        Self {
+            threshold: default_threshold(),
+            max_samples: default_max_samples(),
+            sample_map: None,
+        }
+    }
+}
+
+impl AdaptiveSettings {
+    pub fn validate(&self, location: &str, samples: u32, validator: &mut Validator) {
+        validator.check(
+            self.threshold.is_finite() && self.threshold > 0.0,
+            format!("{location}.threshold"),
+            "threshold must be positive",
+        );
+        validator.check(
+            self.max_samples >= samples,
+            format!("{location}.max_samples"),
+            "max_samples must be at least samples",
+        );
+        if let Some(path) = &self.sample_map {
+            if let Err(e) = OutputFormat::from_path(path) {
+                validator.error(format!("{location}.sample_map"), e.to_string());
+            }
+        }
+    }
+}
+
+/// Running mean and variance of a pixel's samples (Welford's algorithm).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PixelVariance {
+    pub count: u32,
+    pub mean: Vec3A,
+    luminance_mean: f32,
+    luminance_m2: f32,
+}
+
+impl PixelVariance {
+    pub fn add(&mut self, color: Vec3A) {
+        self.count += 1;
+        let n = self.count as f32;
+        self.mean += (color - self.mean) / n;
+        let luminance = vec3::luminance(color);
+        let delta = luminance - self.luminance_mean;
+        self.luminance_mean += delta / n;
+        self.luminance_m2 += delta * (luminance - self.luminance_mean);
+    }
+
+    /// Sample variance of the luminance.
+    pub fn variance(&self) -> f32 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.luminance_m2 / (self.count - 1) as f32
+        }
+    }
+
+    /// Whether the standard error of the mean luminance is below `threshold` relative to it.
+    pub fn converged(&self, threshold: f32) -> bool {
+        let error = (self.variance() / self.count as f32).sqrt();
+        self.count >= 2 && error <= threshold * self.luminance_mean.max(MIN_LUMINANCE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_pixels_converge_after_two_samples() {
+        let mut pixel = PixelVariance::default();
+        pixel.add(Vec3A::splat(0.5));
+        assert!(!pixel.converged(0.01));
+        pixel.add(Vec3A::splat(0.5));
+        assert!(pixel.converged(0.01));
+        assert_eq!(pixel.mean, Vec3A::splat(0.5));
+    }
+
+    #[test]
+    fn noisy_pixels_converge_once_the_error_is_small() {
+        // alternating 0 and 1 has a standard error of 0.5 / sqrt(n - 1) around a mean of 0.5,
+        // which drops below 10% of the mean at 101 samples.
+        let mut pixel = PixelVariance::default();
+        for i in 0..110 {
+            pixel.add(Vec3A::splat((i % 2) as f32));
+            if pixel.count % 2 == 0 {
+                assert_eq!(pixel.converged(0.1), pixel.count > 101, "{}", pixel.count);
+            }
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use crate::error::{TracerError, TracerResult};
+use crate::framebuffer::Framebuffer;
+use crate::validation::Validator;
+use exr::prelude::{
+    read_first_flat_layer_from_file, AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer,
+    LayerAttributes, SmallVec, WritableImage,
+};
+use glam::{Vec2, Vec3A};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Per pixel layer rendered next to the beauty image from the first camera hit. Pixels whose rays
+/// miss everything are zero, except for the albedo which is the background color and the ids
+/// which are -1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Aov {
+    /// Distance from the camera origin to the hit.
+    Depth,
+    /// World space normal, facing the camera.
+    Normal,
+    /// World space position.
+    Position,
+    /// Texture coordinates.
+    Uv,
+    /// Index of the material in the scene's material list.
+    MaterialId,
+    /// Index of the object in the world's object list.
+    ObjectId,
+    /// Color of the material's texture, emitters are black.
+    Albedo,
+}
+
+impl Aov {
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Position => "position",
+            Aov::Uv => "uv",
+            Aov::MaterialId => "material_id",
+            Aov::ObjectId => "object_id",
+            Aov::Albedo => "albedo",
+        }
+    }
+
+    /// Names of the channels in a layered exr, one per component of the value.
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Uv => &["U", "V"],
+            Aov::MaterialId | Aov::ObjectId => &["id"],
+            Aov::Albedo => &["R", "G", "B"],
+        }
+    }
+
+    /// Path of the separate image of this pass, `render.png` becomes `render.depth.exr`.
+    pub fn path<P: AsRef<Path>>(self, path: P) -> PathBuf {
+        let path = path.as_ref();
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        path.with_file_name(format!("{stem}.{}.exr", self.name()))
+    }
+}
+
+/// Checks that no pass is listed twice.
+pub fn validate(location: &str, aovs: &[Aov], validator: &mut Validator) {
+    for (i, aov) in aovs.iter().enumerate() {
+        if aovs[..i].contains(aov) {
+            validator.error(
+                format!("{location}[{i}]"),
+                format!("{aov:?} is listed more than once"),
+            );
+        }
+    }
+}
+
+/// What one camera ray sees first.
+#[derive(Debug, Clone, Copy)]
+pub struct AovSample {
+    pub depth: f32,
+    pub normal: Vec3A,
+    pub position: Vec3A,
+    pub uv: Vec2,
+    pub albedo: Vec3A,
+    pub material: Option<usize>,
+    pub object: Option<usize>,
+}
+
+impl AovSample {
+    /// A ray that escaped the scene.
+    pub fn miss(background: Vec3A) -> Self {
+        Self {
+            depth: 0.0,
+            normal: Vec3A::ZERO,
+            position: Vec3A::ZERO,
+            uv: Vec2::ZERO,
+            albedo: background,
+            material: None,
+            object: None,
+        }
+    }
+}
+
+/// Averages the samples of a pixel, ids can't be averaged and come from its first sample.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AovPixel {
+    count: u32,
+    depth: f32,
+    normal: Vec3A,
+    position: Vec3A,
+    uv: Vec2,
+    albedo: Vec3A,
+    material: Option<usize>,
+    object: Option<usize>,
+}
+
+impl AovPixel {
+    pub fn add(&mut self, sample: &AovSample) {
+        if self.count == 0 {
+            self.material = sample.material;
+            self.object = sample.object;
+        }
+        self.count += 1;
+        self.depth += sample.depth;
+        self.normal += sample.normal;
+        self.position += sample.position;
+        self.uv += sample.uv;
+        self.albedo += sample.albedo;
+    }
+
+    /// Value of `aov`, single values are repeated over the three channels.
+    pub fn value(&self, aov: Aov) -> Vec3A {
+        let scale = 1.0 / self.count.max(1) as f32;
+        let id = |id: Option<usize>| Vec3A::splat(id.map_or(-1.0, |id| id as f32));
+        match aov {
+            Aov::Depth => Vec3A::splat(self.depth * scale),
+            Aov::Normal => self.normal * scale,
+            Aov::Position => self.position * scale,
+            Aov::Uv => (self.uv * scale).extend(0.0).into(),
+            Aov::MaterialId => id(self.material),
+            Aov::ObjectId => id(self.object),
+            Aov::Albedo => self.albedo * scale,
+        }
+    }
+}
+
+/// Writes `image` as the rgb channels of an exr and every pass as a layer of it, with channels
+/// like `depth.Z` or `normal.X`.
+pub fn write_layered_exr<P: AsRef<Path>>(
+    path: P,
+    image: &Framebuffer,
+    aovs: &[(Aov, Framebuffer)],
+) -> TracerResult<()> {
+    let path = path.as_ref();
+    let channel = |name: String, layer: &Framebuffer, component: usize| {
+        let samples = layer.pixels.iter().map(|p| p[component]).collect();
+        AnyChannel::new(name.as_str(), FlatSamples::F32(samples))
+    };
+    let mut channels = SmallVec::new();
+    for (component, name) in ["R", "G", "B"].into_iter().enumerate() {
+        channels.push(channel(name.to_string(), image, component));
+    }
+    for (aov, layer) in aovs {
+        for (component, name) in aov.channels().iter().enumerate() {
+            channels.push(channel(format!("{}.{name}", aov.name()), layer, component));
+        }
+    }
+    let size = (image.width as usize, image.height as usize);
+    let layer = Layer::new(
+        size,
+        LayerAttributes::default(),
+        Encoding::SMALL_LOSSLESS,
+        AnyChannels::sort(channels),
+    );
+    Image::from_layer(layer)
+        .write()
+        .to_file(path)
+        .map_err(|source| TracerError::ExrError {
+            path: path.into(),
+            source,
+        })
+}
+
+/// Reads the layer of `aov` from an exr written by `write_layered_exr`, `None` if the file doesn't
+/// have all its channels.
+pub fn read_exr_layer<P: AsRef<Path>>(path: P, aov: Aov) -> TracerResult<Option<Framebuffer>> {
+    let path = path.as_ref();
+    let image = read_first_flat_layer_from_file(path).map_err(|source| TracerError::ExrError {
+        path: path.into(),
+        source,
+    })?;
+    let layer = image.layer_data;
+    let (width, height) = (layer.size.width(), layer.size.height());
+    let mut pixels = vec![Vec3A::ZERO; width * height];
+    for (component, name) in aov.channels().iter().enumerate() {
+        let name = format!("{}.{name}", aov.name());
+        let channel = layer
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.name.to_string() == name);
+        let Some(channel) = channel else {
+            return Ok(None);
+        };
+        for (pixel, value) in pixels.iter_mut().zip(channel.sample_data.values_as_f32()) {
+            pixel[component] = value;
+        }
+    }
+    // single values are repeated like in `AovPixel::value`.
+    if aov.channels().len() == 1 {
+        pixels
+            .iter_mut()
+            .for_each(|pixel| *pixel = Vec3A::splat(pixel.x));
+    }
+    Ok(Some(Framebuffer::from_pixels(
+        width as u32,
+        height as u32,
+        pixels,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exr::prelude::{read, ReadChannels, ReadLayers};
+    use std::fs;
+
+    #[test]
+    fn layers_round_trip_through_exr() {
+        let (width, height) = (3, 2);
+        let layer = |offset: f32| {
+            let pixels = (0..width * height)
+                .map(|i| Vec3A::new(i as f32, offset, -offset * i as f32))
+                .collect();
+            Framebuffer::from_pixels(width, height, pixels)
+        };
+        let image = layer(0.5);
+        let aovs = vec![
+            (Aov::Depth, layer(1.0)),
+            (Aov::Uv, layer(2.0)),
+            (Aov::Normal, layer(3.0)),
+        ];
+        let path = std::env::temp_dir().join(format!("tracer-{}-layers.exr", std::process::id()));
+        write_layered_exr(&path, &image, &aovs).unwrap();
+        let read = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_file(&path)
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let layer = &read.layer_data;
+        assert_eq!(layer.size.width(), width as usize);
+        assert_eq!(layer.size.height(), height as usize);
+        let channel = |name: &str| -> Vec<f32> {
+            let channel = layer
+                .channel_data
+                .list
+                .iter()
+                .find(|channel| channel.name.to_string() == name)
+                .unwrap_or_else(|| panic!("no channel {name}"));
+            channel.sample_data.values_as_f32().collect()
+        };
+        let expected = |layer: &Framebuffer, component: usize| -> Vec<f32> {
+            layer.pixels.iter().map(|p| p[component]).collect()
+        };
+        assert_eq!(layer.channel_data.list.len(), 3 + 1 + 2 + 3);
+        assert_eq!(channel("R"), expected(&image, 0));
+        assert_eq!(channel("B"), expected(&image, 2));
+        assert_eq!(channel("depth.Z"), expected(&aovs[0].1, 0));
+        assert_eq!(channel("uv.U"), expected(&aovs[1].1, 0));
+        assert_eq!(channel("uv.V"), expected(&aovs[1].1, 1));
+        assert_eq!(channel("normal.X"), expected(&aovs[2].1, 0));
+        assert_eq!(channel("normal.Z"), expected(&aovs[2].1, 2));
+    }
+
+    #[test]
+    fn pixels_average_samples_but_keep_the_first_id() {
+        let hit = |depth: f32, material: usize| AovSample {
+            depth,
+            normal: Vec3A::Z,
+            position: Vec3A::ZERO,
+            uv: Vec2::new(depth, 0.0),
+            albedo: Vec3A::ONE,
+            material: Some(material),
+            object: Some(0),
+        };
+        let mut pixel = AovPixel::default();
+        pixel.add(&hit(1.0, 4));
+        pixel.add(&hit(3.0, 7));
+        pixel.add(&AovSample::miss(Vec3A::ZERO));
+        pixel.add(&hit(4.0, 7));
+        assert_eq!(pixel.value(Aov::Depth), Vec3A::splat(2.0));
+        assert_eq!(pixel.value(Aov::Uv), Vec3A::new(2.0, 0.0, 0.0));
+        assert_eq!(pixel.value(Aov::MaterialId), Vec3A::splat(4.0));
+        assert_eq!(AovPixel::default().value(Aov::ObjectId), Vec3A::splat(-1.0));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::distribution::Distribution2d;
+use crate::error::{TracerError, TracerResult};
+use crate::texture::image::Image;
+use crate::validation::Validator;
+use crate::vec3;
+use glam::{Quat, Vec2, Vec3A};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+/// Equirectangular image wrapped around the scene, its top row straight up and its center
+/// towards -z.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EnvironmentBuilder {
+    /// hdr and exr images are linear, 8 bit images are decoded from sRGB.
+    pub path: String,
+    /// Turns the image about the y axis, in degrees.
+    #[serde(default)]
+    pub rotation: f32,
+    /// Scales the image's colors.
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+}
+
+impl EnvironmentBuilder {
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.file(format!("{location}.path"), &self.path);
+        validator.check(
+            self.rotation.is_finite(),
+            format!("{location}.rotation"),
+            "rotation must be finite",
+        );
+        validator.check(
+            self.intensity.is_finite() && self.intensity >= 0.0,
+            format!("{location}.intensity"),
+            "intensity must not be negative",
+        );
+    }
+}
+
+impl TryInto<EnvironmentMap> for EnvironmentBuilder {
+    type Error = TracerError;
+
+    fn try_into(self) -> TracerResult<EnvironmentMap> {
+        let image = Image::new(&self.path)?;
+        let pixels = image
+            .linear_pixels()
+            .into_iter()
+            .map(|color| color * self.intensity)
+            .collect();
+        Ok(EnvironmentMap::new(
+            image.width,
+            image.height,
+            pixels,
+            self.rotation.to_radians(),
+        ))
+    }
+}
+
+/// Equirectangular image of the light arriving from every direction, sampled in proportion to
+/// the luminance of its pixels.
+pub struct EnvironmentMap {
+    width: u32,
+    height: u32,
+    /// Linear colors in row major order from the top left.
+    pixels: Vec<Vec3A>,
+    /// Turns directions of the scene into directions of the image.
+    to_image: Quat,
+    distribution: Distribution2d,
+}
+
+impl EnvironmentMap {
+    /// Image of `width` by `height` `pixels` turned by `rotation` radians about the y axis.
+    pub fn new(width: u32, height: u32, pixels: Vec<Vec3A>, rotation: f32) -> Self {
+        // rows near the poles cover less of the sphere than the ones at the horizon.
+        let weights = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| {
+                let row = i as u32 / width;
+                let sin_theta = (PI * (row as f32 + 0.5) / height as f32).sin();
+                vec3::luminance(color).max(0.0) * sin_theta
+            })
+            .collect::<Vec<f32>>();
+        Self {
+            width,
+            height,
+            pixels,
+            to_image: Quat::from_rotation_y(-rotation),
+            distribution: Distribution2d::new(&weights, width as usize, height as usize),
+        }
+    }
+
+    /// Position on the image in `[0, 1)²` from the top left seen along `direction`.
+    fn uv(&self, direction: Vec3A) -> Vec2 {
+        let d = (self.to_image * direction).normalize();
+        let phi = d.x.atan2(-d.z);
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+        Vec2::new(phi / (2.0 * PI) + 0.5, theta / PI)
+    }
+
+    /// Direction of the scene seeing `uv`.
+    fn direction(&self, uv: Vec2) -> Vec3A {
+        let phi = (uv.x - 0.5) * 2.0 * PI;
+        let theta = uv.y * PI;
+        let d = Vec3A::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        );
+        self.to_image.inverse() * d
+    }
+
+    pub fn value(&self, direction: Vec3A) -> Vec3A {
+        let uv = self.uv(direction);
+        let x = ((uv.x * self.width as f32) as u32).min(self.width - 1);
+        let y = ((uv.y * self.height as f32) as u32).min(self.height - 1);
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Direction picked with `u`, bright parts of the image more often.
+    pub fn random(&self, u: Vec2) -> Vec3A {
+        let (uv, _) = self.distribution.sample(u);
+        self.direction(uv)
+    }
+
+    /// Density over solid angle of `random` picking `direction`.
+    pub fn pdf_value(&self, direction: Vec3A) -> f32 {
+        let d = (self.to_image * direction).normalize();
+        // from x and z rather than the angle, which loses its precision near the poles.
+        let sin_theta = (d.x * d.x + d.z * d.z).sqrt();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        // the image spans 2π by π radians.
+        self.distribution.pdf(self.uv(direction)) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random;
+    use rand::Rng;
+
+    /// Small map with a bright spot and one black pixel, turned so the seam isn't at -z.
+    fn map() -> EnvironmentMap {
+        let (width, height) = (16, 8);
+        let pixels = (0..width * height)
+            .map(|i| match i {
+                37 => Vec3A::splat(50.0),
+                90 => Vec3A::ZERO,
+                i => Vec3A::new(0.2, 0.5, 1.0) * (1.0 + (i % 5) as f32),
+            })
+            .collect();
+        EnvironmentMap::new(width, height, pixels, 0.7)
+    }
+
+    #[test]
+    fn pdf_integrates_to_one_over_the_sphere() {
+        let map = map();
+        let (rows, columns) = (256, 512);
+        let d_theta = PI / rows as f32;
+        let d_phi = 2.0 * PI / columns as f32;
+        let mut integral = 0.0;
+        for row in 0..rows {
+            let theta = (row as f32 + 0.5) * d_theta;
+            for column in 0..columns {
+                let phi = (column as f32 + 0.5) * d_phi;
+                let direction = Vec3A::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                integral += map.pdf_value(direction) * theta.sin() * d_theta * d_phi;
+            }
+        }
+        assert!(
+            (integral - 1.0).abs() < 0.01,
+            "pdf integrates to {integral}"
+        );
+    }
+
+    #[test]
+    fn pdf_matches_random() {
+        let map = map();
+        let mut rng = random::seeded_rng(24);
+        for _ in 0..10_000 {
+            let u = Vec2::new(rng.gen(), rng.gen());
+            let direction = map.random(u);
+            let (uv, pdf) = map.distribution.sample(u);
+            let expected = pdf / (2.0 * PI * PI * (uv.y * PI).sin());
+            let pdf = map.pdf_value(direction);
+            assert!(pdf > 0.0);
+            assert!(
+                (pdf - expected).abs() <= 1e-3 * expected,
+                "pdf {pdf} of {direction} is not {expected}"
+            );
+            // the black pixel is never picked.
+            let uv = map.uv(direction);
+            let pixel = (uv.y * 8.0) as usize * 16 + (uv.x * 16.0) as usize;
+            assert_ne!(pixel, 90);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+pub mod environment;
+pub mod sky;
+
+use crate::background::environment::{EnvironmentBuilder, EnvironmentMap};
+use crate::background::sky::{Sky, SkyBuilder};
+use crate::error::{TracerError, TracerResult};
+use crate::validation::Validator;
+use crate::vec3;
+use glam::{Vec2, Vec3A};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// What rays leaving the scene see, `settings.background_color` when the scene has none.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum BackgroundBuilder {
+    Environment(EnvironmentBuilder),
+    Sky(SkyBuilder),
+    Gradient(Gradient),
+}
+
+impl BackgroundBuilder {
+    /// Records every problem with this background, `location` is its json path.
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        match self {
+            BackgroundBuilder::Environment(e) => {
+                e.validate(&format!("{location}.Environment"), validator)
+            }
+            BackgroundBuilder::Sky(s) => s.validate(&format!("{location}.Sky"), validator),
+            BackgroundBuilder::Gradient(_) => {}
+        }
+    }
+}
+
+impl TryInto<Background> for BackgroundBuilder {
+    type Error = TracerError;
+
+    fn try_into(self) -> TracerResult<Background> {
+        match self {
+            BackgroundBuilder::Environment(e) => {
+                Ok(Background::Environment(Arc::new(e.try_into()?)))
+            }
+            BackgroundBuilder::Sky(s) => Ok(Background::Sky(Arc::new(s.into()))),
+            BackgroundBuilder::Gradient(g) => Ok(Background::Gradient(g)),
+        }
+    }
+}
+
+fn default_bottom() -> Vec3A {
+    Vec3A::ONE
+}
+
+fn default_top() -> Vec3A {
+    Vec3A::new(0.5, 0.7, 1.0)
+}
+
+/// Blend from `bottom` straight down to `top` straight up, the white to blue sky of the book by
+/// default.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Gradient {
+    #[serde(default = "default_bottom")]
+    pub bottom: Vec3A,
+    #[serde(default = "default_top")]
+    pub top: Vec3A,
+}
+
+impl Gradient {
+    pub fn value(&self, direction: Vec3A) -> Vec3A {
+        let t = 0.5 * (direction.normalize().y + 1.0);
+        (1.0 - t) * self.bottom + t * self.top
+    }
+}
+
+/// Cloning shares the images and skies.
+#[derive(Clone)]
+pub enum Background {
+    Color(Vec3A),
+    Gradient(Gradient),
+    Environment(Arc<EnvironmentMap>),
+    Sky(Arc<Sky>),
+}
+
+impl Background {
+    /// Light arriving from `direction`.
+    pub fn value(&self, direction: Vec3A) -> Vec3A {
+        match self {
+            Background::Color(color) => *color,
+            Background::Gradient(gradient) => gradient.value(direction),
+            Background::Environment(environment) => environment.value(direction),
+            Background::Sky(sky) => sky.value(direction),
+        }
+    }
+
+    /// Whether the lights sample the background, the image of an environment or the sun of a
+    /// sky. Colors and gradients are only found by bouncing.
+    pub fn is_sampled(&self) -> bool {
+        matches!(self, Background::Environment(_) | Background::Sky(_))
+    }
+
+    /// Direction picked with `u`, uniformly for backgrounds that aren't sampled.
+    pub fn random(&self, u: Vec2) -> Vec3A {
+        match self {
+            Background::Color(_) | Background::Gradient(_) => vec3::sample_unit_sphere(u),
+            Background::Environment(environment) => environment.random(u),
+            Background::Sky(sky) => sky.random(u),
+        }
+    }
+
+    /// Density over solid angle of `random` picking `direction`.
+    pub fn pdf_value(&self, direction: Vec3A) -> f32 {
+        match self {
+            Background::Color(_) | Background::Gradient(_) => 1.0 / (4.0 * PI),
+            Background::Environment(environment) => environment.pdf_value(direction),
+            Background::Sky(sky) => sky.pdf_value(direction),
+        }
+    }
+}
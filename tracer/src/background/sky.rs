@@ -0,0 +1,376 @@
+use crate::validation::Validator;
+use glam::{Vec2, Vec3A};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+/// Illuminance of the sun above the atmosphere, in klx like the sky's luminance is in kcd/m².
+const SOLAR_ILLUMINANCE: f32 = 128.0;
+
+fn default_turbidity() -> f32 {
+    3.0
+}
+
+fn default_ground_albedo() -> Vec3A {
+    Vec3A::splat(0.3)
+}
+
+fn default_sun_radius() -> f32 {
+    0.27
+}
+
+fn default_intensity() -> f32 {
+    0.05
+}
+
+/// Clear sky of Preetham et al. 1999 with a sun disc, above a diffuse ground lit by both.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SkyBuilder {
+    /// Towards the sun, has to be above the horizon.
+    pub sun_direction: Vec3A,
+    /// Haziness of the air, from `2` for a very clear sky to `10` for a hazy one.
+    #[serde(default = "default_turbidity")]
+    pub turbidity: f32,
+    /// Color of the ground below the horizon.
+    #[serde(default = "default_ground_albedo")]
+    pub ground_albedo: Vec3A,
+    /// Angular radius of the sun in degrees, larger suns cast softer shadows with the same light.
+    #[serde(default = "default_sun_radius")]
+    pub sun_radius: f32,
+    /// Scales the sky and the sun, the sky's luminance is in kcd/m² which is too bright for
+    /// exposure `0`.
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+}
+
+impl SkyBuilder {
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.check(
+            self.sun_direction.is_finite() && self.sun_direction.y > 0.0,
+            format!("{location}.sun_direction"),
+            "sun_direction must point above the horizon",
+        );
+        validator.check(
+            (1.7..=10.0).contains(&self.turbidity),
+            format!("{location}.turbidity"),
+            "turbidity must be between 1.7 and 10",
+        );
+        validator.check(
+            self.ground_albedo.cmpge(Vec3A::ZERO).all()
+                && self.ground_albedo.cmple(Vec3A::ONE).all(),
+            format!("{location}.ground_albedo"),
+            "ground_albedo must be between 0 and 1",
+        );
+        validator.check(
+            self.sun_radius > 0.0 && self.sun_radius < 90.0,
+            format!("{location}.sun_radius"),
+            "sun_radius must be between 0 and 90 degrees",
+        );
+        validator.check(
+            self.intensity.is_finite() && self.intensity >= 0.0,
+            format!("{location}.intensity"),
+            "intensity must not be negative",
+        );
+    }
+}
+
+impl From<SkyBuilder> for Sky {
+    fn from(builder: SkyBuilder) -> Self {
+        Sky::new(
+            builder.sun_direction,
+            builder.turbidity,
+            builder.ground_albedo,
+            builder.sun_radius.to_radians(),
+            builder.intensity,
+        )
+    }
+}
+
+/// Perez et al.'s model of the sky's luminance relative to the zenith.
+#[derive(Debug, Clone, Copy)]
+struct Perez {
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    e: f32,
+}
+
+impl Perez {
+    /// Coefficients linear in `turbidity`, each given as its slope and offset.
+    fn new(turbidity: f32, coefficients: [(f32, f32); 5]) -> Self {
+        let [a, b, c, d, e] = coefficients.map(|(slope, offset)| slope * turbidity + offset);
+        Self { a, b, c, d, e }
+    }
+
+    /// `theta` is the angle from the zenith, `gamma` from the sun.
+    fn evaluate(&self, cos_theta: f32, gamma: f32) -> f32 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a * (self.b / cos_theta.max(1e-4)).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma)
+    }
+}
+
+/// Light of a clear sky, see `SkyBuilder`.
+pub struct Sky {
+    sun: Vec3A,
+    cos_sun_radius: f32,
+    sun_radiance: Vec3A,
+    /// Perez models of the luminance Y and the chromaticities x and y.
+    perez: [Perez; 3],
+    /// Y, x and y at the zenith over the Perez models there.
+    zenith: [f32; 3],
+    ground: Vec3A,
+    intensity: f32,
+}
+
+impl Sky {
+    /// Sky with the sun towards `sun_direction`, `sun_radius` in radians.
+    pub fn new(
+        sun_direction: Vec3A,
+        turbidity: f32,
+        ground_albedo: Vec3A,
+        sun_radius: f32,
+        intensity: f32,
+    ) -> Self {
+        let sun = sun_direction.normalize();
+        let theta_s = sun.y.clamp(0.0, 1.0).acos();
+        let t = turbidity;
+        let perez = [
+            Perez::new(
+                t,
+                [
+                    (0.1787, -1.4630),
+                    (-0.3554, 0.4275),
+                    (-0.0227, 5.3251),
+                    (0.1206, -2.5771),
+                    (-0.0670, 0.3703),
+                ],
+            ),
+            Perez::new(
+                t,
+                [
+                    (-0.0193, -0.2592),
+                    (-0.0665, 0.0008),
+                    (-0.0004, 0.2125),
+                    (-0.0641, -0.8989),
+                    (-0.0033, 0.0452),
+                ],
+            ),
+            Perez::new(
+                t,
+                [
+                    (-0.0167, -0.2608),
+                    (-0.0950, 0.0092),
+                    (-0.0079, 0.2102),
+                    (-0.0441, -1.6537),
+                    (-0.0109, 0.0529),
+                ],
+            ),
+        ];
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let theta = Vec3A::new(theta_s.powi(3), theta_s.powi(2), theta_s);
+        let polynomial = |t2: Vec3A, t1: Vec3A, t0: Vec3A, offset: f32| {
+            t * t * t2.dot(theta) + t * t1.dot(theta) + t0.dot(theta) + offset
+        };
+        let x = polynomial(
+            Vec3A::new(0.00166, -0.00375, 0.00209),
+            Vec3A::new(-0.02903, 0.06377, -0.03202),
+            Vec3A::new(0.11693, -0.21196, 0.06052),
+            t * 0.00394 + 0.25886,
+        );
+        let y = polynomial(
+            Vec3A::new(0.00275, -0.00610, 0.00317),
+            Vec3A::new(-0.04214, 0.08970, -0.04153),
+            Vec3A::new(0.15346, -0.26756, 0.06670),
+            t * 0.00516 + 0.26688,
+        );
+        let mut zenith = [luminance, x, y];
+        for (zenith, perez) in zenith.iter_mut().zip(&perez) {
+            *zenith /= perez.evaluate(1.0, theta_s);
+        }
+        let solid_angle = 2.0 * PI * (1.0 - sun_radius.cos());
+        let sun_illuminance = SOLAR_ILLUMINANCE * sun_transmittance(theta_s, turbidity);
+        let mut sky = Self {
+            sun,
+            cos_sun_radius: sun_radius.cos(),
+            sun_radiance: sun_illuminance / solid_angle,
+            perez,
+            zenith,
+            ground: Vec3A::ZERO,
+            intensity,
+        };
+        sky.ground = ground_albedo * (sky.sky_irradiance() + sun_illuminance * sun.y) / PI;
+        sky
+    }
+
+    /// Light of the sky from `direction` above the horizon, without the sun.
+    fn sky(&self, direction: Vec3A) -> Vec3A {
+        let cos_theta = direction.y;
+        let gamma = direction.dot(self.sun).clamp(-1.0, 1.0).acos();
+        let [luminance, x, y] =
+            [0, 1, 2].map(|i| self.zenith[i] * self.perez[i].evaluate(cos_theta, gamma));
+        xyy_to_rgb(x, y, luminance)
+    }
+
+    /// Light of the sky on the ground, summed over the sky's hemisphere.
+    fn sky_irradiance(&self) -> Vec3A {
+        let (rows, columns) = (32, 64);
+        let d_theta = FRAC_PI_2 / rows as f32;
+        let d_phi = 2.0 * PI / columns as f32;
+        let mut irradiance = Vec3A::ZERO;
+        for row in 0..rows {
+            let theta = (row as f32 + 0.5) * d_theta;
+            for column in 0..columns {
+                let phi = (column as f32 + 0.5) * d_phi;
+                let direction = Vec3A::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                irradiance += self.sky(direction) * theta.cos() * theta.sin() * d_theta * d_phi;
+            }
+        }
+        irradiance
+    }
+
+    pub fn value(&self, direction: Vec3A) -> Vec3A {
+        let direction = direction.normalize();
+        if direction.y <= 0.0 {
+            return self.ground * self.intensity;
+        }
+        let mut value = self.sky(direction);
+        if direction.dot(self.sun) >= self.cos_sun_radius {
+            value += self.sun_radiance;
+        }
+        value * self.intensity
+    }
+
+    /// Direction towards a point on the sun picked with `u`.
+    pub fn random(&self, u: Vec2) -> Vec3A {
+        let z = 1.0 + u.y * (self.cos_sun_radius - 1.0);
+        let phi = 2.0 * PI * u.x;
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        let (a, b) = self.sun.any_orthonormal_pair();
+        a * phi.cos() * sin_theta + b * phi.sin() * sin_theta + self.sun * z
+    }
+
+    /// Density over solid angle of `random` picking `direction`.
+    pub fn pdf_value(&self, direction: Vec3A) -> f32 {
+        if direction.normalize().dot(self.sun) < self.cos_sun_radius {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * (1.0 - self.cos_sun_radius))
+    }
+}
+
+/// Part of the sun's light getting through the atmosphere at `theta_s` from the zenith, from
+/// scattering by air and by haze, at the wavelengths of red, green and blue.
+fn sun_transmittance(theta_s: f32, turbidity: f32) -> Vec3A {
+    // relative length of the path through the atmosphere, Kasten's formula.
+    let air_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_s.to_degrees()).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let wavelengths = Vec3A::new(0.68, 0.55, 0.44);
+    let rayleigh = wavelengths
+        .to_array()
+        .map(|l| (-0.008735 * l.powf(-4.08) * air_mass).exp());
+    let haze = wavelengths
+        .to_array()
+        .map(|l| (-beta * l.powf(-1.3) * air_mass).exp());
+    Vec3A::from(rayleigh) * Vec3A::from(haze)
+}
+
+/// Linear sRGB of the CIE chromaticity `x`, `y` and luminance `luminance`.
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Vec3A {
+    if y <= 0.0 {
+        return Vec3A::ZERO;
+    }
+    let xyz = Vec3A::new(x * luminance / y, luminance, (1.0 - x - y) * luminance / y);
+    Vec3A::new(
+        Vec3A::new(3.2406, -1.5372, -0.4986).dot(xyz),
+        Vec3A::new(-0.9689, 1.8758, 0.0415).dot(xyz),
+        Vec3A::new(0.0557, -0.2040, 1.0570).dot(xyz),
+    )
+    .max(Vec3A::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random;
+    use rand::Rng;
+
+    fn sky() -> Sky {
+        Sky::new(
+            Vec3A::new(0.4, 0.5, -0.3),
+            3.0,
+            Vec3A::splat(0.3),
+            2f32.to_radians(),
+            1.0,
+        )
+    }
+
+    /// Direction `theta` from the sun and `phi` around it.
+    fn around_sun(sky: &Sky, theta: f32, phi: f32) -> Vec3A {
+        let (a, b) = sky.sun.any_orthonormal_pair();
+        (a * phi.cos() + b * phi.sin()) * theta.sin() + sky.sun * theta.cos()
+    }
+
+    #[test]
+    fn pdf_integrates_to_one_over_the_sphere() {
+        let sky = sky();
+        // the disc is tiny, so integrate over the cap around the sun twice its size.
+        let cap = 2.0 * sky.cos_sun_radius.acos();
+        let (rows, columns) = (512, 64);
+        let d_theta = cap / rows as f32;
+        let d_phi = 2.0 * PI / columns as f32;
+        let mut integral = 0.0;
+        for row in 0..rows {
+            let theta = (row as f32 + 0.5) * d_theta;
+            for column in 0..columns {
+                let phi = (column as f32 + 0.5) * d_phi;
+                let direction = around_sun(&sky, theta, phi);
+                integral += sky.pdf_value(direction) * theta.sin() * d_theta * d_phi;
+            }
+        }
+        assert!(
+            (integral - 1.0).abs() < 0.01,
+            "pdf integrates to {integral}"
+        );
+        assert_eq!(sky.pdf_value(around_sun(&sky, 1.1 * cap / 2.0, 0.3)), 0.0);
+    }
+
+    #[test]
+    fn sampling_the_sun_finds_its_light() {
+        let sky = sky();
+        let mut rng = random::seeded_rng(25);
+        let samples = 10_000;
+        let mut estimate = Vec3A::ZERO;
+        for _ in 0..samples {
+            let direction = sky.random(Vec2::new(rng.gen(), rng.gen()));
+            assert!(direction.is_normalized());
+            assert!(direction.dot(sky.sun) >= sky.cos_sun_radius - 1e-5);
+            let pdf = sky.pdf_value(direction);
+            assert!(pdf > 0.0);
+            estimate += (sky.value(direction) - sky.sky(direction)) / pdf;
+        }
+        let estimate = estimate / samples as f32;
+        // the disc adds the sun's light through the atmosphere, spread over its solid angle.
+        let theta_s = sky.sun.y.acos();
+        let expected = SOLAR_ILLUMINANCE * sun_transmittance(theta_s, 3.0);
+        assert!(
+            (estimate - expected).abs().max_element() < 1e-3 * expected.max_element(),
+            "sampling the sun gives {estimate}, not {expected}"
+        );
+    }
+
+    #[test]
+    fn sun_is_brighter_than_the_sky_and_the_ground() {
+        let sky = sky();
+        let sun = sky.value(sky.sun);
+        let beside = sky.value(around_sun(&sky, 0.2, 0.0));
+        let ground = sky.value(-Vec3A::Y);
+        assert!(sun.cmpgt(beside).all() && beside.cmpgt(Vec3A::ZERO).all());
+        assert!(ground.cmpgt(Vec3A::ZERO).all() && ground.cmplt(sun).all());
+    }
+}
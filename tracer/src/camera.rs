@@ -1,4 +1,6 @@
 use crate::intersection::ray::Ray;
+use crate::sampler::Sampler;
+use crate::validation::Validator;
 use crate::vec3;
 use glam::Vec3A;
 
@@ -47,6 +49,7 @@ pub struct CamerBuilder {
 }
 
 impl CamerBuilder {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         look_from: Vec3A,
         look_at: Vec3A,
@@ -70,8 +73,48 @@ impl CamerBuilder {
             time1,
         }
     }
-    pub fn build(&self) -> Camera {
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        let view = self.look_from - self.look_at;
+        validator.check(
+            view.length_squared() > 0.0,
+            format!("{location}.look_at"),
+            "look_at must differ from look_from",
+        );
+        validator.check(
+            self.vup.cross(view).length_squared() > 0.0,
+            format!("{location}.vup"),
+            "vup must not be zero or parallel to the view direction",
+        );
+        validator.check(
+            self.vfov > 0.0 && self.vfov < 180.0,
+            format!("{location}.vfov"),
+            "vfov must be between 0 and 180 degrees",
+        );
+        validator.check(
+            self.aspect_ratio > 0.0,
+            format!("{location}.aspect_ratio"),
+            "aspect_ratio must be positive",
+        );
+        validator.check(
+            self.aperture >= 0.0,
+            format!("{location}.aperture"),
+            "aperture must not be negative",
+        );
+        if let Some(focus_dist) = self.focus_dist {
+            validator.check(
+                focus_dist > 0.0,
+                format!("{location}.focus_dist"),
+                "focus_dist must be positive",
+            );
+        }
+        validator.check(
+            self.time0 <= self.time1,
+            format!("{location}.time1"),
+            "time1 must not be before time0",
+        );
+    }
 
+    pub fn build(&self) -> Camera {
         let focus_dist = if let Some(v) = self.focus_dist {
             v
         } else {
@@ -106,14 +149,7 @@ pub struct Camera {
 }
 
 impl Camera {
-    pub(crate) fn as_ray(&self, u: f32, v: f32) -> Ray {
-        Ray::new(
-            self.origin,
-            self.lower_left_corner + self.horizontal * u + self.vertical * v - self.origin,
-            self.time0 + rand::random::<f32>() * (self.time1 - self.time0),
-        )
-    }
-
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         look_from: Vec3A,
         look_at: Vec3A,
@@ -153,13 +189,14 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, u: f32, v: f32) -> Ray {
-        let rd = vec3::random_in_unit_disk() * self.lens_radius;
+    /// Ray through `(u, v)` on the image plane, the lens position and time come from `sampler`.
+    pub fn get_ray(&self, u: f32, v: f32, sampler: &mut dyn Sampler) -> Ray {
+        let rd = vec3::sample_unit_disk(sampler.get_2d()) * self.lens_radius;
         let offset = self.u * rd.x + self.v * rd.y;
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + self.horizontal * u + self.vertical * v - self.origin - offset,
-            self.time0 + rand::random::<f32>() * (self.time1 - self.time0),
+            self.time0 + sampler.get_1d() * (self.time1 - self.time0),
         )
     }
 }
@@ -0,0 +1,315 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use glam::Vec3A;
+
+use tracer::{Aov, BvhSplit, FilterType, RenderSettings, SamplerType, TileOrder, ToneMapper};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "tracer",
+    version,
+    about = "Render ray traced scenes described in json"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Render a scene to an image.
+    Render {
+        /// Path to the scene json.
+        scene: PathBuf,
+        #[command(flatten)]
+        overrides: SettingsOverrides,
+        /// Don't print progress and statistics.
+        #[arg(short, long)]
+        quiet: bool,
+    },
+    /// Load and build a scene without rendering it.
+    Validate {
+        /// Path to the scene json.
+        scene: PathBuf,
+    },
+    /// Print a summary of a scene.
+    Info {
+        /// Path to the scene json.
+        scene: PathBuf,
+        #[command(flatten)]
+        overrides: SettingsOverrides,
+    },
+    /// Denoise a previously rendered hdr image.
+    Denoise {
+        /// Image to denoise, the `albedo` and `normal` layers of an exr are used as guides.
+        input: PathBuf,
+        /// Output image path, the format is picked from the extension.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Albedo guide, like the `<name>.albedo.exr` saved next to a render.
+        #[arg(long)]
+        albedo: Option<PathBuf>,
+        /// Normal guide, like the `<name>.normal.exr` saved next to a render.
+        #[arg(long)]
+        normal: Option<PathBuf>,
+        /// Number of filter iterations, each one doubles the filter's reach.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..=10))]
+        iterations: Option<u32>,
+    },
+}
+
+/// Command line overrides for the scene's `RenderSettings`.
+#[derive(Debug, Args)]
+pub struct SettingsOverrides {
+    /// Output image width in pixels.
+    #[arg(short = 'w', long)]
+    pub image_width: Option<u32>,
+    /// Samples per pixel.
+    #[arg(short, long)]
+    pub samples: Option<u32>,
+    /// Maximum number of bounces per ray.
+    #[arg(short = 'd', long)]
+    pub max_depth: Option<u32>,
+    /// Output image path, the format is picked from the extension.
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// Background color as `r,g,b`.
+    #[arg(short, long, value_parser = parse_color)]
+    pub background_color: Option<Vec3A>,
+    /// Trace against the flat object list instead of building a bvh.
+    #[arg(long)]
+    pub no_bvh: bool,
+    /// How the bvh is split, `sah` or `median`.
+    #[arg(long, value_parser = parse_bvh_split)]
+    pub bvh_split: Option<BvhSplit>,
+    /// Seed of the render's random numbers.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Pixel sampler, `independent`, `stratified`, `halton` or `sobol`.
+    #[arg(long, value_parser = parse_sampler)]
+    pub sampler: Option<SamplerType>,
+    /// Reconstruction filter, `box`, `tent`, `gaussian`, `mitchell` or `lanczos`.
+    #[arg(long, value_parser = parse_filter)]
+    pub filter: Option<FilterType>,
+    /// Radius of the reconstruction filter in pixels.
+    #[arg(long)]
+    pub filter_radius: Option<f32>,
+    /// Exposure of 8 bit outputs in stops.
+    #[arg(long, allow_hyphen_values = true)]
+    pub exposure: Option<f32>,
+    /// Tone mapper of 8 bit outputs, `clamp`, `reinhard`, `extended-reinhard`, `hable` or `aces`.
+    #[arg(long, value_parser = parse_tone_mapper)]
+    pub tone_mapper: Option<ToneMapper>,
+    /// Sample adaptively until the relative error of every pixel is below this.
+    #[arg(long)]
+    pub adaptive_threshold: Option<f32>,
+    /// Most samples per pixel when sampling adaptively.
+    #[arg(long)]
+    pub max_samples: Option<u32>,
+    /// Save an image of the samples taken per pixel when sampling adaptively.
+    #[arg(long)]
+    pub sample_map: Option<String>,
+    /// Extra passes, any of `depth`, `normal`, `position`, `uv`, `material-id`, `object-id` and
+    /// `albedo`.
+    #[arg(long, value_delimiter = ',', value_parser = parse_aov)]
+    pub aov: Vec<Aov>,
+    /// Save the passes as separate images even when the output is an exr.
+    #[arg(long)]
+    pub aov_files: bool,
+    /// Denoise the image using the albedo and normal of the first hits as guides.
+    #[arg(long)]
+    pub denoise: bool,
+    /// Width and height of the render tiles in pixels.
+    #[arg(long)]
+    pub tile_size: Option<u32>,
+    /// Order tiles are rendered in, `scanline`, `spiral` or `hilbert`.
+    #[arg(long, value_parser = parse_tile_order)]
+    pub tile_order: Option<TileOrder>,
+    /// Render threads, all cores by default.
+    #[arg(short = 'j', long)]
+    pub threads: Option<usize>,
+    /// Sample lights directly at every diffuse bounce instead of only finding them by bouncing
+    /// into them.
+    #[arg(long)]
+    pub light_sampling: bool,
+    /// Bounces after which paths are ended at random, off by default.
+    #[arg(long)]
+    pub roulette_depth: Option<u32>,
+    /// Highest luminance of the light a sample reflects, brighter ones are scaled down to it.
+    #[arg(long)]
+    pub clamp: Option<f32>,
+}
+
+impl SettingsOverrides {
+    pub fn apply(&self, settings: &mut RenderSettings) {
+        if let Some(image_width) = self.image_width {
+            settings.image_width = image_width;
+        }
+        if let Some(samples) = self.samples {
+            settings.samples = samples;
+        }
+        if let Some(max_depth) = self.max_depth {
+            settings.max_depth = max_depth;
+        }
+        if let Some(output) = &self.output {
+            settings.path = output.clone();
+        }
+        if let Some(background_color) = self.background_color {
+            settings.background_color = background_color;
+        }
+        if self.no_bvh {
+            settings.bvh = false;
+        }
+        if let Some(bvh_split) = self.bvh_split {
+            settings.bvh_split = bvh_split;
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = seed;
+        }
+        if let Some(sampler) = self.sampler {
+            settings.sampler = sampler;
+        }
+        if let Some(filter) = self.filter {
+            settings.filter = filter;
+        }
+        if let Some(filter_radius) = self.filter_radius {
+            settings.filter_radius = Some(filter_radius);
+        }
+        if let Some(exposure) = self.exposure {
+            settings.exposure = exposure;
+        }
+        if let Some(tone_mapper) = self.tone_mapper {
+            settings.tone_mapper = tone_mapper;
+        }
+        if !self.aov.is_empty() {
+            settings.aovs = self.aov.clone();
+        }
+        if self.aov_files {
+            settings.aov_files = true;
+        }
+        if self.denoise && settings.denoise.is_none() {
+            settings.denoise = Some(Default::default());
+        }
+        if let Some(tile_size) = self.tile_size {
+            settings.tile_size = tile_size;
+        }
+        if let Some(tile_order) = self.tile_order {
+            settings.tile_order = tile_order;
+        }
+        if let Some(threads) = self.threads {
+            settings.threads = Some(threads);
+        }
+        if self.light_sampling {
+            settings.light_sampling = true;
+        }
+        if let Some(roulette_depth) = self.roulette_depth {
+            settings.roulette_depth = Some(roulette_depth);
+        }
+        if let Some(clamp) = self.clamp {
+            settings.clamp = Some(clamp);
+        }
+        // any adaptive option turns adaptive sampling on.
+        if self.adaptive_threshold.is_some()
+            || self.max_samples.is_some()
+            || self.sample_map.is_some()
+        {
+            let adaptive = settings.adaptive.get_or_insert_with(Default::default);
+            if let Some(threshold) = self.adaptive_threshold {
+                adaptive.threshold = threshold;
+            }
+            if let Some(max_samples) = self.max_samples {
+                adaptive.max_samples = max_samples;
+            }
+            if let Some(sample_map) = &self.sample_map {
+                adaptive.sample_map = Some(sample_map.clone());
+            }
+        }
+    }
+}
+
+fn parse_color(value: &str) -> Result<Vec3A, String> {
+    let channels = value
+        .split(',')
+        .map(|c| c.trim().parse::<f32>().map_err(|e| format!("{c:?}: {e}")))
+        .collect::<Result<Vec<f32>, String>>()?;
+    match channels[..] {
+        [r, g, b] => Ok(Vec3A::new(r, g, b)),
+        _ => Err(format!(
+            "expected 3 comma separated values, got {}",
+            channels.len()
+        )),
+    }
+}
+
+fn parse_bvh_split(value: &str) -> Result<BvhSplit, String> {
+    match value {
+        "sah" => Ok(BvhSplit::Sah),
+        "median" => Ok(BvhSplit::Median),
+        _ => Err(format!("expected sah or median, got {value:?}")),
+    }
+}
+
+fn parse_filter(value: &str) -> Result<FilterType, String> {
+    match value {
+        "box" => Ok(FilterType::Box),
+        "tent" => Ok(FilterType::Tent),
+        "gaussian" => Ok(FilterType::Gaussian),
+        "mitchell" => Ok(FilterType::Mitchell),
+        "lanczos" => Ok(FilterType::Lanczos),
+        _ => Err(format!(
+            "expected box, tent, gaussian, mitchell or lanczos, got {value:?}"
+        )),
+    }
+}
+
+fn parse_tone_mapper(value: &str) -> Result<ToneMapper, String> {
+    match value {
+        "clamp" => Ok(ToneMapper::Clamp),
+        "reinhard" => Ok(ToneMapper::Reinhard),
+        "extended-reinhard" => Ok(ToneMapper::extended_reinhard()),
+        "hable" => Ok(ToneMapper::Hable),
+        "aces" => Ok(ToneMapper::Aces),
+        _ => Err(format!(
+            "expected clamp, reinhard, extended-reinhard, hable or aces, got {value:?}"
+        )),
+    }
+}
+
+fn parse_sampler(value: &str) -> Result<SamplerType, String> {
+    match value {
+        "independent" => Ok(SamplerType::Independent),
+        "stratified" => Ok(SamplerType::Stratified),
+        "halton" => Ok(SamplerType::Halton),
+        "sobol" => Ok(SamplerType::Sobol),
+        _ => Err(format!(
+            "expected independent, stratified, halton or sobol, got {value:?}"
+        )),
+    }
+}
+
+fn parse_aov(value: &str) -> Result<Aov, String> {
+    match value {
+        "depth" => Ok(Aov::Depth),
+        "normal" => Ok(Aov::Normal),
+        "position" => Ok(Aov::Position),
+        "uv" => Ok(Aov::Uv),
+        "material-id" => Ok(Aov::MaterialId),
+        "object-id" => Ok(Aov::ObjectId),
+        "albedo" => Ok(Aov::Albedo),
+        _ => Err(format!(
+            "expected depth, normal, position, uv, material-id, object-id or albedo, got {value:?}"
+        )),
+    }
+}
+
+fn parse_tile_order(value: &str) -> Result<TileOrder, String> {
+    match value {
+        "scanline" => Ok(TileOrder::Scanline),
+        "spiral" => Ok(TileOrder::Spiral),
+        "hilbert" => Ok(TileOrder::Hilbert),
+        _ => Err(format!(
+            "expected scanline, spiral or hilbert, got {value:?}"
+        )),
+    }
+}
@@ -0,0 +1,219 @@
+use crate::framebuffer::Framebuffer;
+use crate::validation::Validator;
+use glam::Vec3A;
+use rayon::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+// b3 spline of the a-trous wavelet, applied along both axes.
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// albedo channels below this aren't divided out of the color.
+const MIN_ALBEDO: f32 = 0.001;
+
+fn default_iterations() -> u32 {
+    5
+}
+
+fn default_color_sigma() -> f32 {
+    1.5
+}
+
+fn default_normal_sigma() -> f32 {
+    0.3
+}
+
+fn default_albedo_sigma() -> f32 {
+    0.1
+}
+
+/// Edge avoiding a-trous wavelet filter, Dammertz et al. 2010. Every iteration blurs with a 5x5
+/// kernel whose taps are twice as far apart as in the previous one, and skips neighbours whose
+/// color, normal or albedo differ from the pixel's. The albedo is divided out of the color before
+/// filtering and multiplied back in after, so textures stay sharp.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DenoiseSettings {
+    #[serde(default = "default_iterations")]
+    pub iterations: u32,
+    /// Color difference at which neighbours stop counting, halved every iteration. Colors are
+    /// compared after mapping them to `[0, 1]` with `c / (1 + c)`.
+    #[serde(default = "default_color_sigma")]
+    pub color_sigma: f32,
+    #[serde(default = "default_normal_sigma")]
+    pub normal_sigma: f32,
+    #[serde(default = "default_albedo_sigma")]
+    pub albedo_sigma: f32,
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        Self {
+            iterations: default_iterations(),
+            color_sigma: default_color_sigma(),
+            normal_sigma: default_normal_sigma(),
+            albedo_sigma: default_albedo_sigma(),
+        }
+    }
+}
+
+impl DenoiseSettings {
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.check(
+            (1..=10).contains(&self.iterations),
+            format!("{location}.iterations"),
+            "iterations must be between 1 and 10",
+        );
+        for (name, sigma) in [
+            ("color_sigma", self.color_sigma),
+            ("normal_sigma", self.normal_sigma),
+            ("albedo_sigma", self.albedo_sigma),
+        ] {
+            validator.check(
+                sigma.is_finite() && sigma > 0.0,
+                format!("{location}.{name}"),
+                "sigma must be positive",
+            );
+        }
+    }
+
+    /// Denoises `image`, the guides have to be the same size as it.
+    pub fn denoise(
+        &self,
+        image: &Framebuffer,
+        albedo: Option<&Framebuffer>,
+        normal: Option<&Framebuffer>,
+    ) -> Framebuffer {
+        let width = image.width as usize;
+        let height = image.height as usize;
+        let factors = match albedo {
+            Some(albedo) => albedo
+                .pixels
+                .iter()
+                .map(|&a| Vec3A::select(a.cmplt(Vec3A::splat(MIN_ALBEDO)), Vec3A::ONE, a))
+                .collect(),
+            None => vec![Vec3A::ONE; image.pixels.len()],
+        };
+        let mut current = image
+            .pixels
+            .iter()
+            .zip(&factors)
+            .map(|(&color, &factor)| color / factor)
+            .collect::<Vec<Vec3A>>();
+
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            let color_weight = edge_weight(self.color_sigma / step as f32);
+            let normal_weight = edge_weight(self.normal_sigma);
+            let albedo_weight = edge_weight(self.albedo_sigma);
+            let mut next = vec![Vec3A::ZERO; current.len()];
+            next.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+                for (x, output) in row.iter_mut().enumerate() {
+                    let center = y * width + x;
+                    let color = compress(current[center]);
+                    let mut sum = Vec3A::ZERO;
+                    let mut total = 0.0;
+                    for (j, ky) in KERNEL.iter().enumerate() {
+                        let qy = y as isize + (j as isize - 2) * step;
+                        if qy < 0 || qy >= height as isize {
+                            continue;
+                        }
+                        for (i, kx) in KERNEL.iter().enumerate() {
+                            let qx = x as isize + (i as isize - 2) * step;
+                            if qx < 0 || qx >= width as isize {
+                                continue;
+                            }
+                            let q = qy as usize * width + qx as usize;
+                            let mut weight = kx
+                                * ky
+                                * color_weight(color.distance_squared(compress(current[q])));
+                            if let Some(normal) = normal {
+                                weight *= normal_weight(
+                                    normal.pixels[center].distance_squared(normal.pixels[q]),
+                                );
+                            }
+                            if let Some(albedo) = albedo {
+                                weight *= albedo_weight(
+                                    albedo.pixels[center].distance_squared(albedo.pixels[q]),
+                                );
+                            }
+                            sum += current[q] * weight;
+                            total += weight;
+                        }
+                    }
+                    // the center tap always has a weight, total is never zero.
+                    *output = sum / total;
+                }
+            });
+            current = next;
+        }
+
+        let pixels = current
+            .iter()
+            .zip(&factors)
+            .map(|(&color, &factor)| color * factor)
+            .collect();
+        Framebuffer::from_pixels(image.width, image.height, pixels)
+    }
+}
+
+/// Gaussian falloff of a squared distance.
+fn edge_weight(sigma: f32) -> impl Fn(f32) -> f32 {
+    let scale = 1.0 / (sigma * sigma);
+    move |distance_squared| (-distance_squared * scale).exp()
+}
+
+fn compress(color: Vec3A) -> Vec3A {
+    let color = color.max(Vec3A::ZERO);
+    color / (color + 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 16;
+
+    fn image(color: impl Fn(u32, u32) -> Vec3A) -> Framebuffer {
+        let pixels = (0..SIZE * SIZE)
+            .map(|i| color(i % SIZE, i / SIZE))
+            .collect();
+        Framebuffer::from_pixels(SIZE, SIZE, pixels)
+    }
+
+    #[test]
+    fn constant_images_are_kept() {
+        let color = Vec3A::new(0.2, 0.5, 3.0);
+        let albedo = image(|_, _| Vec3A::splat(0.5));
+        let normal = image(|_, _| Vec3A::Z);
+        let denoised =
+            DenoiseSettings::default().denoise(&image(|_, _| color), Some(&albedo), Some(&normal));
+        for pixel in denoised.pixels {
+            assert!(pixel.abs_diff_eq(color, 1e-5), "{pixel}");
+        }
+    }
+
+    #[test]
+    fn guides_keep_edges_and_smooth_noise() {
+        // a noisy dark left half next to a noisy bright right half, with a matching albedo edge
+        // and a crease in the normals.
+        let left = |x| x < SIZE / 2;
+        let noise = |x: u32, y: u32| if (x + y).is_multiple_of(2) { 0.8 } else { 1.2 };
+        let noisy = image(|x, y| {
+            let base = if left(x) { 0.1 } else { 0.9 };
+            Vec3A::splat(base * noise(x, y))
+        });
+        let albedo = image(|x, _| Vec3A::splat(if left(x) { 0.1 } else { 0.9 }));
+        let normal = image(|x, _| if left(x) { Vec3A::Z } else { Vec3A::X });
+        let denoised = DenoiseSettings::default().denoise(&noisy, Some(&albedo), Some(&normal));
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let expected = if left(x) { 0.1 } else { 0.9 };
+                let pixel = denoised.get(x, y);
+                assert!(
+                    pixel.abs_diff_eq(Vec3A::splat(expected), 0.05 * expected),
+                    "{x} {y} {pixel}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use glam::Vec2;
+
+/// Piecewise constant density over `[0, 1)`, every interval is picked in proportion to its
+/// value.
+#[derive(Debug, Clone)]
+pub struct Distribution1d {
+    function: Vec<f32>,
+    /// Running sums of `function` over its length, `cdf[0]` is zero and the last entry one.
+    cdf: Vec<f32>,
+    integral: f32,
+}
+
+impl Distribution1d {
+    /// Distribution over the non negative `function`, uniform when it's zero everywhere.
+    pub fn new(function: Vec<f32>) -> Self {
+        let n = function.len() as f32;
+        let mut cdf = Vec::with_capacity(function.len() + 1);
+        cdf.push(0.0);
+        for value in &function {
+            cdf.push(cdf[cdf.len() - 1] + value / n);
+        }
+        let integral = cdf[cdf.len() - 1];
+        if integral > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        } else {
+            cdf.iter_mut()
+                .enumerate()
+                .for_each(|(i, c)| *c = i as f32 / n);
+        }
+        Self {
+            function,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.function.len()
+    }
+
+    /// Average of the function.
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    /// Position picked with `u`, its density and the interval it's in.
+    pub fn sample(&self, u: f32) -> (f32, f32, usize) {
+        // last entry of the cdf that's at most `u`.
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+        let x = ((offset as f32 + du) / self.count() as f32).min(1.0 - f32::EPSILON);
+        (x, self.density(offset), offset)
+    }
+
+    /// Density of `sample` picking `x`.
+    pub fn pdf(&self, x: f32) -> f32 {
+        let offset = ((x * self.count() as f32) as usize).min(self.count() - 1);
+        self.density(offset)
+    }
+
+    fn density(&self, offset: usize) -> f32 {
+        if self.integral > 0.0 {
+            self.function[offset] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Piecewise constant density over `[0, 1)²` given by a grid of values, picks a row by the sum of
+/// its values and then a column within it.
+#[derive(Debug, Clone)]
+pub struct Distribution2d {
+    rows: Vec<Distribution1d>,
+    marginal: Distribution1d,
+}
+
+impl Distribution2d {
+    /// `values` holds `height` rows of `width` values.
+    pub fn new(values: &[f32], width: usize, height: usize) -> Self {
+        let rows = values
+            .chunks_exact(width)
+            .take(height)
+            .map(|row| Distribution1d::new(row.to_vec()))
+            .collect::<Vec<_>>();
+        let marginal = Distribution1d::new(rows.iter().map(|row| row.integral()).collect());
+        Self { rows, marginal }
+    }
+
+    /// Point picked with `u`, x along the rows and y across them, and its density.
+    pub fn sample(&self, u: Vec2) -> (Vec2, f32) {
+        let (y, row_pdf, row) = self.marginal.sample(u.y);
+        let (x, pdf, _) = self.rows[row].sample(u.x);
+        (Vec2::new(x, y), row_pdf * pdf)
+    }
+
+    /// Density of `sample` picking `p`.
+    pub fn pdf(&self, p: Vec2) -> f32 {
+        let row = ((p.y * self.rows.len() as f32) as usize).min(self.rows.len() - 1);
+        self.marginal.pdf(p.y) * self.rows[row].pdf(p.x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random;
+    use rand::Rng;
+
+    fn assert_close(a: f32, b: f32, tolerance: f32) {
+        assert!((a - b).abs() <= tolerance, "{a} is not {b}");
+    }
+
+    #[test]
+    fn pdf_1d_integrates_to_one_and_matches_sample() {
+        for function in [
+            vec![1.0, 3.0, 0.0, 6.0],
+            vec![0.0, 0.0, 2.0],
+            vec![5.0],
+            vec![0.0; 3],
+        ] {
+            let distribution = Distribution1d::new(function.clone());
+            let n = function.len();
+            let integral = (0..n)
+                .map(|i| distribution.pdf((i as f32 + 0.5) / n as f32) / n as f32)
+                .sum::<f32>();
+            assert_close(integral, 1.0, 1e-5);
+
+            let mut rng = random::seeded_rng(1);
+            let mut counts = vec![0; n];
+            let samples = 100_000;
+            for _ in 0..samples {
+                let (x, pdf, offset) = distribution.sample(rng.gen());
+                assert!((0.0..1.0).contains(&x));
+                assert_eq!(offset, (x * n as f32) as usize);
+                assert_eq!(pdf, distribution.pdf(x));
+                assert!(pdf > 0.0);
+                counts[offset] += 1;
+            }
+            for (i, count) in counts.into_iter().enumerate() {
+                let expected = distribution.pdf((i as f32 + 0.5) / n as f32) / n as f32;
+                assert_close(count as f32 / samples as f32, expected, 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_2d_integrates_to_one_and_matches_sample() {
+        let (width, height) = (5, 3);
+        let values = [
+            1.0, 0.0, 2.0, 4.0, 1.0, //
+            0.0, 0.0, 0.0, 0.0, 0.0, //
+            3.0, 1.0, 0.5, 0.0, 7.0,
+        ];
+        let distribution = Distribution2d::new(&values, width, height);
+        let mut integral = 0.0;
+        for y in 0..height {
+            for x in 0..width {
+                let p = Vec2::new(
+                    (x as f32 + 0.5) / width as f32,
+                    (y as f32 + 0.5) / height as f32,
+                );
+                let pdf = distribution.pdf(p);
+                assert_close(pdf, values[y * width + x] / 1.3, 1e-5);
+                integral += pdf / (width * height) as f32;
+            }
+        }
+        assert_close(integral, 1.0, 1e-5);
+
+        let mut rng = random::seeded_rng(2);
+        for _ in 0..10_000 {
+            let (p, pdf) = distribution.sample(Vec2::new(rng.gen(), rng.gen()));
+            assert!(pdf > 0.0);
+            assert_close(pdf, distribution.pdf(p), 1e-4);
+        }
+    }
+}
@@ -1,22 +1,66 @@
+use crate::validation::ValidationError;
 use std::convert::Infallible;
+use std::path::PathBuf;
 use thiserror::Error;
 
 pub type TracerResult<T> = std::result::Result<T, TracerError>;
 
 #[derive(Error, Debug)]
 pub enum TracerError {
-    #[error("fs error")]
+    #[error("fs error: {0}")]
     FsError(#[from] std::io::Error),
 
-    #[error("image error")]
+    #[error("could not open {path}")]
+    FileError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("image error: {0}")]
     ImageError(#[from] image::ImageError),
 
+    #[error("could not process image {path}")]
+    ImageFileError {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+
+    #[error("could not write exr {path}")]
+    ExrError {
+        path: PathBuf,
+        source: exr::error::Error,
+    },
+
+    #[error("could not start the render threads: {0}")]
+    ThreadPoolError(#[from] rayon::ThreadPoolBuildError),
+
     #[error("null bound box error")]
     BvhBoundingBoxError,
 
-    #[error("null bound box error")]
+    #[error("infallible")]
     Infallible(#[from] Infallible),
 
-    #[error("json input error")]
+    #[error("json input error: {0}")]
     JsonInputError(#[from] serde_json::Error),
+
+    #[error("invalid scene:{}", .0.iter().map(|e| format!("\n  {e}")).collect::<String>())]
+    InvalidScene(Vec<ValidationError>),
+
+    #[error("unknown {kind} {name:?}")]
+    UnknownReference { kind: &'static str, name: String },
+
+    #[error("duplicate {kind} name {name:?}")]
+    DuplicateName { kind: &'static str, name: String },
+
+    #[error("could not load obj {path}")]
+    ObjError {
+        path: PathBuf,
+        source: tobj::LoadError,
+    },
+
+    #[error("{path}: {model:?} has no material, set `material` on the ObjMesh")]
+    ObjMissingMaterial { path: PathBuf, model: String },
+
+    #[error("obj {path} has to be loaded with RenderBuilder::load_meshes before it is built")]
+    UnloadedObj { path: PathBuf },
 }
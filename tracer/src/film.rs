@@ -0,0 +1,177 @@
+use crate::filter::Filter;
+use crate::framebuffer::Framebuffer;
+use glam::{Vec2, Vec3A};
+
+/// Weighted sums of the samples splatted into a rectangle of the image. Every sample adds to all
+/// pixels within the filter radius, the final color is the weighted average.
+#[derive(Debug, Clone)]
+pub struct Film {
+    /// Image column of the film's first column.
+    pub x: u32,
+    /// Image row of the film's first row.
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<Vec3A>,
+    weights: Vec<f32>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::region(0, 0, width, height)
+    }
+
+    /// Film holding the image pixels `x..x + width` of the rows `y..y + height`.
+    pub fn region(x: u32, y: u32, width: u32, height: u32) -> Self {
+        let len = (width * height) as usize;
+        Self {
+            x,
+            y,
+            width,
+            height,
+            pixels: vec![Vec3A::ZERO; len],
+            weights: vec![0.0; len],
+        }
+    }
+
+    /// Adds a sample at `position` in pixels from the top left of the image, pixel centers are at
+    /// half pixel offsets. Pixels outside of the film are skipped.
+    pub fn add_sample(&mut self, position: Vec2, color: Vec3A, filter: &Filter) {
+        let min = (position - 0.5 - filter.radius).ceil();
+        let max = (position - 0.5 + filter.radius).floor();
+        let min_x = min.x.max(self.x as f32);
+        let min_y = min.y.max(self.y as f32);
+        let max_x = max.x.min((self.x + self.width) as f32 - 1.0);
+        let max_y = max.y.min((self.y + self.height) as f32 - 1.0);
+        if min_x > max_x || min_y > max_y {
+            return;
+        }
+        for y in min_y as u32..=max_y as u32 {
+            for x in min_x as u32..=max_x as u32 {
+                let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let weight = filter.evaluate(center - position);
+                if weight != 0.0 {
+                    let i = ((y - self.y) * self.width + x - self.x) as usize;
+                    self.pixels[i] += color * weight;
+                    self.weights[i] += weight;
+                }
+            }
+        }
+    }
+
+    /// Adds the sums of `other` to the pixels both films hold.
+    pub fn merge(&mut self, other: &Film) {
+        let first_x = self.x.max(other.x);
+        let last_x = (self.x + self.width).min(other.x + other.width);
+        let first_y = self.y.max(other.y);
+        let last_y = (self.y + self.height).min(other.y + other.height);
+        for y in first_y..last_y {
+            let to = ((y - self.y) * self.width) as usize;
+            let from = ((y - other.y) * other.width) as usize;
+            for x in first_x..last_x {
+                let to = to + (x - self.x) as usize;
+                let from = from + (x - other.x) as usize;
+                self.pixels[to] += other.pixels[from];
+                self.weights[to] += other.weights[from];
+            }
+        }
+    }
+
+    /// Divides the sums by their weights, pixels without weight stay black.
+    pub fn to_framebuffer(&self) -> Framebuffer {
+        let pixels = self
+            .pixels
+            .iter()
+            .zip(&self.weights)
+            .map(|(&color, &weight)| {
+                if weight.abs() > 1e-6 {
+                    color / weight
+                } else {
+                    Vec3A::ZERO
+                }
+            })
+            .collect();
+        Framebuffer::from_pixels(self.width, self.height, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterType;
+    use crate::random;
+    use rand::Rng;
+
+    #[test]
+    fn weighted_average_of_a_constant_is_that_constant() {
+        let color = Vec3A::new(0.2, 0.5, 3.0);
+        for filter_type in [
+            FilterType::Box,
+            FilterType::Tent,
+            FilterType::Gaussian,
+            FilterType::Mitchell,
+            FilterType::Lanczos,
+        ] {
+            let filter = Filter::new(filter_type, filter_type.default_radius());
+            let mut film = Film::new(6, 4);
+            let mut rng = random::seeded_rng(14);
+            for _ in 0..4000 {
+                let position = Vec2::new(rng.gen::<f32>() * 6.0, rng.gen::<f32>() * 4.0);
+                film.add_sample(position, color, &filter);
+            }
+            let image = film.to_framebuffer();
+            for pixel in &image.pixels {
+                assert!(
+                    pixel.abs_diff_eq(color, 1e-4),
+                    "{filter_type:?} gave {pixel}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn default_box_keeps_samples_in_their_pixel() {
+        let filter = Filter::new(FilterType::Box, FilterType::Box.default_radius());
+        let mut film = Film::new(3, 1);
+        film.add_sample(Vec2::new(1.2, 0.5), Vec3A::ONE, &filter);
+        film.add_sample(Vec2::new(1.8, 0.3), Vec3A::splat(3.0), &filter);
+        let image = film.to_framebuffer();
+        assert_eq!(
+            image.pixels,
+            vec![Vec3A::ZERO, Vec3A::splat(2.0), Vec3A::ZERO]
+        );
+    }
+
+    #[test]
+    fn merged_tiles_match_one_film() {
+        let filter = Filter::new(FilterType::Gaussian, 1.5);
+        let mut whole = Film::new(8, 6);
+        // 4x3 tiles, each holding the pixels its samples can reach, as the renderer splits them.
+        let mut tiles = [
+            Film::region(0, 0, 6, 5),
+            Film::region(0, 1, 6, 5),
+            Film::region(2, 0, 6, 5),
+            Film::region(2, 1, 6, 5),
+        ];
+        let mut rng = random::seeded_rng(15);
+        for _ in 0..1000 {
+            let position = Vec2::new(rng.gen::<f32>() * 8.0, rng.gen::<f32>() * 6.0);
+            let color = Vec3A::new(rng.gen(), rng.gen(), rng.gen());
+            whole.add_sample(position, color, &filter);
+            let tile = (position.x >= 4.0) as usize * 2 + (position.y >= 3.0) as usize;
+            tiles[tile].add_sample(position, color, &filter);
+        }
+        let mut merged = Film::new(8, 6);
+        for tile in &tiles {
+            merged.merge(tile);
+        }
+        for (a, b) in merged
+            .to_framebuffer()
+            .pixels
+            .iter()
+            .zip(&whole.to_framebuffer().pixels)
+        {
+            assert!(a.abs_diff_eq(*b, 1e-5));
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use glam::Vec2;
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+/// Reconstruction filter samples are weighted with when they're added to the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum FilterType {
+    /// Equal weight over the radius, with the default radius every sample stays in its pixel.
+    #[default]
+    Box,
+    /// Weight falling linearly to zero at the radius.
+    Tent,
+    /// Gaussian with a standard deviation of a third of the radius, shifted to reach zero at it.
+    Gaussian,
+    /// Mitchell-Netravali cubic with `B = C = 1/3`.
+    Mitchell,
+    /// Sinc windowed by a sinc stretched over the radius.
+    Lanczos,
+}
+
+impl FilterType {
+    /// Radius used when the settings don't give one, in pixels.
+    pub fn default_radius(self) -> f32 {
+        match self {
+            FilterType::Box => 0.5,
+            FilterType::Tent => 1.0,
+            FilterType::Gaussian => 1.5,
+            FilterType::Mitchell | FilterType::Lanczos => 2.0,
+        }
+    }
+}
+
+/// A filter of a given radius, evaluated per axis.
+#[derive(Debug, Clone, Copy)]
+pub struct Filter {
+    pub filter_type: FilterType,
+    pub radius: f32,
+}
+
+impl Filter {
+    pub fn new(filter_type: FilterType, radius: f32) -> Self {
+        Self {
+            filter_type,
+            radius,
+        }
+    }
+
+    /// Weight of a sample `offset` pixels away from a pixel center.
+    pub fn evaluate(&self, offset: Vec2) -> f32 {
+        self.evaluate_1d(offset.x) * self.evaluate_1d(offset.y)
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+        if x > self.radius {
+            return 0.0;
+        }
+        match self.filter_type {
+            FilterType::Box => 1.0,
+            FilterType::Tent => self.radius - x,
+            FilterType::Gaussian => {
+                let sigma = self.radius / 3.0;
+                let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(self.radius)).max(0.0)
+            }
+            FilterType::Mitchell => mitchell(2.0 * x / self.radius, 1.0 / 3.0, 1.0 / 3.0),
+            FilterType::Lanczos => sinc(x) * sinc(x / self.radius),
+        }
+    }
+}
+
+/// Mitchell-Netravali cubic, non zero for `|x| < 2`.
+fn mitchell(x: f32, b: f32, c: f32) -> f32 {
+    let x = x.abs();
+    let value = if x <= 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b)
+    } else if x <= 2.0 {
+        (-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    } else {
+        0.0
+    };
+    value / 6.0
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [FilterType; 5] = [
+        FilterType::Box,
+        FilterType::Tent,
+        FilterType::Gaussian,
+        FilterType::Mitchell,
+        FilterType::Lanczos,
+    ];
+
+    /// Integral of the filter over its square, by the midpoint rule.
+    fn integral(filter: &Filter) -> f32 {
+        let steps = 200;
+        let step = 2.0 * filter.radius / steps as f32;
+        let mut sum = 0.0;
+        for i in 0..steps {
+            for j in 0..steps {
+                let offset = Vec2::new(i as f32 + 0.5, j as f32 + 0.5) * step - filter.radius;
+                sum += filter.evaluate(offset);
+            }
+        }
+        sum * step * step
+    }
+
+    #[test]
+    fn filters_are_symmetric_and_end_at_their_radius() {
+        for filter_type in ALL {
+            let filter = Filter::new(filter_type, filter_type.default_radius());
+            let radius = filter.radius;
+            assert!(filter.evaluate(Vec2::ZERO) > 0.0, "{filter_type:?}");
+            for x in [0.1, 0.4, 0.9, 1.3] {
+                let x = x * radius;
+                let weight = filter.evaluate(Vec2::new(x, 0.0));
+                assert!((weight - filter.evaluate(Vec2::new(-x, 0.0))).abs() < 1e-6);
+                assert!((weight - filter.evaluate(Vec2::new(0.0, x))).abs() < 1e-6);
+            }
+            assert!(filter.evaluate(Vec2::new(radius * 1.01, 0.0)) == 0.0);
+            assert!(filter.evaluate(Vec2::new(0.0, radius * 1.01)) == 0.0);
+            if filter_type != FilterType::Box {
+                assert!(filter.evaluate(Vec2::new(radius * 0.999, 0.0)).abs() < 0.02);
+            }
+        }
+    }
+
+    #[test]
+    fn filters_have_a_positive_integral() {
+        // the film divides by the summed weights, so only the sign of the integral matters.
+        for filter_type in ALL {
+            let filter = Filter::new(filter_type, filter_type.default_radius());
+            assert!(integral(&filter) > 0.1, "{filter_type:?}");
+        }
+        let tent = Filter::new(FilterType::Tent, 1.0);
+        assert!((integral(&tent) - 1.0).abs() < 1e-3);
+        let unit_box = Filter::new(FilterType::Box, 0.5);
+        assert!((integral(&unit_box) - 1.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,322 @@
+use crate::error::{TracerError, TracerResult};
+use crate::tone_map::{srgb_decode, DisplayTransform};
+use glam::Vec3A;
+use image::codecs::hdr::HdrEncoder;
+use image::{DynamicImage, ImageError, ImageFormat, ImageResult, Rgb, Rgb32FImage, RgbImage};
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Image format of an output path, picked from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// OpenEXR with f32 channels.
+    Exr,
+    /// Radiance rgbe.
+    Hdr,
+    /// Portable float map with f32 channels.
+    Pfm,
+    /// 8 bit formats like png, jpeg or ppm, gamma corrected and clamped.
+    Ldr(ImageFormat),
+}
+
+impl OutputFormat {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("exr") => Ok(OutputFormat::Exr),
+            Some("hdr") => Ok(OutputFormat::Hdr),
+            Some("pfm") => Ok(OutputFormat::Pfm),
+            _ => ImageFormat::from_path(path).map(OutputFormat::Ldr),
+        }
+    }
+
+    /// Whether the format keeps values above one.
+    pub fn is_hdr(self) -> bool {
+        !matches!(self, OutputFormat::Ldr(_))
+    }
+}
+
+/// Linear colors of the pixels of `image` in row major order from the top left. Float images, like
+/// hdr and exr, are already linear, the others are decoded from sRGB.
+pub fn linear_pixels(image: &DynamicImage) -> Vec<Vec3A> {
+    let linear = matches!(
+        image,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    );
+    image
+        .to_rgb32f()
+        .pixels()
+        .map(|p| {
+            let color = Vec3A::from(p.0);
+            if linear {
+                color
+            } else {
+                Vec3A::from(color.to_array().map(srgb_decode))
+            }
+        })
+        .collect()
+}
+
+/// Linear f32 RGB image produced by the renderer, stored row major from the top left pixel.
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Vec3A>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Vec3A::ZERO; (width * height) as usize],
+        }
+    }
+
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<Vec3A>) -> Self {
+        assert_eq!(pixels.len(), (width * height) as usize);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Loads an image, hdr formats keep their linear values and 8 bit formats are decoded from
+    /// sRGB.
+    pub fn open<P: AsRef<Path>>(path: P) -> TracerResult<Self> {
+        let path = path.as_ref();
+        let result = match OutputFormat::from_path(path) {
+            Ok(OutputFormat::Pfm) => Self::read_pfm(path),
+            _ => image::open(path).map(|image| {
+                let pixels = linear_pixels(&image);
+                Self::from_pixels(image.width(), image.height(), pixels)
+            }),
+        };
+        result.map_err(|source| TracerError::ImageFileError {
+            path: path.into(),
+            source,
+        })
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Vec3A {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: Vec3A) {
+        self.pixels[(y * self.width + x) as usize] = color;
+    }
+
+    pub fn to_rgb32f(&self) -> Rgb32FImage {
+        Rgb32FImage::from_fn(self.width, self.height, |x, y| {
+            let color = self.get(x, y);
+            Rgb([color.x, color.y, color.z])
+        })
+    }
+
+    /// 8 bit view of the image through `display`.
+    pub fn to_rgb8(&self, display: &DisplayTransform) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            let encoded = (display.apply(self.get(x, y)) * 255.0).round();
+            Rgb([encoded.x as u8, encoded.y as u8, encoded.z as u8])
+        })
+    }
+
+    /// Saves the image in the format of the path's extension, 8 bit formats are clamped and sRGB
+    /// encoded.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> TracerResult<()> {
+        self.save_with(path, &DisplayTransform::default())
+    }
+
+    /// Saves the image in the format of the path's extension. Hdr formats keep the linear values,
+    /// 8 bit formats go through `display`.
+    pub fn save_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        display: &DisplayTransform,
+    ) -> TracerResult<()> {
+        let path = path.as_ref();
+        let result = match OutputFormat::from_path(path) {
+            Ok(OutputFormat::Exr) => self
+                .to_rgb32f()
+                .save_with_format(path, ImageFormat::OpenExr),
+            Ok(OutputFormat::Hdr) => self.write_hdr(path),
+            Ok(OutputFormat::Pfm) => self.write_pfm(path),
+            Ok(OutputFormat::Ldr(format)) => self.to_rgb8(display).save_with_format(path, format),
+            Err(e) => Err(e),
+        };
+        result.map_err(|source| TracerError::ImageFileError {
+            path: path.into(),
+            source,
+        })
+    }
+
+    fn write_hdr(&self, path: &Path) -> ImageResult<()> {
+        let file = BufWriter::new(fs::File::create(path)?);
+        let pixels = self
+            .pixels
+            .iter()
+            .map(|color| Rgb([color.x, color.y, color.z]))
+            .collect::<Vec<Rgb<f32>>>();
+        HdrEncoder::new(file).encode(&pixels, self.width as usize, self.height as usize)
+    }
+
+    /// Little endian pfm, its rows go from the bottom of the image to the top.
+    fn write_pfm(&self, path: &Path) -> ImageResult<()> {
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        write!(file, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for row in self.pixels.chunks_exact(self.width as usize).rev() {
+            for color in row {
+                for channel in color.to_array() {
+                    file.write_all(&channel.to_le_bytes())?;
+                }
+            }
+        }
+        file.flush().map_err(ImageError::IoError)
+    }
+
+    /// Reads the rgb pfm files written by `write_pfm`, in either byte order.
+    fn read_pfm(path: &Path) -> ImageResult<Self> {
+        let invalid = |message: &str| {
+            ImageError::IoError(io::Error::new(io::ErrorKind::InvalidData, message))
+        };
+        let mut file = BufReader::new(fs::File::open(path)?);
+        let mut header = Vec::new();
+        // magic, size and scale are separated by whitespace, a single one ends the header.
+        while header.len() < 3 {
+            let mut line = String::new();
+            if file.read_line(&mut line)? == 0 {
+                return Err(invalid("truncated pfm header"));
+            }
+            header.extend(line.split_whitespace().map(str::to_string));
+        }
+        if header[0] != "PF" {
+            return Err(invalid("only rgb pfm files are supported"));
+        }
+        let number = |s: &str| s.parse::<f32>().map_err(|_| invalid("invalid pfm header"));
+        let width = number(&header[1])? as u32;
+        let height = number(&header[2])? as u32;
+        let little_endian = match header.get(3) {
+            Some(scale) => number(scale)? < 0.0,
+            None => {
+                let mut line = String::new();
+                file.read_line(&mut line)?;
+                number(line.trim())? < 0.0
+            }
+        };
+        let mut bytes = vec![0; (width * height * 12) as usize];
+        file.read_exact(&mut bytes)?;
+        let mut pixels = bytes
+            .chunks_exact(12)
+            .map(|pixel| {
+                let channel = |i: usize| {
+                    let bytes = pixel[i * 4..i * 4 + 4].try_into().unwrap();
+                    if little_endian {
+                        f32::from_le_bytes(bytes)
+                    } else {
+                        f32::from_be_bytes(bytes)
+                    }
+                };
+                Vec3A::new(channel(0), channel(1), channel(2))
+            })
+            .collect::<Vec<Vec3A>>();
+        // rows are stored from the bottom up.
+        let rows = pixels.chunks_exact(width as usize).rev().flatten();
+        pixels = rows.copied().collect();
+        Ok(Self::from_pixels(width, height, pixels))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("tracer-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn pfm_round_trips_bit_for_bit() {
+        let (width, height) = (5, 3);
+        let pixels = (0..width * height)
+            .map(|i| Vec3A::new(i as f32 / 3.0, -1.5e-7, 1e20))
+            .collect();
+        let image = Framebuffer::from_pixels(width, height, pixels);
+        let path = temp_path("round_trip.pfm");
+        image.save(&path).unwrap();
+        let read = Framebuffer::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((read.width, read.height), (width, height));
+        for (read, written) in read.pixels.iter().zip(&image.pixels) {
+            assert_eq!(
+                read.to_array().map(f32::to_bits),
+                written.to_array().map(f32::to_bits)
+            );
+        }
+    }
+
+    #[test]
+    fn pfm_reads_big_endian_bottom_up() {
+        let mut bytes = b"PF 1 2 1.0\n".to_vec();
+        for value in [4.0f32, 5.0, 6.0, 1.0, 2.0, 3.0] {
+            bytes.extend(value.to_be_bytes());
+        }
+        let path = temp_path("big_endian.pfm");
+        fs::write(&path, bytes).unwrap();
+        let read = Framebuffer::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.get(0, 0), Vec3A::new(1.0, 2.0, 3.0));
+        assert_eq!(read.get(0, 1), Vec3A::new(4.0, 5.0, 6.0));
+    }
+
+    #[test]
+    fn png_round_trips_its_8_bit_values() {
+        let values = [0u8, 1, 10, 64, 128, 200, 255];
+        let png = RgbImage::from_fn(values.len() as u32, 1, |x, _| {
+            let v = values[x as usize];
+            Rgb([v, 255 - v, v / 2])
+        });
+        let path = temp_path("round_trip.png");
+        png.save(&path).unwrap();
+        let image = Framebuffer::open(&path).unwrap();
+        // 128 is sRGB for about 0.216 linear.
+        assert!((image.get(4, 0).x - 0.2158605).abs() < 1e-5);
+        image.save(&path).unwrap();
+        let saved = image::open(&path).unwrap().to_rgb8();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(saved, png);
+    }
+
+    #[test]
+    fn pfm_is_little_endian_bottom_up_bit_for_bit() {
+        let (width, height) = (5, 3);
+        let pixels = (0..width * height)
+            .map(|i| Vec3A::new(i as f32 / 3.0, -1.5e-7, 1e20))
+            .collect();
+        let image = Framebuffer::from_pixels(width, height, pixels);
+        let path = temp_path("written.pfm");
+        image.save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let header = b"PF\n5 3\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let values = bytes[header.len()..]
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect::<Vec<u32>>();
+        assert_eq!(values.len(), (width * height * 3) as usize);
+        for (i, color) in values.chunks_exact(3).enumerate() {
+            let (x, row) = (i as u32 % width, i as u32 / width);
+            let written = image.get(x, height - 1 - row).to_array().map(f32::to_bits);
+            assert_eq!(color, written);
+        }
+    }
+}
@@ -29,6 +29,24 @@ impl Aabb {
         Self::new(small, big)
     }
 
+    pub fn centroid(&self) -> Vec3A {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Slab test with the ray's inverse direction precomputed, used by the bvh traversal.
+    pub fn hit_inverse(&self, origin: Vec3A, inv_direction: Vec3A, t_min: f32, t_max: f32) -> bool {
+        let t0 = (self.min - origin) * inv_direction;
+        let t1 = (self.max - origin) * inv_direction;
+        let t_near = t0.min(t1).max_element().max(t_min);
+        let t_far = t0.max(t1).min_element().min(t_max);
+        t_near <= t_far
+    }
+
     pub fn slow_hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
         for a in 0..3 {
             let t0 = ((self.min[a] - ray.origin[a]) / ray.direction[a])
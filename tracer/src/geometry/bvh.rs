@@ -1,39 +1,25 @@
 use crate::error::TracerError::BvhBoundingBoxError;
-use crate::error::{TracerError, TracerResult};
+use crate::error::TracerResult;
 use crate::geometry::aabb::Aabb;
-use crate::geometry::{Geometry, GeometryFile, Hittable};
+use crate::geometry::bvh_tree::{area_or_one, BvhStats, INTERSECTION_COST, TRAVERSAL_COST};
+use crate::geometry::{Geometry, Hittable};
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
+use crate::random::TracerRng;
+use crate::stats;
+use rand::Rng;
 use std::cmp::Ordering;
-use std::rc::Rc;
-
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct BvhNodeBuilder {
-    pub left: Box<GeometryFile>,
-    pub right: Box<GeometryFile>,
-    pub bounding_box: Aabb,
-}
-
-impl TryInto<Geometry> for BvhNodeBuilder {
-    type Error = TracerError;
-
-    fn try_into(self) -> TracerResult<Geometry> {
-        let l = self.left.try_into()?;
-        let r = self.right.try_into()?;
-        Ok(Geometry::BvhNode(BvhNode::new(l, r, self.bounding_box)))
-    }
-}
+use std::sync::Arc;
 
+// the bvh is built from the scene in RenderBuilder::build, it isn't part of the json.
 pub struct BvhNode {
-    pub left: Rc<Geometry>,
-    pub right: Rc<Geometry>,
+    pub left: Arc<Geometry>,
+    pub right: Arc<Geometry>,
     pub bounding_box: Aabb,
 }
 
 impl BvhNode {
-    pub fn new(left: Rc<Geometry>, right: Rc<Geometry>, bounding_box: Aabb) -> BvhNode {
+    pub fn new(left: Arc<Geometry>, right: Arc<Geometry>, bounding_box: Aabb) -> BvhNode {
         BvhNode {
             left,
             right,
@@ -41,36 +27,85 @@ impl BvhNode {
         }
     }
 
-    // note that the rest of the geometry objects are stored in a flat list in the render context
-    pub fn from_list(list: Vec<Rc<Geometry>>, t0: f32, t1: f32) -> TracerResult<BvhNode> {
-        let mut list = list;
-        let axis = Axis::random();
+    /// Builds a tree over `list`, every object in it must have a bounding box. Split axes are
+    /// drawn from `rng`.
+    pub fn from_list(
+        list: Vec<Arc<Geometry>>,
+        t0: f32,
+        t1: f32,
+        rng: &mut TracerRng,
+    ) -> TracerResult<BvhNode> {
+        let mut list = list
+            .into_iter()
+            .map(|object| {
+                let bounding_box = object.bounding_box(t0, t1).ok_or(BvhBoundingBoxError)?;
+                Ok((bounding_box, object))
+            })
+            .collect::<TracerResult<Vec<(Aabb, Arc<Geometry>)>>>()?;
+        Ok(Self::from_boxes(&mut list, rng))
+    }
+
+    fn from_boxes(list: &mut [(Aabb, Arc<Geometry>)], rng: &mut TracerRng) -> BvhNode {
+        let axis = Axis::random(rng);
         let object_span = list.len();
         let (left, right) = if object_span == 1 {
             //if there's just one object put it in both left and right
-            (list[0].to_owned(), list[0].to_owned())
+            (list[0].1.clone(), list[0].1.clone())
         } else if object_span == 2 {
-            if compare_boxes(&list[0], &list[1], axis) {
-                (list[0].to_owned(), list[1].to_owned())
+            if compare_boxes(&list[0].0, &list[1].0, axis) == Ordering::Greater {
+                (list[1].1.clone(), list[0].1.clone())
             } else {
-                (list[1].to_owned(), list[0].to_owned())
+                (list[0].1.clone(), list[1].1.clone())
             }
         } else {
-            list.sort_by(|a, b| sort_boxes(a, b, axis));
-            let mid = object_span / 2;
-            let left_list = list[0..mid].to_vec();
-            let right_list = list[mid..].to_vec();
-            let left: Rc<Geometry> =
-                Rc::new(Geometry::BvhNode(BvhNode::from_list(left_list, t0, t1)?));
-            let right: Rc<Geometry> =
-                Rc::new(Geometry::BvhNode(BvhNode::from_list(right_list, t0, t1)?));
+            list.sort_by(|a, b| compare_boxes(&a.0, &b.0, axis));
+            let (left_list, right_list) = list.split_at_mut(object_span / 2);
+            let left = Arc::new(Geometry::BvhNode(BvhNode::from_boxes(left_list, rng)));
+            let right = Arc::new(Geometry::BvhNode(BvhNode::from_boxes(right_list, rng)));
             (left, right)
         };
 
-        let box_left = left.bounding_box(t0, t1).ok_or(BvhBoundingBoxError)?;
-        let box_right = right.bounding_box(t0, t1).ok_or(BvhBoundingBoxError)?;
-        let bounding_box = Aabb::surrounding_box(&box_left, &box_right);
-        Ok(BvhNode::new(left, right, bounding_box))
+        let bounding_box = list
+            .iter()
+            .map(|(bounding_box, _)| *bounding_box)
+            .reduce(|a, b| Aabb::surrounding_box(&a, &b))
+            .expect("bvh node without objects");
+        BvhNode::new(left, right, bounding_box)
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats {
+            trees: 1,
+            ..BvhStats::default()
+        };
+        self.collect_stats(1, area_or_one(&self.bounding_box), &mut stats);
+        stats
+    }
+
+    fn collect_stats(&self, depth: usize, root_area: f32, stats: &mut BvhStats) {
+        stats.nodes += 1;
+        stats.max_depth = stats.max_depth.max(depth);
+        // objects below the node are intersected whenever its box is hit.
+        let mut objects = 0;
+        // a node over a single object holds it on both sides.
+        let children = if Arc::ptr_eq(&self.left, &self.right) {
+            &[&self.left][..]
+        } else {
+            &[&self.left, &self.right][..]
+        };
+        for child in children {
+            match child.as_ref() {
+                Geometry::BvhNode(node) => node.collect_stats(depth + 1, root_area, stats),
+                _ => objects += 1,
+            }
+        }
+        if objects > 0 {
+            stats.leaves += 1;
+            stats.primitives += objects;
+            stats.max_leaf_size = stats.max_leaf_size.max(objects);
+        }
+        stats.sah_cost += self.bounding_box.surface_area() / root_area
+            * (TRAVERSAL_COST + objects as f32 * INTERSECTION_COST);
     }
 }
 
@@ -82,15 +117,8 @@ enum Axis {
 }
 
 impl Axis {
-    fn next(&self) -> Axis {
-        match self {
-            Axis::X => Axis::Y,
-            Axis::Y => Axis::Z,
-            Axis::Z => Axis::X,
-        }
-    }
-    fn random() -> Axis {
-        match (3.0 * rand::random::<f32>()) as usize {
+    fn random(rng: &mut TracerRng) -> Axis {
+        match rng.gen_range(0..3) {
             0 => Axis::X,
             1 => Axis::Y,
             2 => Axis::Z,
@@ -99,47 +127,27 @@ impl Axis {
     }
 }
 
-fn sort_boxes(a: &Rc<Geometry>, b: &Rc<Geometry>, axis: Axis) -> Ordering {
-    if compare_boxes(a, b, axis) {
-        Ordering::Less
-    } else {
-        Ordering::Greater
-    }
-}
-
-fn compare_boxes(a: &Rc<Geometry>, b: &Rc<Geometry>, axis: Axis) -> bool {
-    let box_a = a.bounding_box(0.0, 0.0).unwrap();
-    let box_b = b.bounding_box(0.0, 0.0).unwrap();
+fn compare_boxes(box_a: &Aabb, box_b: &Aabb, axis: Axis) -> Ordering {
     match axis {
-        Axis::X => box_a.min.x < box_b.min.x,
-        Axis::Y => box_a.min.y < box_b.min.y,
-        Axis::Z => box_a.min.z < box_b.min.z,
+        Axis::X => box_a.min.x.total_cmp(&box_b.min.x),
+        Axis::Y => box_a.min.y.total_cmp(&box_b.min.y),
+        Axis::Z => box_a.min.z.total_cmp(&box_b.min.z),
     }
 }
 
 impl Hittable for BvhNode {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut TracerRng) -> Option<HitRecord> {
+        stats::count_bvh_visit();
         if !self.bounding_box.hit(r, t_min, t_max) {
             return None;
         }
 
-        let hit_left = self.left.hit(r, t_min, t_max);
-        let hit_right = self.right.hit(r, t_min, t_max);
-
-        match (hit_left, hit_right) {
-            (Some(left), Some(right)) => {
-                if left.root < right.root {
-                    Some(left)
-                } else {
-                    Some(right)
-                }
-            }
-            (Some(left), None) => Some(left),
-            (None, Some(right)) => Some(right),
-            (None, None) => None,
-        }
+        let hit_left = self.left.hit(r, t_min, t_max, rng);
+        let t_max = hit_left.as_ref().map_or(t_max, |left| left.root);
+        let hit_right = self.right.hit(r, t_min, t_max, rng);
+        hit_right.or(hit_left)
     }
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
-        Some(self.bounding_box.clone())
+        Some(self.bounding_box)
     }
 }
@@ -0,0 +1,448 @@
+use crate::error::TracerError::BvhBoundingBoxError;
+use crate::error::TracerResult;
+use crate::geometry::aabb::Aabb;
+use crate::geometry::{Geometry, Hittable};
+use crate::intersection::hit_record::HitRecord;
+use crate::intersection::ray::Ray;
+use crate::random::TracerRng;
+use crate::stats;
+use glam::Vec3A;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Relative cost of testing a ray against a node's box.
+pub const TRAVERSAL_COST: f32 = 1.0;
+/// Relative cost of testing a ray against a primitive.
+pub const INTERSECTION_COST: f32 = 1.0;
+
+const BINS: usize = 16;
+const MAX_LEAF_SIZE: usize = 8;
+// also the size of the traversal stack, deeper nodes are turned into leaves.
+const MAX_DEPTH: usize = 64;
+// below this many primitives the two halves are built on the current thread.
+const PARALLEL_THRESHOLD: usize = 4096;
+
+/// How the bvh over the world is split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum BvhSplit {
+    /// Binned surface area heuristic into a flat `BvhTree`.
+    #[default]
+    Sah,
+    /// Median split on a random axis into linked `BvhNode`s.
+    Median,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FlatNode {
+    bounding_box: Aabb,
+    // first primitive for leaves, index of the second child for interior nodes.
+    offset: u32,
+    // 0 for interior nodes.
+    count: u32,
+    axis: u8,
+}
+
+/// Bounding volume hierarchy over a list of boxes, stored as a flat depth first array. The first
+/// child of an interior node directly follows it, leaves hold a range of `indices`.
+#[derive(Debug, Clone)]
+pub struct BvhTree {
+    nodes: Vec<FlatNode>,
+    indices: Vec<usize>,
+    build_time: Duration,
+}
+
+struct BuildPrimitive {
+    index: usize,
+    bounding_box: Aabb,
+    centroid: Vec3A,
+}
+
+enum BuildNode {
+    Leaf {
+        bounding_box: Aabb,
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        bounding_box: Aabb,
+        axis: usize,
+        left: Box<BuildNode>,
+        right: Box<BuildNode>,
+    },
+}
+
+#[derive(Clone, Copy)]
+struct Bin {
+    bounding_box: Option<Aabb>,
+    count: usize,
+}
+
+fn union(a: Option<Aabb>, b: &Aabb) -> Aabb {
+    match a {
+        Some(a) => Aabb::surrounding_box(&a, b),
+        None => *b,
+    }
+}
+
+impl BvhTree {
+    /// Builds a tree over `boxes`, the indices passed to `hit` are positions in `boxes`.
+    pub fn build(boxes: &[Aabb]) -> BvhTree {
+        let start = Instant::now();
+        let mut primitives: Vec<BuildPrimitive> = boxes
+            .iter()
+            .enumerate()
+            .map(|(index, bounding_box)| BuildPrimitive {
+                index,
+                bounding_box: *bounding_box,
+                centroid: bounding_box.centroid(),
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        if !primitives.is_empty() {
+            let root = build_node(&mut primitives, 0, 0);
+            flatten(&root, &mut nodes);
+        }
+        BvhTree {
+            nodes,
+            indices: primitives.iter().map(|p| p.index).collect(),
+            build_time: start.elapsed(),
+        }
+    }
+
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| node.bounding_box)
+    }
+
+    /// Walks the tree front to back, calling `hit_primitive` with the index of every primitive
+    /// whose leaf the ray reaches. `t_max` shrinks to the closest hit found so far.
+    pub fn hit<F>(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        mut t_max: f32,
+        mut hit_primitive: F,
+    ) -> Option<HitRecord>
+    where
+        F: FnMut(usize, &Ray, f32, f32) -> Option<HitRecord>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_direction = ray.direction.recip();
+        let direction_is_negative = [
+            inv_direction.x < 0.0,
+            inv_direction.y < 0.0,
+            inv_direction.z < 0.0,
+        ];
+
+        let mut closest = None;
+        let mut stack = [0usize; MAX_DEPTH];
+        let mut stack_size = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            stats::count_bvh_visit();
+            if node
+                .bounding_box
+                .hit_inverse(ray.origin, inv_direction, t_min, t_max)
+            {
+                if node.count == 0 {
+                    // visit the child nearest to the ray origin first.
+                    let second = node.offset as usize;
+                    let (near, far) = if direction_is_negative[node.axis as usize] {
+                        (second, current + 1)
+                    } else {
+                        (current + 1, second)
+                    };
+                    stack[stack_size] = far;
+                    stack_size += 1;
+                    current = near;
+                    continue;
+                }
+                let first = node.offset as usize;
+                for &index in &self.indices[first..first + node.count as usize] {
+                    if let Some(hit) = hit_primitive(index, ray, t_min, t_max) {
+                        t_max = hit.root;
+                        closest = Some(hit);
+                    }
+                }
+            }
+            if stack_size == 0 {
+                break;
+            }
+            stack_size -= 1;
+            current = stack[stack_size];
+        }
+        closest
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats {
+            trees: 1,
+            primitives: self.indices.len(),
+            build_time: self.build_time,
+            ..BvhStats::default()
+        };
+        if let Some(root) = self.bounding_box() {
+            self.collect_stats(0, 1, area_or_one(&root), &mut stats);
+        }
+        stats
+    }
+
+    fn collect_stats(&self, index: usize, depth: usize, root_area: f32, stats: &mut BvhStats) {
+        let node = &self.nodes[index];
+        let area = node.bounding_box.surface_area() / root_area;
+        stats.nodes += 1;
+        stats.max_depth = stats.max_depth.max(depth);
+        if node.count == 0 {
+            stats.sah_cost += area * TRAVERSAL_COST;
+            self.collect_stats(index + 1, depth + 1, root_area, stats);
+            self.collect_stats(node.offset as usize, depth + 1, root_area, stats);
+        } else {
+            stats.leaves += 1;
+            stats.max_leaf_size = stats.max_leaf_size.max(node.count as usize);
+            stats.sah_cost += area * (TRAVERSAL_COST + node.count as f32 * INTERSECTION_COST);
+        }
+    }
+}
+
+pub(crate) fn area_or_one(bounding_box: &Aabb) -> f32 {
+    let area = bounding_box.surface_area();
+    if area > 0.0 {
+        area
+    } else {
+        1.0
+    }
+}
+
+fn build_node(primitives: &mut [BuildPrimitive], first: usize, depth: usize) -> BuildNode {
+    let count = primitives.len();
+    let bounding_box = primitives
+        .iter()
+        .fold(None, |b, p| Some(union(b, &p.bounding_box)))
+        .expect("bvh node without primitives");
+    let leaf = BuildNode::Leaf {
+        bounding_box,
+        first,
+        count,
+    };
+    if count == 1 || depth + 1 >= MAX_DEPTH {
+        return leaf;
+    }
+
+    let (centroid_min, centroid_max) = primitives.iter().fold(
+        (Vec3A::splat(f32::INFINITY), Vec3A::splat(f32::NEG_INFINITY)),
+        |(min, max), p| (min.min(p.centroid), max.max(p.centroid)),
+    );
+    let extent = centroid_max - centroid_min;
+    let bin_of = |centroid: Vec3A, axis: usize| {
+        let offset = (centroid[axis] - centroid_min[axis]) / extent[axis];
+        ((offset * BINS as f32) as usize).min(BINS - 1)
+    };
+
+    // (cost, axis, first bin of the right side)
+    let mut best: Option<(f32, usize, usize)> = None;
+    for axis in 0..3 {
+        if extent[axis] <= 0.0 {
+            continue;
+        }
+        let mut bins = [Bin {
+            bounding_box: None,
+            count: 0,
+        }; BINS];
+        for p in primitives.iter() {
+            let bin = &mut bins[bin_of(p.centroid, axis)];
+            bin.bounding_box = Some(union(bin.bounding_box, &p.bounding_box));
+            bin.count += 1;
+        }
+
+        // sweep from the right to get the area and count of every right side.
+        let mut right_area = [0.0; BINS];
+        let mut right_count = [0; BINS];
+        let (mut right_box, mut count_so_far) = (None, 0);
+        for split in (1..BINS).rev() {
+            if let Some(b) = bins[split].bounding_box {
+                right_box = Some(union(right_box, &b));
+            }
+            count_so_far += bins[split].count;
+            right_area[split] = right_box.map_or(0.0, |b| b.surface_area());
+            right_count[split] = count_so_far;
+        }
+
+        let (mut left_box, mut left_count) = (None, 0);
+        for split in 1..BINS {
+            if let Some(b) = bins[split - 1].bounding_box {
+                left_box = Some(union(left_box, &b));
+            }
+            left_count += bins[split - 1].count;
+            if left_count == 0 || right_count[split] == 0 {
+                continue;
+            }
+            let left_area = left_box.map_or(0.0, |b| b.surface_area());
+            let cost = TRAVERSAL_COST
+                + INTERSECTION_COST
+                    * (left_area * left_count as f32
+                        + right_area[split] * right_count[split] as f32)
+                    / area_or_one(&bounding_box);
+            if cost < best.map_or(f32::INFINITY, |(best_cost, _, _)| best_cost) {
+                best = Some((cost, axis, split));
+            }
+        }
+    }
+
+    let leaf_cost = INTERSECTION_COST * count as f32;
+    let (axis, mid) = match best {
+        Some((cost, _, _)) if count <= MAX_LEAF_SIZE && leaf_cost <= cost => return leaf,
+        Some((_, axis, split)) => {
+            let mid = partition(primitives, |p| bin_of(p.centroid, axis) < split);
+            (axis, mid)
+        }
+        // every centroid is in the same place, no split separates them.
+        None if count <= MAX_LEAF_SIZE => return leaf,
+        None => (0, count / 2),
+    };
+
+    let (left, right) = primitives.split_at_mut(mid);
+    let (left, right) = if count > PARALLEL_THRESHOLD {
+        rayon::join(
+            || build_node(left, first, depth + 1),
+            || build_node(right, first + mid, depth + 1),
+        )
+    } else {
+        (
+            build_node(left, first, depth + 1),
+            build_node(right, first + mid, depth + 1),
+        )
+    };
+    BuildNode::Interior {
+        bounding_box,
+        axis,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+/// Moves every primitive matching `predicate` to the front, returning how many there are.
+fn partition<P>(primitives: &mut [BuildPrimitive], predicate: P) -> usize
+where
+    P: Fn(&BuildPrimitive) -> bool,
+{
+    let mut mid = 0;
+    for i in 0..primitives.len() {
+        if predicate(&primitives[i]) {
+            primitives.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+fn flatten(node: &BuildNode, nodes: &mut Vec<FlatNode>) {
+    match node {
+        BuildNode::Leaf {
+            bounding_box,
+            first,
+            count,
+        } => nodes.push(FlatNode {
+            bounding_box: *bounding_box,
+            offset: *first as u32,
+            count: *count as u32,
+            axis: 0,
+        }),
+        BuildNode::Interior {
+            bounding_box,
+            axis,
+            left,
+            right,
+        } => {
+            let index = nodes.len();
+            nodes.push(FlatNode {
+                bounding_box: *bounding_box,
+                offset: 0,
+                count: 0,
+                axis: *axis as u8,
+            });
+            flatten(left, nodes);
+            nodes[index].offset = nodes.len() as u32;
+            flatten(right, nodes);
+        }
+    }
+}
+
+/// Statistics of one or more bvh trees. The sah cost is the expected cost of tracing a ray
+/// that hits the root box, in units of `TRAVERSAL_COST` and `INTERSECTION_COST`.
+#[derive(Debug, Clone, Default)]
+pub struct BvhStats {
+    pub trees: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    pub primitives: usize,
+    pub max_depth: usize,
+    pub max_leaf_size: usize,
+    pub sah_cost: f32,
+    pub build_time: Duration,
+}
+
+impl BvhStats {
+    pub fn merge(&mut self, other: &BvhStats) {
+        self.trees += other.trees;
+        self.nodes += other.nodes;
+        self.leaves += other.leaves;
+        self.primitives += other.primitives;
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.max_leaf_size = self.max_leaf_size.max(other.max_leaf_size);
+        self.sah_cost += other.sah_cost;
+        self.build_time += other.build_time;
+    }
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} trees, {} primitives, {} nodes, {} leaves, max depth {}, max leaf size {}, sah cost {:.2}, built in {:.2?}",
+            self.trees,
+            self.primitives,
+            self.nodes,
+            self.leaves,
+            self.max_depth,
+            self.max_leaf_size,
+            self.sah_cost,
+            self.build_time
+        )
+    }
+}
+
+/// A `BvhTree` over a list of objects, built from the scene in `RenderBuilder::build`.
+pub struct Bvh {
+    pub tree: BvhTree,
+    pub objects: Vec<Geometry>,
+}
+
+impl Bvh {
+    /// Every object in `objects` must have a bounding box.
+    pub fn new(objects: Vec<Geometry>, t0: f32, t1: f32) -> TracerResult<Bvh> {
+        let boxes = objects
+            .iter()
+            .map(|object| object.bounding_box(t0, t1).ok_or(BvhBoundingBoxError))
+            .collect::<TracerResult<Vec<Aabb>>>()?;
+        Ok(Bvh {
+            tree: BvhTree::build(&boxes),
+            objects,
+        })
+    }
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut TracerRng) -> Option<HitRecord> {
+        self.tree.hit(r, t_min, t_max, |index, r, t_min, t_max| {
+            self.objects[index].hit(r, t_min, t_max, rng)
+        })
+    }
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        self.tree.bounding_box()
+    }
+}
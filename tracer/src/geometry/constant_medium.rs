@@ -2,10 +2,14 @@ use crate::geometry::aabb::Aabb;
 use crate::geometry::{Geometry, GeometryFile, Hittable};
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
-use log::debug;
+use crate::random::TracerRng;
+use crate::reference::{Reference, SceneNames};
+use rand::Rng;
 
 // this is an odd type as it is a geometry that contains a geometry
 use crate::error::{TracerError, TracerResult};
+use crate::stats::{self, Primitive};
+use crate::validation::{self, Validator};
 use serde::{Deserialize, Serialize};
 
 fn default_density() -> f32 {
@@ -16,7 +20,29 @@ pub struct ConstantMediumBuilder {
     pub boundary: Box<GeometryFile>,
     #[serde(default = "default_density")]
     pub density: f32,
-    pub material_index: usize,
+    #[serde(alias = "material")]
+    pub material_index: Reference,
+}
+
+impl ConstantMediumBuilder {
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.boundary.resolve(names)?;
+        self.material_index.resolve(&names.materials)
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        self.boundary
+            .validate(&format!("{location}.boundary"), validator);
+        validator.check(
+            self.density > 0.0,
+            format!("{location}.density"),
+            "density must be positive",
+        );
+        validator.material(
+            validation::aliased(location, "material_index", "material"),
+            &self.material_index,
+        );
+    }
 }
 
 impl TryInto<Geometry> for ConstantMediumBuilder {
@@ -26,7 +52,7 @@ impl TryInto<Geometry> for ConstantMediumBuilder {
         Ok(Geometry::ConstantMedium(ConstantMedium::new(
             self.boundary.try_into()?,
             self.density,
-            self.material_index,
+            self.material_index.index(),
         )))
     }
 }
@@ -57,14 +83,12 @@ impl ConstantMedium {
 
 // only works for convex shapes
 impl Hittable for ConstantMedium {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        // Print occasional samples when debugging. To enable, set enableDebug true.
-        let debug_threshold = 0.00001;
-
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut TracerRng) -> Option<HitRecord> {
+        stats::count_intersection(Primitive::Medium);
         // Intersect ray with bounding box.
-        if let Some(rec1) = self.boundary.hit(r, -f32::INFINITY, f32::INFINITY) {
+        if let Some(rec1) = self.boundary.hit(r, -f32::INFINITY, f32::INFINITY, rng) {
             // Bounce next ray off bounding box.
-            if let Some(rec2) = self.boundary.hit(r, rec1.root + 0.0001, f32::INFINITY) {
+            if let Some(rec2) = self.boundary.hit(r, rec1.root + 0.0001, f32::INFINITY, rng) {
                 let t1 = rec1.root.max(t_min);
                 let t2 = rec2.root.min(t_max);
 
@@ -76,22 +100,20 @@ impl Hittable for ConstantMedium {
                 // Ray length inside bounding box.
                 let ray_length = r.direction.length();
                 let distance_inside_boundary = (t2 - t1) * ray_length;
-                let hit_distance = self.neg_inv_density * rand::random::<f32>().ln();
+                let hit_distance = self.neg_inv_density * rng.gen::<f32>().ln();
 
                 if hit_distance > distance_inside_boundary {
                     return None;
                 }
 
                 let t = t1 + hit_distance / ray_length;
-                if rand::random::<f32>() < debug_threshold {
-                    debug!("hit_distance = {}, rec.t = {}", hit_distance, t);
-                }
                 Some(HitRecord {
                     root: t,
                     position: r.at(t),
                     normal: Default::default(),
                     front_face: true,
                     material_index: self.material_index,
+                    object_index: 0,
                     u: 0.0,
                     v: 0.0,
                 })
@@ -6,16 +6,38 @@ use crate::geometry::yz_rect::YzRect;
 use crate::geometry::{Geometry, Hittable};
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
+use crate::random::TracerRng;
+use crate::reference::{Reference, SceneNames};
 use glam::Vec3A;
 
 use crate::error::{TracerError, TracerResult};
+use crate::validation::{self, Validator};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct CornellBoxBuilder {
     pub min: Vec3A,
     pub max: Vec3A,
-    pub material_index: usize,
+    #[serde(alias = "material")]
+    pub material_index: Reference,
+}
+
+impl CornellBoxBuilder {
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.material_index.resolve(&names.materials)
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.check(
+            self.min.cmplt(self.max).all(),
+            format!("{location}.max"),
+            "max must be greater than min on every axis",
+        );
+        validator.material(
+            validation::aliased(location, "material_index", "material"),
+            &self.material_index,
+        );
+    }
 }
 
 impl TryInto<Geometry> for CornellBoxBuilder {
@@ -25,7 +47,7 @@ impl TryInto<Geometry> for CornellBoxBuilder {
         Ok(Geometry::CornellBox(CornellBox::new(
             self.min,
             self.max,
-            self.material_index,
+            self.material_index.index(),
         )))
     }
 }
@@ -39,54 +61,12 @@ pub struct CornellBox {
 impl CornellBox {
     pub fn new(p0: Vec3A, p1: Vec3A, material_index: usize) -> Self {
         let sides = HittableList::new(vec![
-            Geometry::XyRect(XyRect::new(
-                p0.x,
-                p1.x,
-                p0.y,
-                p1.y,
-                p1.z,
-                material_index.clone(),
-            )),
-            Geometry::XyRect(XyRect::new(
-                p0.x,
-                p1.x,
-                p0.y,
-                p1.y,
-                p0.z,
-                material_index.clone(),
-            )),
-            Geometry::XzRect(XzRect::new(
-                p0.x,
-                p1.x,
-                p0.z,
-                p1.z,
-                p1.y,
-                material_index.clone(),
-            )),
-            Geometry::XzRect(XzRect::new(
-                p0.x,
-                p1.x,
-                p0.z,
-                p1.z,
-                p0.y,
-                material_index.clone(),
-            )),
-            Geometry::YzRect(YzRect::new(
-                p0.y,
-                p1.y,
-                p0.z,
-                p1.z,
-                p1.x,
-                material_index.clone(),
-            )),
-            Geometry::YzRect(YzRect::new(
-                p0.y,
-                p1.y,
-                p0.z,
-                p1.z,
-                p0.x,
-                material_index.clone(),
-            )),
+            Geometry::XyRect(XyRect::new(p0.x, p1.x, p0.y, p1.y, p1.z, material_index)),
+            Geometry::XyRect(XyRect::new(p0.x, p1.x, p0.y, p1.y, p0.z, material_index)),
+            Geometry::XzRect(XzRect::new(p0.x, p1.x, p0.z, p1.z, p1.y, material_index)),
+            Geometry::XzRect(XzRect::new(p0.x, p1.x, p0.z, p1.z, p0.y, material_index)),
+            Geometry::YzRect(YzRect::new(p0.y, p1.y, p0.z, p1.z, p1.x, material_index)),
+            Geometry::YzRect(YzRect::new(p0.y, p1.y, p0.z, p1.z, p0.x, material_index)),
         ]);
 
         Self {
@@ -98,8 +78,8 @@ impl CornellBox {
 }
 
 impl Hittable for CornellBox {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        self.sides.hit(r, t_min, t_max)
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut TracerRng) -> Option<HitRecord> {
+        self.sides.hit(r, t_min, t_max, rng)
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
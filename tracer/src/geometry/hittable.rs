@@ -3,6 +3,12 @@ use crate::geometry::aabb::Aabb;
 use crate::geometry::{Geometry, GeometryFile, Hittable};
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
+use crate::material::MaterialType;
+use crate::random::TracerRng;
+use crate::reference::{Named, SceneNames};
+use crate::texture::TextureFile;
+use crate::validation::Validator;
+use glam::{Vec2, Vec3A};
 
 use serde::{Deserialize, Serialize};
 
@@ -15,6 +21,28 @@ impl HittableListBuilder {
     pub fn new(objects: Vec<GeometryFile>) -> Self {
         HittableListBuilder { objects }
     }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.objects
+            .iter_mut()
+            .try_for_each(|object| object.resolve(names))
+    }
+
+    pub fn load_meshes(
+        &mut self,
+        materials: &mut Vec<Named<MaterialType>>,
+        textures: &mut Vec<Named<TextureFile>>,
+    ) -> TracerResult<()> {
+        self.objects
+            .iter_mut()
+            .try_for_each(|object| object.load_meshes(materials, textures))
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        for (i, object) in self.objects.iter().enumerate() {
+            object.validate(&format!("{location}.objects[{i}]"), validator);
+        }
+    }
 }
 
 impl TryInto<Geometry> for HittableListBuilder {
@@ -30,6 +58,7 @@ impl TryInto<Geometry> for HittableListBuilder {
     }
 }
 
+#[derive(Default)]
 pub struct HittableList {
     pub objects: Vec<Geometry>,
 }
@@ -41,14 +70,14 @@ impl HittableList {
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut TracerRng) -> Option<HitRecord> {
         let mut closest_so_far = t_max;
         let mut hit_anything = None;
 
         // might be interesting to see if we could presort the scene
         // see if we can early out on the first hit
         for object in &self.objects {
-            if let Some(hit_record) = object.hit(r, t_min, closest_so_far) {
+            if let Some(hit_record) = object.hit(r, t_min, closest_so_far, rng) {
                 closest_so_far = hit_record.root;
                 hit_anything = Some(hit_record);
             }
@@ -57,20 +86,34 @@ impl Hittable for HittableList {
     }
 
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<Aabb> {
+        // a single unbounded object makes the whole list unbounded
+        self.objects
+            .iter()
+            .map(|object| object.bounding_box(t0, t1))
+            .reduce(|output_box, temp_box| Some(Aabb::surrounding_box(&output_box?, &temp_box?)))
+            .flatten()
+    }
+
+    fn pdf_value(&self, origin: Vec3A, direction: Vec3A, rng: &mut TracerRng) -> f32 {
         if self.objects.is_empty() {
-            return None;
+            return 0.0;
         }
+        let sum = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction, rng))
+            .sum::<f32>();
+        sum / self.objects.len() as f32
+    }
 
-        self.objects.iter().fold(None, |output_box, object| {
-            if let Some(temp_box) = object.bounding_box(t0, t1) {
-                if let Some(output_value) = output_box {
-                    Some(Aabb::surrounding_box(&output_value, &temp_box))
-                } else {
-                    Some(temp_box)
-                }
-            } else {
-                None
-            }
-        })
+    /// Samples one of the objects picked uniformly, `u.x` is reused to sample the object.
+    fn random(&self, origin: Vec3A, u: Vec2) -> Vec3A {
+        if self.objects.is_empty() {
+            return Vec3A::X;
+        }
+        let scaled = u.x * self.objects.len() as f32;
+        let index = (scaled as usize).min(self.objects.len() - 1);
+        let u = Vec2::new((scaled - index as f32).min(1.0), u.y);
+        self.objects[index].random(origin, u)
     }
 }
@@ -1,30 +1,48 @@
 use crate::error::{TracerError, TracerResult};
 use crate::geometry::aabb::Aabb;
-// use crate::geometry::bvh::{BvhNode, BvhNodeBuilder};
+use crate::geometry::bvh::BvhNode;
+use crate::geometry::bvh_tree::{Bvh, BvhSplit, BvhStats};
 use crate::geometry::constant_medium::{ConstantMedium, ConstantMediumBuilder};
 use crate::geometry::cornell_box::{CornellBox, CornellBoxBuilder};
 use crate::geometry::hittable::{HittableList, HittableListBuilder};
 use crate::geometry::moving_sphere::MovingSphere;
-use crate::geometry::rotate_y::{RotateY, RotateYBuilder};
+use crate::geometry::obj_mesh::ObjMeshBuilder;
+use crate::geometry::object::Object;
+use crate::geometry::rotate_y::RotateYBuilder;
 use crate::geometry::sphere::Sphere;
-use crate::geometry::translate::{Translate, TranslateBuilder};
+use crate::geometry::transform::{Transform, TransformBuilder};
+use crate::geometry::translate::TranslateBuilder;
+use crate::geometry::triangle::Triangle;
+use crate::geometry::triangle_mesh::{TriangleMesh, TriangleMeshBuilder};
 use crate::geometry::xy_rect::XyRect;
 use crate::geometry::xz_rect::XzRect;
 use crate::geometry::yz_rect::YzRect;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
-
-use std::rc::Rc;
+use crate::material::{Material, MaterialType};
+use crate::random::{self, TracerRng};
+use crate::reference::{Named, SceneNames};
+use crate::texture::TextureFile;
+use crate::validation::Validator;
+use glam::{Vec2, Vec3A};
+use std::sync::Arc;
+use std::time::Instant;
 
 pub mod aabb;
-// pub mod bvh;
+pub mod bvh;
+pub mod bvh_tree;
 pub mod constant_medium;
 pub mod cornell_box;
 pub mod hittable;
 pub mod moving_sphere;
+pub mod obj_mesh;
+pub mod object;
 pub mod rotate_y;
 pub mod sphere;
+pub mod transform;
 pub mod translate;
+pub mod triangle;
+pub mod triangle_mesh;
 pub mod xy_rect;
 pub mod xz_rect;
 pub mod yz_rect;
@@ -40,11 +58,109 @@ pub enum GeometryFile {
     YzRect(YzRect),
     Translate(TranslateBuilder),
     RotateY(RotateYBuilder),
+    Transform(TransformBuilder),
     CornellBox(CornellBoxBuilder),
-    // BvhNode(BvhNodeBuilder),
     ConstantMedium(ConstantMediumBuilder),
     MovingSphere(MovingSphere),
     HittableList(HittableListBuilder),
+    Triangle(Triangle),
+    TriangleMesh(TriangleMeshBuilder),
+    ObjMesh(ObjMeshBuilder),
+}
+
+impl GeometryFile {
+    /// Replaces named material references with their index.
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        match self {
+            GeometryFile::Sphere(sphere) => sphere.resolve(names),
+            GeometryFile::XyRect(xy_rect) => xy_rect.resolve(names),
+            GeometryFile::XzRect(xz_rect) => xz_rect.resolve(names),
+            GeometryFile::YzRect(yz_rect) => yz_rect.resolve(names),
+            GeometryFile::Translate(translate) => translate.resolve(names),
+            GeometryFile::RotateY(rotate_y) => rotate_y.resolve(names),
+            GeometryFile::Transform(transform) => transform.resolve(names),
+            GeometryFile::CornellBox(cornell_box) => cornell_box.resolve(names),
+            GeometryFile::ConstantMedium(constant_medium) => constant_medium.resolve(names),
+            GeometryFile::MovingSphere(moving_sphere) => moving_sphere.resolve(names),
+            GeometryFile::HittableList(hittable_list) => hittable_list.resolve(names),
+            GeometryFile::Triangle(triangle) => triangle.resolve(names),
+            GeometryFile::TriangleMesh(triangle_mesh) => triangle_mesh.resolve(names),
+            GeometryFile::ObjMesh(obj_mesh) => obj_mesh.resolve(names),
+        }
+    }
+
+    /// Loads every `ObjMesh` in the tree into triangle meshes, appending their materials to
+    /// `materials` and `textures`.
+    pub fn load_meshes(
+        &mut self,
+        materials: &mut Vec<Named<MaterialType>>,
+        textures: &mut Vec<Named<TextureFile>>,
+    ) -> TracerResult<()> {
+        match self {
+            GeometryFile::ObjMesh(obj_mesh) => {
+                *self = GeometryFile::HittableList(obj_mesh.load(materials, textures)?);
+                Ok(())
+            }
+            GeometryFile::Translate(translate) => translate.object.load_meshes(materials, textures),
+            GeometryFile::RotateY(rotate_y) => rotate_y.object.load_meshes(materials, textures),
+            GeometryFile::Transform(transform) => transform.object.load_meshes(materials, textures),
+            GeometryFile::ConstantMedium(constant_medium) => {
+                constant_medium.boundary.load_meshes(materials, textures)
+            }
+            GeometryFile::HittableList(hittable_list) => {
+                hittable_list.load_meshes(materials, textures)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Records every problem with this object, `location` is its json path.
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        match self {
+            GeometryFile::Sphere(sphere) => {
+                sphere.validate(&format!("{location}.Sphere"), validator)
+            }
+            GeometryFile::XyRect(xy_rect) => {
+                xy_rect.validate(&format!("{location}.XyRect"), validator)
+            }
+            GeometryFile::XzRect(xz_rect) => {
+                xz_rect.validate(&format!("{location}.XzRect"), validator)
+            }
+            GeometryFile::YzRect(yz_rect) => {
+                yz_rect.validate(&format!("{location}.YzRect"), validator)
+            }
+            GeometryFile::Translate(translate) => {
+                translate.validate(&format!("{location}.Translate"), validator)
+            }
+            GeometryFile::RotateY(rotate_y) => {
+                rotate_y.validate(&format!("{location}.RotateY"), validator)
+            }
+            GeometryFile::Transform(transform) => {
+                transform.validate(&format!("{location}.Transform"), validator)
+            }
+            GeometryFile::CornellBox(cornell_box) => {
+                cornell_box.validate(&format!("{location}.CornellBox"), validator)
+            }
+            GeometryFile::ConstantMedium(constant_medium) => {
+                constant_medium.validate(&format!("{location}.ConstantMedium"), validator)
+            }
+            GeometryFile::MovingSphere(moving_sphere) => {
+                moving_sphere.validate(&format!("{location}.MovingSphere"), validator)
+            }
+            GeometryFile::HittableList(hittable_list) => {
+                hittable_list.validate(&format!("{location}.HittableList"), validator)
+            }
+            GeometryFile::Triangle(triangle) => {
+                triangle.validate(&format!("{location}.Triangle"), validator)
+            }
+            GeometryFile::TriangleMesh(triangle_mesh) => {
+                triangle_mesh.validate(&format!("{location}.TriangleMesh"), validator)
+            }
+            GeometryFile::ObjMesh(obj_mesh) => {
+                obj_mesh.validate(&format!("{location}.ObjMesh"), validator)
+            }
+        }
+    }
 }
 
 impl TryInto<Geometry> for GeometryFile {
@@ -58,11 +174,16 @@ impl TryInto<Geometry> for GeometryFile {
             GeometryFile::YzRect(yz_rect) => Ok(Geometry::YzRect(yz_rect)),
             GeometryFile::Translate(translate) => Ok(translate.try_into()?),
             GeometryFile::RotateY(rotate_y) => Ok(rotate_y.try_into()?),
+            GeometryFile::Transform(transform) => Ok(transform.try_into()?),
             GeometryFile::CornellBox(cornell_box) => Ok(cornell_box.try_into()?),
             GeometryFile::ConstantMedium(constant_medium) => Ok(constant_medium.try_into()?),
             GeometryFile::MovingSphere(moving_sphere) => Ok(Geometry::MovingSphere(moving_sphere)),
             GeometryFile::HittableList(hittable_list) => Ok(hittable_list.try_into()?),
-            // GeometryFile::BvhNode(bvh_node) => Ok(bvh_node.try_into()?),
+            GeometryFile::Triangle(triangle) => Ok(Geometry::Triangle(triangle)),
+            GeometryFile::TriangleMesh(triangle_mesh) => Ok(triangle_mesh.try_into()?),
+            GeometryFile::ObjMesh(obj_mesh) => Err(TracerError::UnloadedObj {
+                path: obj_mesh.path.into(),
+            }),
         }
     }
 }
@@ -78,6 +199,7 @@ impl TryInto<Box<Geometry>> for Box<GeometryFile> {
             GeometryFile::YzRect(yz_rect) => Ok(Box::new(Geometry::YzRect(yz_rect))),
             GeometryFile::Translate(translate) => Ok(Box::new(translate.try_into()?)),
             GeometryFile::RotateY(rotate_y) => Ok(Box::new(rotate_y.try_into()?)),
+            GeometryFile::Transform(transform) => Ok(Box::new(transform.try_into()?)),
             GeometryFile::CornellBox(cornell_box) => Ok(Box::new(cornell_box.try_into()?)),
             GeometryFile::ConstantMedium(constant_medium) => {
                 Ok(Box::new(constant_medium.try_into()?))
@@ -86,31 +208,11 @@ impl TryInto<Box<Geometry>> for Box<GeometryFile> {
                 Ok(Box::new(Geometry::MovingSphere(moving_sphere)))
             }
             GeometryFile::HittableList(hittable_list) => Ok(Box::new(hittable_list.try_into()?)),
-            // GeometryFile::BvhNode(bvh_node) => Ok(Box::new(bvh_node.try_into()?)),
-        }
-    }
-}
-
-impl TryInto<Rc<Geometry>> for Box<GeometryFile> {
-    type Error = TracerError;
-
-    fn try_into(self) -> TracerResult<Rc<Geometry>> {
-        match *self {
-            GeometryFile::Sphere(sphere) => Ok(Rc::new(Geometry::Sphere(sphere))),
-            GeometryFile::XyRect(xy_rect) => Ok(Rc::new(Geometry::XyRect(xy_rect))),
-            GeometryFile::XzRect(xz_rect) => Ok(Rc::new(Geometry::XzRect(xz_rect))),
-            GeometryFile::YzRect(yz_rect) => Ok(Rc::new(Geometry::YzRect(yz_rect))),
-            GeometryFile::Translate(translate) => Ok(Rc::new(translate.try_into()?)),
-            GeometryFile::RotateY(rotate_y) => Ok(Rc::new(rotate_y.try_into()?)),
-            GeometryFile::CornellBox(cornell_box) => Ok(Rc::new(cornell_box.try_into()?)),
-            GeometryFile::ConstantMedium(constant_medium) => {
-                Ok(Rc::new(constant_medium.try_into()?))
-            }
-            GeometryFile::MovingSphere(moving_sphere) => {
-                Ok(Rc::new(Geometry::MovingSphere(moving_sphere)))
-            }
-            GeometryFile::HittableList(hittable_list) => Ok(Rc::new(hittable_list.try_into()?)),
-            // GeometryFile::BvhNode(bvh_node) => Ok(Rc::new(bvh_node.try_into()?)),
+            GeometryFile::Triangle(triangle) => Ok(Box::new(Geometry::Triangle(triangle))),
+            GeometryFile::TriangleMesh(triangle_mesh) => Ok(Box::new(triangle_mesh.try_into()?)),
+            GeometryFile::ObjMesh(obj_mesh) => Err(TracerError::UnloadedObj {
+                path: obj_mesh.path.into(),
+            }),
         }
     }
 }
@@ -120,29 +222,225 @@ pub enum Geometry {
     XyRect(XyRect),
     XzRect(XzRect),
     YzRect(YzRect),
-    Translate(Translate),
-    RotateY(RotateY),
+    Transform(Transform),
     CornellBox(CornellBox),
     ConstantMedium(ConstantMedium),
-    // BvhNode(BvhNode),
+    BvhNode(BvhNode),
+    Bvh(Bvh),
     MovingSphere(MovingSphere),
     HittableList(HittableList),
+    Triangle(Triangle),
+    TriangleMesh(TriangleMesh),
+    Object(Object),
+}
+
+impl Geometry {
+    /// Replaces every `HittableList` in the tree with a bvh over its objects. Nested lists are
+    /// merged into their parent first so all their objects end up in one tree. The statistics of
+    /// every tree built are added to `stats`.
+    pub fn accelerate(
+        self,
+        t0: f32,
+        t1: f32,
+        split: BvhSplit,
+        stats: &mut BvhStats,
+    ) -> TracerResult<Geometry> {
+        match self {
+            Geometry::HittableList(hittable_list) => {
+                let objects = flatten(hittable_list.objects)
+                    .into_iter()
+                    .map(|object| object.accelerate(t0, t1, split, stats))
+                    .collect::<TracerResult<Vec<Geometry>>>()?;
+                build_bvh(objects, t0, t1, split, stats)
+            }
+            Geometry::Transform(mut transform) => {
+                transform.object = Box::new(transform.object.accelerate(t0, t1, split, stats)?);
+                Ok(Geometry::Transform(transform))
+            }
+            Geometry::ConstantMedium(mut constant_medium) => {
+                constant_medium.boundary =
+                    Box::new(constant_medium.boundary.accelerate(t0, t1, split, stats)?);
+                Ok(Geometry::ConstantMedium(constant_medium))
+            }
+            Geometry::Object(mut object) => {
+                object.object = Box::new(object.object.accelerate(t0, t1, split, stats)?);
+                Ok(Geometry::Object(object))
+            }
+            // meshes always carry their own bvh, only count it.
+            Geometry::TriangleMesh(triangle_mesh) => {
+                stats.merge(&triangle_mesh.bvh.stats());
+                Ok(Geometry::TriangleMesh(triangle_mesh))
+            }
+            geometry => Ok(geometry),
+        }
+    }
+
+    /// Wraps every object of a `HittableList` in an `Object` holding its index in the list. Lists
+    /// nested in an object keep their own tree when accelerated.
+    pub fn tag_objects(self) -> Geometry {
+        match self {
+            Geometry::HittableList(hittable_list) => Geometry::HittableList(HittableList::new(
+                hittable_list
+                    .objects
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, object)| Geometry::Object(Object::new(index, object)))
+                    .collect(),
+            )),
+            geometry => Geometry::Object(Object::new(0, geometry)),
+        }
+    }
+
+    /// Adds the emitters light can be sampled from to `lights` along with their material, spheres
+    /// and rects outside of transforms. The materials of every other primitive emitting light are
+    /// added to `unsampled`.
+    pub fn collect_lights(
+        &self,
+        materials: &[MaterialType],
+        lights: &mut Vec<(Geometry, usize)>,
+        unsampled: &mut Vec<usize>,
+    ) {
+        self.collect_emitters(true, materials, lights, unsampled)
+    }
+
+    fn collect_emitters(
+        &self,
+        sampled: bool,
+        materials: &[MaterialType],
+        lights: &mut Vec<(Geometry, usize)>,
+        unsampled: &mut Vec<usize>,
+    ) {
+        let (light, material_index) = match self {
+            Geometry::Sphere(sphere) => (
+                Some(Geometry::Sphere(sphere.clone())),
+                sphere.material_index.index(),
+            ),
+            Geometry::XyRect(xy_rect) => (
+                Some(Geometry::XyRect(xy_rect.clone())),
+                xy_rect.material_index.index(),
+            ),
+            Geometry::XzRect(xz_rect) => (
+                Some(Geometry::XzRect(xz_rect.clone())),
+                xz_rect.material_index.index(),
+            ),
+            Geometry::YzRect(yz_rect) => (
+                Some(Geometry::YzRect(yz_rect.clone())),
+                yz_rect.material_index.index(),
+            ),
+            Geometry::MovingSphere(moving_sphere) => (None, moving_sphere.material_index.index()),
+            Geometry::Triangle(triangle) => (None, triangle.material_index.index()),
+            Geometry::TriangleMesh(triangle_mesh) => (None, triangle_mesh.material_index),
+            Geometry::ConstantMedium(constant_medium) => (None, constant_medium.material_index),
+            Geometry::Transform(transform) => {
+                return transform
+                    .object
+                    .collect_emitters(false, materials, lights, unsampled);
+            }
+            Geometry::Object(object) => {
+                return object
+                    .object
+                    .collect_emitters(sampled, materials, lights, unsampled);
+            }
+            Geometry::BvhNode(bvh_node) => {
+                for child in [&bvh_node.left, &bvh_node.right] {
+                    child.collect_emitters(sampled, materials, lights, unsampled);
+                }
+                return;
+            }
+            Geometry::Bvh(Bvh { objects, .. })
+            | Geometry::HittableList(HittableList { objects })
+            | Geometry::CornellBox(CornellBox {
+                sides: HittableList { objects },
+                ..
+            }) => {
+                for object in objects {
+                    object.collect_emitters(sampled, materials, lights, unsampled);
+                }
+                return;
+            }
+        };
+        if materials[material_index].emitted().is_some() {
+            match light {
+                Some(light) if sampled => lights.push((light, material_index)),
+                _ => unsampled.push(material_index),
+            }
+        }
+    }
+}
+
+/// Builds a bvh over `objects`. Objects without a bounding box, like a `HittableList` containing
+/// an infinite object, can't be sorted into the tree and are kept next to it in a list.
+fn build_bvh(
+    objects: Vec<Geometry>,
+    t0: f32,
+    t1: f32,
+    split: BvhSplit,
+    stats: &mut BvhStats,
+) -> TracerResult<Geometry> {
+    let (bounded, mut unbounded): (Vec<Geometry>, Vec<Geometry>) = objects
+        .into_iter()
+        .partition(|object| object.bounding_box(t0, t1).is_some());
+    if bounded.len() < 2 {
+        unbounded.extend(bounded);
+        return Ok(Geometry::HittableList(HittableList::new(unbounded)));
+    }
+
+    let node = match split {
+        BvhSplit::Sah => {
+            let bvh = Bvh::new(bounded, t0, t1)?;
+            stats.merge(&bvh.tree.stats());
+            Geometry::Bvh(bvh)
+        }
+        BvhSplit::Median => {
+            let start = Instant::now();
+            // the axes don't change the image, a fixed seed only keeps the tree the same.
+            let mut rng = random::seeded_rng(0);
+            let objects = bounded.into_iter().map(Arc::new).collect();
+            let node = BvhNode::from_list(objects, t0, t1, &mut rng)?;
+            let mut node_stats = node.stats();
+            node_stats.build_time = start.elapsed();
+            stats.merge(&node_stats);
+            Geometry::BvhNode(node)
+        }
+    };
+    if unbounded.is_empty() {
+        Ok(node)
+    } else {
+        unbounded.insert(0, node);
+        Ok(Geometry::HittableList(HittableList::new(unbounded)))
+    }
+}
+
+fn flatten(objects: Vec<Geometry>) -> Vec<Geometry> {
+    let mut flat = Vec::with_capacity(objects.len());
+    for object in objects {
+        match object {
+            Geometry::HittableList(hittable_list) => flat.extend(flatten(hittable_list.objects)),
+            object => flat.push(object),
+        }
+    }
+    flat
 }
 
 impl Hittable for Geometry {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, rng: &mut TracerRng) -> Option<HitRecord> {
         match self {
-            Geometry::Sphere(sphere) => sphere.hit(ray, t_min, t_max),
-            Geometry::XyRect(xy_rect) => xy_rect.hit(ray, t_min, t_max),
-            Geometry::XzRect(xz_rect) => xz_rect.hit(ray, t_min, t_max),
-            Geometry::YzRect(yz_rect) => yz_rect.hit(ray, t_min, t_max),
-            Geometry::Translate(translate) => translate.hit(ray, t_min, t_max),
-            Geometry::RotateY(rotate_y) => rotate_y.hit(ray, t_min, t_max),
-            Geometry::CornellBox(cornell_box) => cornell_box.hit(ray, t_min, t_max),
-            Geometry::ConstantMedium(constant_medium) => constant_medium.hit(ray, t_min, t_max),
-            // Geometry::BvhNode(bvh_node) => bvh_node.hit(ray, t_min, t_max),
-            Geometry::MovingSphere(moving_sphere) => moving_sphere.hit(ray, t_min, t_max),
-            Geometry::HittableList(hittable_list) => hittable_list.hit(ray, t_min, t_max),
+            Geometry::Sphere(sphere) => sphere.hit(ray, t_min, t_max, rng),
+            Geometry::XyRect(xy_rect) => xy_rect.hit(ray, t_min, t_max, rng),
+            Geometry::XzRect(xz_rect) => xz_rect.hit(ray, t_min, t_max, rng),
+            Geometry::YzRect(yz_rect) => yz_rect.hit(ray, t_min, t_max, rng),
+            Geometry::Transform(transform) => transform.hit(ray, t_min, t_max, rng),
+            Geometry::CornellBox(cornell_box) => cornell_box.hit(ray, t_min, t_max, rng),
+            Geometry::ConstantMedium(constant_medium) => {
+                constant_medium.hit(ray, t_min, t_max, rng)
+            }
+            Geometry::BvhNode(bvh_node) => bvh_node.hit(ray, t_min, t_max, rng),
+            Geometry::Bvh(bvh) => bvh.hit(ray, t_min, t_max, rng),
+            Geometry::MovingSphere(moving_sphere) => moving_sphere.hit(ray, t_min, t_max, rng),
+            Geometry::HittableList(hittable_list) => hittable_list.hit(ray, t_min, t_max, rng),
+            Geometry::Triangle(triangle) => triangle.hit(ray, t_min, t_max, rng),
+            Geometry::TriangleMesh(triangle_mesh) => triangle_mesh.hit(ray, t_min, t_max, rng),
+            Geometry::Object(object) => object.hit(ray, t_min, t_max, rng),
         }
     }
 
@@ -152,19 +450,113 @@ impl Hittable for Geometry {
             Geometry::XyRect(xy_rect) => xy_rect.bounding_box(t0, t1),
             Geometry::XzRect(xz_rect) => xz_rect.bounding_box(t0, t1),
             Geometry::YzRect(yz_rect) => yz_rect.bounding_box(t0, t1),
-            Geometry::Translate(translate) => translate.bounding_box(t0, t1),
-            Geometry::RotateY(rotate_y) => rotate_y.bounding_box(t0, t1),
+            Geometry::Transform(transform) => transform.bounding_box(t0, t1),
             Geometry::CornellBox(cornell_box) => cornell_box.bounding_box(t0, t1),
             Geometry::ConstantMedium(constant_medium) => constant_medium.bounding_box(t0, t1),
-            // Geometry::BvhNode(bvh_node) => bvh_node.bounding_box(t0, t1),
+            Geometry::BvhNode(bvh_node) => bvh_node.bounding_box(t0, t1),
+            Geometry::Bvh(bvh) => bvh.bounding_box(t0, t1),
             Geometry::MovingSphere(moving_sphere) => moving_sphere.bounding_box(t0, t1),
             Geometry::HittableList(hittable_list) => hittable_list.bounding_box(t0, t1),
+            Geometry::Triangle(triangle) => triangle.bounding_box(t0, t1),
+            Geometry::TriangleMesh(triangle_mesh) => triangle_mesh.bounding_box(t0, t1),
+            Geometry::Object(object) => object.bounding_box(t0, t1),
+        }
+    }
+
+    fn pdf_value(&self, origin: Vec3A, direction: Vec3A, rng: &mut TracerRng) -> f32 {
+        match self {
+            Geometry::Sphere(sphere) => sphere.pdf_value(origin, direction, rng),
+            Geometry::XyRect(xy_rect) => xy_rect.pdf_value(origin, direction, rng),
+            Geometry::XzRect(xz_rect) => xz_rect.pdf_value(origin, direction, rng),
+            Geometry::YzRect(yz_rect) => yz_rect.pdf_value(origin, direction, rng),
+            Geometry::HittableList(hittable_list) => {
+                hittable_list.pdf_value(origin, direction, rng)
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Vec3A, u: Vec2) -> Vec3A {
+        match self {
+            Geometry::Sphere(sphere) => sphere.random(origin, u),
+            Geometry::XyRect(xy_rect) => xy_rect.random(origin, u),
+            Geometry::XzRect(xz_rect) => xz_rect.random(origin, u),
+            Geometry::YzRect(yz_rect) => yz_rect.random(origin, u),
+            Geometry::HittableList(hittable_list) => hittable_list.random(origin, u),
+            _ => Vec3A::X,
         }
     }
 }
 
 // enum Hittable
 pub trait Hittable {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut TracerRng) -> Option<HitRecord>;
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<Aabb>;
+    /// Density over solid angle of `random` picking `direction` from `origin`, zero for objects
+    /// that can't be sampled.
+    fn pdf_value(&self, _origin: Vec3A, _direction: Vec3A, _rng: &mut TracerRng) -> f32 {
+        0.0
+    }
+    /// Direction from `origin` to a point on the object, picked with the uniform sample `u`.
+    fn random(&self, _origin: Vec3A, _u: Vec2) -> Vec3A {
+        Vec3A::X
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3;
+    use glam::Vec3A;
+    use rand::Rng;
+
+    /// Rects along every axis and spheres, overlapping so the closest hit matters.
+    fn scene() -> Geometry {
+        let mut objects = vec![
+            Geometry::XyRect(XyRect::new(0.0, 5.0, 0.0, 5.0, 5.0, 0)),
+            Geometry::XzRect(XzRect::new(0.0, 5.0, 0.0, 5.0, 0.0, 1)),
+            Geometry::XzRect(XzRect::new(1.0, 2.0, 3.0, 4.5, 4.9, 2)),
+            Geometry::YzRect(YzRect::new(0.0, 5.0, 0.0, 5.0, 0.0, 3)),
+            Geometry::YzRect(YzRect::new(1.0, 3.0, 0.5, 2.0, 2.5, 4)),
+        ];
+        let mut rng = random::seeded_rng(6);
+        for material_index in 5..25 {
+            let center = Vec3A::new(rng.gen(), rng.gen(), rng.gen()) * 5.0;
+            objects.push(Geometry::Sphere(Sphere::new(
+                center,
+                rng.gen_range(0.1..0.8),
+                material_index,
+            )));
+        }
+        Geometry::HittableList(HittableList::new(objects))
+    }
+
+    #[test]
+    fn bvh_hits_match_the_list() {
+        for split in [BvhSplit::Sah, BvhSplit::Median] {
+            let list = scene();
+            let bvh = scene()
+                .accelerate(0.0, 1.0, split, &mut BvhStats::default())
+                .unwrap();
+            let mut rng = random::seeded_rng(7);
+            for _ in 0..20_000 {
+                let origin = Vec3A::new(rng.gen(), rng.gen(), rng.gen()) * 7.0 - 1.0;
+                let ray = Ray::new(origin, vec3::random_in_unit_sphere(&mut rng), 0.0);
+                let expected = list.hit(&ray, 0.001, f32::INFINITY, &mut rng);
+                let hit = bvh.hit(&ray, 0.001, f32::INFINITY, &mut rng);
+                match (expected, hit) {
+                    (None, None) => {}
+                    (Some(expected), Some(hit)) => {
+                        assert_eq!(hit.material_index, expected.material_index);
+                        assert_eq!(hit.root, expected.root);
+                    }
+                    (expected, hit) => panic!(
+                        "{split:?} hit {:?} where the list hit {:?}",
+                        hit.map(|hit| hit.material_index),
+                        expected.map(|hit| hit.material_index)
+                    ),
+                }
+            }
+        }
+    }
 }
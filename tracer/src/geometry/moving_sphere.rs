@@ -1,8 +1,13 @@
+use crate::error::TracerResult;
 use crate::geometry::aabb::Aabb;
 use crate::geometry::sphere::get_sphere_uv;
 use crate::geometry::Hittable;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
+use crate::random::TracerRng;
+use crate::reference::{Reference, SceneNames};
+use crate::stats::{self, Primitive};
+use crate::validation::{self, Validator};
 
 use glam::Vec3A;
 
@@ -13,7 +18,8 @@ pub struct MovingSphere {
     pub center0: Vec3A,
     pub center1: Vec3A,
     pub radius: f32,
-    pub material_index: usize,
+    #[serde(alias = "material")]
+    pub material_index: Reference,
 }
 
 impl MovingSphere {
@@ -22,13 +28,29 @@ impl MovingSphere {
             center0,
             center1,
             radius,
-            material_index,
+            material_index: Reference::Index(material_index),
         }
     }
 
     pub fn center(&self, time: f32) -> Vec3A {
         self.center0 + ((time - 0.0) / (1.0 - 0.0)) * (self.center1 - self.center0)
     }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.material_index.resolve(&names.materials)
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.check(
+            self.radius > 0.0,
+            format!("{location}.radius"),
+            "radius must be positive",
+        );
+        validator.material(
+            validation::aliased(location, "material_index", "material"),
+            &self.material_index,
+        );
+    }
 }
 
 impl Hittable for MovingSphere {
@@ -44,7 +66,8 @@ impl Hittable for MovingSphere {
         Some(Aabb::surrounding_box(&box0, &box1))
     }
 
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, _rng: &mut TracerRng) -> Option<HitRecord> {
+        stats::count_intersection(Primitive::MovingSphere);
         let oc = ray.origin - self.center(ray.time);
         let a = ray.direction.length_squared();
         let half_b = oc.dot(ray.direction);
@@ -76,7 +99,8 @@ impl Hittable for MovingSphere {
                 position,
                 normal: outward_normal,
                 front_face: true,
-                material_index: self.material_index,
+                material_index: self.material_index.index(),
+                object_index: 0,
                 u,
                 v,
             })
@@ -88,7 +112,8 @@ impl Hittable for MovingSphere {
                 position,
                 normal: outward_normal,
                 front_face: false,
-                material_index: self.material_index,
+                material_index: self.material_index.index(),
+                object_index: 0,
                 u,
                 v,
             })
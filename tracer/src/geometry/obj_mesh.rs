@@ -0,0 +1,309 @@
+use crate::error::{TracerError, TracerResult};
+use crate::geometry::hittable::HittableListBuilder;
+use crate::geometry::triangle_mesh::TriangleMeshBuilder;
+use crate::geometry::GeometryFile;
+use crate::material::dieletric::Dieletric;
+use crate::material::lambertian::Lambertian;
+use crate::material::metal::Metal;
+use crate::material::MaterialType;
+use crate::reference::{Named, Reference, SceneNames};
+use crate::texture::image::ImageBuilder;
+use crate::texture::solid::Solid;
+use crate::texture::TextureFile;
+use crate::validation::{self, Validator};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use glam::{Vec2, Vec3A};
+use serde::{Deserialize, Serialize};
+
+/// Meshes loaded from a Wavefront obj file. Every object or group in the file becomes a
+/// `TriangleMesh`, the materials of its mtl file are appended to the scene's materials and
+/// textures when the scene is built.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ObjMeshBuilder {
+    pub path: String,
+    /// Material for groups without a `usemtl`, or whose mtl material can't be loaded.
+    #[serde(default, alias = "material", skip_serializing_if = "Option::is_none")]
+    pub material_index: Option<Reference>,
+    /// Scene materials used instead of the mtl material with the same name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub materials: BTreeMap<String, Reference>,
+}
+
+impl ObjMeshBuilder {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            material_index: None,
+            materials: BTreeMap::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        if let Some(material_index) = &mut self.material_index {
+            material_index.resolve(&names.materials)?;
+        }
+        self.materials
+            .values_mut()
+            .try_for_each(|material| material.resolve(&names.materials))
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.file(format!("{location}.path"), &self.path);
+        if let Some(material_index) = &self.material_index {
+            validator.material(
+                validation::aliased(location, "material_index", "material"),
+                material_index,
+            );
+        }
+        for (name, material) in &self.materials {
+            validator.material(format!("{location}.materials.{name}"), material);
+        }
+    }
+
+    /// Loads the obj into a list of meshes, appending its mtl materials to `materials` and
+    /// `textures`.
+    pub fn load(
+        &self,
+        materials: &mut Vec<Named<MaterialType>>,
+        textures: &mut Vec<Named<TextureFile>>,
+    ) -> TracerResult<HittableListBuilder> {
+        let path = Path::new(&self.path);
+        let (models, mtl) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS).map_err(|source| {
+            TracerError::ObjError {
+                path: path.into(),
+                source,
+            }
+        })?;
+        let mtl = mtl.unwrap_or_else(|e| {
+            log::warn!("could not load the materials of {}: {e}", path.display());
+            Vec::new()
+        });
+
+        // only convert the mtl materials that are used and not replaced.
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut converted: Vec<Option<Reference>> = vec![None; mtl.len()];
+        let mut objects = Vec::with_capacity(models.len());
+        for model in models {
+            let mesh = model.mesh;
+            if mesh.indices.is_empty() {
+                continue;
+            }
+            let material = match mesh.material_id {
+                Some(id) if id < mtl.len() => match self.materials.get(&mtl[id].name) {
+                    Some(material) => Some(material.clone()),
+                    None => Some(
+                        converted[id]
+                            .get_or_insert_with(|| {
+                                convert(&mtl[id], directory, materials, textures).into()
+                            })
+                            .clone(),
+                    ),
+                },
+                _ => self.material_index.clone(),
+            }
+            .ok_or_else(|| TracerError::ObjMissingMaterial {
+                path: path.into(),
+                model: model.name.clone(),
+            })?;
+            objects.push(GeometryFile::TriangleMesh(TriangleMeshBuilder {
+                positions: mesh
+                    .positions
+                    .chunks_exact(3)
+                    .map(Vec3A::from_slice)
+                    .collect(),
+                normals: mesh
+                    .normals
+                    .chunks_exact(3)
+                    .map(Vec3A::from_slice)
+                    .collect(),
+                uvs: mesh
+                    .texcoords
+                    .chunks_exact(2)
+                    .map(Vec2::from_slice)
+                    .collect(),
+                indices: mesh
+                    .indices
+                    .chunks_exact(3)
+                    .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                    .collect(),
+                material_index: material,
+            }));
+        }
+        Ok(HittableListBuilder::new(objects))
+    }
+}
+
+fn push<T>(list: &mut Vec<Named<T>>, value: T) -> usize {
+    list.push(value.into());
+    list.len() - 1
+}
+
+fn parse_color(value: &str) -> Option<Vec3A> {
+    let channels = value
+        .split_whitespace()
+        .map(|c| c.parse::<f32>().ok())
+        .collect::<Option<Vec<f32>>>()?;
+    match channels[..] {
+        [r, g, b] => Some(Vec3A::new(r, g, b)),
+        [c] => Some(Vec3A::splat(c)),
+        _ => None,
+    }
+}
+
+/// Maps an mtl material onto the closest material type:
+/// - transparent materials (`d` or `Tr`, or a glass `illum`) become `Dieletric` with `Ni`,
+/// - mirror materials (`illum 3`, or `Ks` brighter than `Kd`) become `Metal`, `Ns` sets the fuzz,
+/// - everything else becomes `Lambertian` with `map_Kd` or `Kd`.
+///
+/// `Ke` is used as the emitted color. Returns the index of the new material.
+fn convert(
+    material: &tobj::Material,
+    directory: &Path,
+    materials: &mut Vec<Named<MaterialType>>,
+    textures: &mut Vec<Named<TextureFile>>,
+) -> usize {
+    let param = |key: &str| material.unknown_param.get(key).and_then(|v| parse_color(v));
+    let emitted = param("Ke")
+        .filter(|color| color.max_element() > 0.0)
+        .map(|color| push(textures, TextureFile::Solid(Solid::new(color))));
+
+    let diffuse = Vec3A::from(material.diffuse);
+    let specular = Vec3A::from(material.specular);
+    let transparency = param("Tr").map_or(0.0, |tr| tr.x);
+    let value = if material.dissolve < 1.0
+        || transparency > 0.0
+        || matches!(material.illumination_model, Some(4 | 6 | 7 | 9))
+    {
+        let filter = param("Tf").unwrap_or(Vec3A::ONE);
+        let texture = push(textures, TextureFile::Solid(Solid::new(filter)));
+        let ref_idx = if material.optical_density > 1.0 {
+            material.optical_density
+        } else {
+            1.5
+        };
+        MaterialType::Dieletric(Dieletric::new(ref_idx, texture, emitted))
+    } else if material.illumination_model == Some(3)
+        || specular.max_element() > diffuse.max_element()
+    {
+        let texture = push(textures, TextureFile::Solid(Solid::new(specular)));
+        // common mapping from a phong exponent to a roughness.
+        let fuzz = (2.0 / (material.shininess.max(0.0) + 2.0)).sqrt();
+        MaterialType::Metal(Metal::new(texture, fuzz, emitted))
+    } else {
+        let texture = if material.diffuse_texture.is_empty() {
+            TextureFile::Solid(Solid::new(diffuse))
+        } else {
+            let path = directory.join(&material.diffuse_texture);
+            TextureFile::Image(ImageBuilder::new(path.to_string_lossy().into_owned()))
+        };
+        let texture = push(textures, texture);
+        MaterialType::Lambertian(Lambertian::new(texture, emitted))
+    };
+
+    push(materials, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const OBJ: &str = "mtllib fixture.mtl
+v 0 0 0
+v 1 0 0
+v 1 0 1
+v 0 0 1
+v 0 1 0
+v 1 1 0
+o plain
+f 1 2 5
+o floor
+usemtl red
+f 1 2 3 4
+o lid
+usemtl mirror
+f 5 6 2
+o gem
+usemtl glass
+f 4 3 6
+";
+
+    const MTL: &str = "newmtl red
+Kd 0.8 0.1 0.1
+newmtl mirror
+Kd 0.1 0.1 0.1
+Ks 0.9 0.9 0.9
+illum 3
+newmtl glass
+Kd 1 1 1
+Ni 1.5
+d 0
+";
+
+    /// Writes the fixture obj and mtl into a directory of their own and returns the obj path.
+    fn fixture(test: &str) -> String {
+        let directory = std::env::temp_dir().join(format!("tracer-{test}-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("fixture.mtl"), MTL).unwrap();
+        let path = directory.join("fixture.obj");
+        fs::write(&path, OBJ).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn mesh(object: &GeometryFile) -> &TriangleMeshBuilder {
+        match object {
+            GeometryFile::TriangleMesh(mesh) => mesh,
+            _ => panic!("obj objects load as triangle meshes"),
+        }
+    }
+
+    #[test]
+    fn loads_objects_with_their_mtl_materials() {
+        let mut builder = ObjMeshBuilder::new(fixture("obj-materials"));
+        builder.material_index = Some(Reference::Index(0));
+        builder.materials.insert(
+            "glass".to_string(),
+            Reference::Name("scene glass".to_string()),
+        );
+        let mut materials = vec![Named::from(MaterialType::Lambertian(Lambertian::new(
+            0, None,
+        )))];
+        let mut textures = vec![Named::from(TextureFile::Solid(Solid::new(Vec3A::ONE)))];
+        let list = builder.load(&mut materials, &mut textures).unwrap();
+
+        let [plain, floor, lid, gem] = &list.objects[..] else {
+            panic!("expected four objects, got {}", list.objects.len());
+        };
+        let (plain, floor, lid, gem) = (mesh(plain), mesh(floor), mesh(lid), mesh(gem));
+        // the quad is split into two triangles.
+        assert_eq!(floor.indices.len(), 2);
+        assert_eq!(plain.indices.len(), 1);
+
+        // objects without usemtl fall back to the builder's material.
+        assert_eq!(plain.material_index, Reference::Index(0));
+        // mtl materials are appended in the order they are first used.
+        assert_eq!(floor.material_index, Reference::Index(1));
+        assert_eq!(lid.material_index, Reference::Index(2));
+        assert!(matches!(materials[1].value, MaterialType::Lambertian(_)));
+        assert!(matches!(materials[2].value, MaterialType::Metal(_)));
+        // replaced materials are not converted.
+        assert_eq!(
+            gem.material_index,
+            Reference::Name("scene glass".to_string())
+        );
+        assert_eq!(materials.len(), 3);
+        assert_eq!(textures.len(), 3);
+    }
+
+    #[test]
+    fn objects_without_a_material_are_errors() {
+        let builder = ObjMeshBuilder::new(fixture("obj-missing"));
+        let result = builder.load(&mut Vec::new(), &mut Vec::new());
+        assert!(matches!(
+            result,
+            Err(TracerError::ObjMissingMaterial { .. })
+        ));
+    }
+}
@@ -0,0 +1,32 @@
+use crate::geometry::aabb::Aabb;
+use crate::geometry::{Geometry, Hittable};
+use crate::intersection::hit_record::HitRecord;
+use crate::intersection::ray::Ray;
+use crate::random::TracerRng;
+
+/// One of the world's top level objects, it stamps its index on every hit for the object id aov.
+pub struct Object {
+    pub index: usize,
+    pub object: Box<Geometry>,
+}
+
+impl Object {
+    pub fn new(index: usize, object: Geometry) -> Self {
+        Object {
+            index,
+            object: Box::new(object),
+        }
+    }
+}
+
+impl Hittable for Object {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut TracerRng) -> Option<HitRecord> {
+        let mut rec = self.object.hit(r, t_min, t_max, rng)?;
+        rec.object_index = self.index;
+        Some(rec)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<Aabb> {
+        self.object.bounding_box(t0, t1)
+    }
+}
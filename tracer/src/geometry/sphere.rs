@@ -1,16 +1,22 @@
+use crate::error::TracerResult;
 use crate::geometry::aabb::Aabb;
 use crate::geometry::Hittable;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
+use crate::random::TracerRng;
+use crate::reference::{Reference, SceneNames};
+use crate::stats::{self, Primitive};
+use crate::validation::{self, Validator};
 
-use glam::Vec3A;
+use glam::{Vec2, Vec3A};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Sphere {
     pub center: Vec3A,
     pub radius: f32,
-    pub material_index: usize,
+    #[serde(alias = "material")]
+    pub material_index: Reference,
 }
 
 pub struct Square {
@@ -25,7 +31,7 @@ impl Square {
 }
 
 impl Hittable for Square {
-    fn hit(&self, _r: &Ray, _t_min: f32, _t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, _r: &Ray, _t_min: f32, _t_max: f32, _rng: &mut TracerRng) -> Option<HitRecord> {
         None
     }
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
@@ -34,32 +40,25 @@ impl Hittable for Square {
 }
 
 pub fn get_sphere_uv(p: Vec3A) -> (f32, f32) {
-    let theta = -p.y.acos();
-    let phi = -p.z.atan2(p.x) + std::f32::consts::PI;
+    let theta = (-p.y).acos();
+    let phi = (-p.z).atan2(p.x) + std::f32::consts::PI;
     (phi / (TWO_PI), theta / std::f32::consts::PI)
 }
 
 static TWO_PI: f32 = std::f32::consts::PI * 2.0;
 
 impl Sphere {
-    pub fn new(center: Vec3A, radius: f32, material_index: usize) -> Sphere {
-        Sphere {
-            center,
-            radius,
-            material_index,
-        }
-    }
-}
-
-impl Hittable for Sphere {
-    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
-        Some(Aabb::new(
-            self.center - Vec3A::splat(self.radius),
-            self.center + Vec3A::splat(self.radius),
-        ))
+    /// Cosine of the half angle of the cone the sphere covers seen from `origin`, `None` inside of
+    /// it.
+    fn cos_theta_max(&self, origin: Vec3A) -> Option<f32> {
+        let distance_squared = (self.center - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        (distance_squared > radius_squared)
+            .then(|| (1.0 - radius_squared / distance_squared).sqrt())
     }
 
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    /// `hit` without counting the test, for sampling.
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
         let oc = ray.origin - self.center;
         let a = ray.direction.length_squared();
         let half_b = oc.dot(ray.direction);
@@ -90,7 +89,8 @@ impl Hittable for Sphere {
                 position,
                 normal: outward_normal,
                 front_face: true,
-                material_index: self.material_index,
+                material_index: self.material_index.index(),
+                object_index: 0,
                 u,
                 v,
             })
@@ -102,10 +102,77 @@ impl Hittable for Sphere {
                 position,
                 normal: -outward_normal,
                 front_face: false,
-                material_index: self.material_index,
+                material_index: self.material_index.index(),
+                object_index: 0,
                 u,
                 v,
             })
         }
     }
 }
+
+impl Sphere {
+    pub fn new(center: Vec3A, radius: f32, material_index: usize) -> Sphere {
+        Sphere {
+            center,
+            radius,
+            material_index: Reference::Index(material_index),
+        }
+    }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.material_index.resolve(&names.materials)
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.check(
+            self.radius > 0.0,
+            format!("{location}.radius"),
+            "radius must be positive",
+        );
+        validator.material(
+            validation::aliased(location, "material_index", "material"),
+            &self.material_index,
+        );
+    }
+}
+
+impl Hittable for Sphere {
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(Aabb::new(
+            self.center - Vec3A::splat(self.radius),
+            self.center + Vec3A::splat(self.radius),
+        ))
+    }
+
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, _rng: &mut TracerRng) -> Option<HitRecord> {
+        stats::count_intersection(Primitive::Sphere);
+        self.intersect(ray, t_min, t_max)
+    }
+
+    fn pdf_value(&self, origin: Vec3A, direction: Vec3A, _rng: &mut TracerRng) -> f32 {
+        let Some(cos_theta_max) = self.cos_theta_max(origin) else {
+            return 0.0;
+        };
+        if self
+            .intersect(&Ray::new(origin, direction, 0.0), 0.001, f32::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+        1.0 / (TWO_PI * (1.0 - cos_theta_max))
+    }
+
+    /// Samples the cone of directions the sphere covers uniformly.
+    fn random(&self, origin: Vec3A, u: Vec2) -> Vec3A {
+        let w = (self.center - origin).normalize();
+        let Some(cos_theta_max) = self.cos_theta_max(origin) else {
+            return w;
+        };
+        let z = 1.0 + u.y * (cos_theta_max - 1.0);
+        let phi = TWO_PI * u.x;
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        let (a, b) = w.any_orthonormal_pair();
+        a * phi.cos() * sin_theta + b * phi.sin() * sin_theta + w * z
+    }
+}
@@ -0,0 +1,320 @@
+use crate::error::{TracerError, TracerResult};
+use crate::geometry::aabb::Aabb;
+use crate::geometry::{Geometry, GeometryFile, Hittable};
+use crate::intersection::hit_record::HitRecord;
+use crate::intersection::ray::Ray;
+use crate::random::TracerRng;
+use crate::reference::SceneNames;
+use crate::validation::Validator;
+use glam::{Affine3A, EulerRot, Mat3A, Mat4, Quat, Vec3A};
+
+use serde::{Deserialize, Serialize};
+
+/// One step of a `Transform`, angles are in degrees.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum TransformComponent {
+    Translate(Vec3A),
+    RotateX(f32),
+    RotateY(f32),
+    RotateZ(f32),
+    Rotate {
+        axis: Vec3A,
+        angle: f32,
+    },
+    /// Rotation about x, then y, then z.
+    Euler(Vec3A),
+    /// Rotation as `[x, y, z, w]`, normalized before use.
+    Quaternion(Quat),
+    Scale(Vec3A),
+    /// Moves the origin to `from` and turns the object's -z axis towards `to`, like the camera.
+    LookAt {
+        from: Vec3A,
+        to: Vec3A,
+        up: Vec3A,
+    },
+    /// Affine matrix given as rows, the last row must be `[0, 0, 0, 1]`.
+    Matrix([[f32; 4]; 4]),
+}
+
+impl TransformComponent {
+    pub fn matrix(&self) -> Affine3A {
+        match self {
+            TransformComponent::Translate(offset) => Affine3A::from_translation((*offset).into()),
+            TransformComponent::RotateX(angle) => Affine3A::from_rotation_x(angle.to_radians()),
+            TransformComponent::RotateY(angle) => Affine3A::from_rotation_y(angle.to_radians()),
+            TransformComponent::RotateZ(angle) => Affine3A::from_rotation_z(angle.to_radians()),
+            TransformComponent::Rotate { axis, angle } => {
+                Affine3A::from_axis_angle(axis.normalize().into(), angle.to_radians())
+            }
+            TransformComponent::Euler(angles) => Affine3A::from_quat(Quat::from_euler(
+                EulerRot::ZYX,
+                angles.z.to_radians(),
+                angles.y.to_radians(),
+                angles.x.to_radians(),
+            )),
+            TransformComponent::Quaternion(rotation) => Affine3A::from_quat(rotation.normalize()),
+            TransformComponent::Scale(scale) => Affine3A::from_scale((*scale).into()),
+            TransformComponent::LookAt { from, to, up } => {
+                Affine3A::look_at_rh((*from).into(), (*to).into(), (*up).into()).inverse()
+            }
+            TransformComponent::Matrix(rows) => {
+                Affine3A::from_mat4(Mat4::from_cols_array_2d(rows).transpose())
+            }
+        }
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        let finite = |v: &[f32]| v.iter().all(|x| x.is_finite());
+        match self {
+            TransformComponent::Translate(v)
+            | TransformComponent::Euler(v)
+            | TransformComponent::Scale(v) => {
+                validator.check(finite(&v.to_array()), location, "values must be finite")
+            }
+            TransformComponent::RotateX(angle)
+            | TransformComponent::RotateY(angle)
+            | TransformComponent::RotateZ(angle) => {
+                validator.check(angle.is_finite(), location, "angle must be finite")
+            }
+            TransformComponent::Rotate { axis, angle } => {
+                validator.check(
+                    axis.is_finite() && axis.length_squared() > 0.0,
+                    format!("{location}.axis"),
+                    "axis must be finite and not zero",
+                );
+                validator.check(
+                    angle.is_finite(),
+                    format!("{location}.angle"),
+                    "angle must be finite",
+                );
+            }
+            TransformComponent::Quaternion(rotation) => validator.check(
+                rotation.is_finite() && rotation.length_squared() > 0.0,
+                location,
+                "quaternion must be finite and not zero",
+            ),
+            TransformComponent::LookAt { from, to, up } => {
+                validator.check(
+                    from != to,
+                    format!("{location}.to"),
+                    "from and to must be different points",
+                );
+                validator.check(
+                    up.cross(*to - *from).length_squared() > 0.0,
+                    format!("{location}.up"),
+                    "up must not be parallel to the view direction",
+                );
+            }
+            TransformComponent::Matrix(rows) => {
+                validator.check(
+                    rows.iter().all(|row| finite(row)),
+                    location,
+                    "values must be finite",
+                );
+                validator.check(
+                    rows[3] == [0.0, 0.0, 0.0, 1.0],
+                    format!("{location}[3]"),
+                    "the last row must be [0, 0, 0, 1]",
+                );
+            }
+        }
+    }
+}
+
+/// Applies `transforms` to `object`, the first transform is applied first.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TransformBuilder {
+    pub object: Box<GeometryFile>,
+    pub transforms: Vec<TransformComponent>,
+}
+
+impl TransformBuilder {
+    pub fn new(object: Box<GeometryFile>, transforms: Vec<TransformComponent>) -> Self {
+        Self { object, transforms }
+    }
+
+    /// The combined object to world matrix.
+    pub fn matrix(&self) -> Affine3A {
+        self.transforms
+            .iter()
+            .fold(Affine3A::IDENTITY, |matrix, t| t.matrix() * matrix)
+    }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.object.resolve(names)
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        self.object
+            .validate(&format!("{location}.object"), validator);
+        let before = validator.error_count();
+        for (i, transform) in self.transforms.iter().enumerate() {
+            transform.validate(&format!("{location}.transforms[{i}]"), validator);
+        }
+        // only check the product once every part of it is valid.
+        if validator.error_count() == before {
+            let determinant = self.matrix().matrix3.determinant();
+            validator.check(
+                determinant.is_finite() && determinant.abs() > 1e-12,
+                format!("{location}.transforms"),
+                "transform must be invertible",
+            );
+        }
+    }
+}
+
+impl TryInto<Geometry> for TransformBuilder {
+    type Error = TracerError;
+
+    fn try_into(self) -> TracerResult<Geometry> {
+        let matrix = self.matrix();
+        Ok(Geometry::Transform(
+            Transform::new(self.object.try_into()?, matrix).flatten(),
+        ))
+    }
+}
+
+/// An object placed in the world by an affine matrix. Rays are moved into object space, hits are
+/// moved back with normals transformed by the inverse transpose.
+pub struct Transform {
+    pub object: Box<Geometry>,
+    pub matrix: Affine3A,
+    pub inverse: Affine3A,
+    normal_matrix: Mat3A,
+}
+
+impl Transform {
+    pub fn new(object: Box<Geometry>, matrix: Affine3A) -> Self {
+        let inverse = matrix.inverse();
+        Self {
+            object,
+            matrix,
+            inverse,
+            normal_matrix: inverse.matrix3.transpose(),
+        }
+    }
+
+    pub fn translate(object: Box<Geometry>, offset: Vec3A) -> Self {
+        Self::new(object, Affine3A::from_translation(offset.into()))
+    }
+
+    pub fn rotate_y(object: Box<Geometry>, angle: f32) -> Self {
+        Self::new(object, Affine3A::from_rotation_y(angle.to_radians()))
+    }
+
+    /// Combines this transform with the one of its object when that's a transform as well.
+    pub fn flatten(self) -> Self {
+        match *self.object {
+            Geometry::Transform(inner) => Self::new(inner.object, self.matrix * inner.matrix),
+            object => Self::new(Box::new(object), self.matrix),
+        }
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, rng: &mut TracerRng) -> Option<HitRecord> {
+        // the direction isn't normalized so t is the same in both spaces.
+        let object_ray = Ray::new(
+            self.inverse.transform_point3a(r.origin),
+            self.inverse.transform_vector3a(r.direction),
+            r.time,
+        );
+        let mut rec = self.object.hit(&object_ray, t_min, t_max, rng)?;
+        rec.position = self.matrix.transform_point3a(rec.position);
+        // a linear map keeps the normal on the side facing the ray, front_face stays valid.
+        rec.normal = (self.normal_matrix * rec.normal).normalize();
+        Some(rec)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<Aabb> {
+        let object_box = self.object.bounding_box(t0, t1)?;
+        let (min, max) = (0..8)
+            .map(|corner| {
+                let pick = |bit: usize, axis: usize| {
+                    if corner & bit == 0 {
+                        object_box.min[axis]
+                    } else {
+                        object_box.max[axis]
+                    }
+                };
+                self.matrix
+                    .transform_point3a(Vec3A::new(pick(1, 0), pick(2, 1), pick(4, 2)))
+            })
+            .fold(
+                (Vec3A::splat(f32::INFINITY), Vec3A::splat(f32::NEG_INFINITY)),
+                |(min, max), p| (min.min(p), max.max(p)),
+            );
+        Some(Aabb::new(min, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::{self, TracerRng};
+
+    fn rng() -> TracerRng {
+        random::seeded_rng(0)
+    }
+    use crate::geometry::sphere::Sphere;
+
+    fn unit_sphere(matrix: Affine3A) -> Transform {
+        Transform::new(
+            Box::new(Geometry::Sphere(Sphere::new(Vec3A::ZERO, 1.0, 0))),
+            matrix,
+        )
+    }
+
+    #[test]
+    fn bounding_box_covers_the_moved_corners() {
+        let stretched = unit_sphere(
+            Affine3A::from_translation([1.0, 2.0, 3.0].into())
+                * Affine3A::from_scale([2.0, 1.0, 0.5].into()),
+        );
+        let bounds = stretched.bounding_box(0.0, 1.0).unwrap();
+        assert!(bounds.min.abs_diff_eq(Vec3A::new(-1.0, 1.0, 2.5), 1e-5));
+        assert!(bounds.max.abs_diff_eq(Vec3A::new(3.0, 3.0, 3.5), 1e-5));
+
+        // the box of the object's box turned by 45 degrees.
+        let turned = unit_sphere(
+            Affine3A::from_rotation_z(45f32.to_radians())
+                * Affine3A::from_scale([2.0, 1.0, 1.0].into()),
+        );
+        let bounds = turned.bounding_box(0.0, 1.0).unwrap();
+        let half = 3.0 / 2f32.sqrt();
+        assert!(bounds.min.abs_diff_eq(Vec3A::new(-half, -half, -1.0), 1e-5));
+        assert!(bounds.max.abs_diff_eq(Vec3A::new(half, half, 1.0), 1e-5));
+    }
+
+    #[test]
+    fn normals_follow_a_non_uniform_scale() {
+        let ellipsoid = unit_sphere(Affine3A::from_scale([2.0, 1.0, 1.0].into()));
+        for angle in [0.0f32, 0.3, 0.8, 1.2] {
+            // point on x²/4 + y² = 1, whose normal is the gradient (x / 2, 2y).
+            let point = Vec3A::new(2.0 * angle.cos(), angle.sin(), 0.0);
+            let normal = Vec3A::new(point.x / 2.0, 2.0 * point.y, 0.0).normalize();
+            let ray = Ray::new(point + 3.0 * normal, -normal, 0.0);
+            let hit = ellipsoid
+                .hit(&ray, 0.001, f32::INFINITY, &mut rng())
+                .unwrap();
+            assert!((hit.root - 3.0).abs() < 1e-4, "{}", hit.root);
+            assert!(hit.position.abs_diff_eq(point, 1e-4));
+            assert!(hit.normal.abs_diff_eq(normal, 1e-4), "{}", hit.normal);
+            assert!(hit.front_face);
+        }
+    }
+
+    #[test]
+    fn nested_transforms_are_flattened() {
+        let inner = unit_sphere(Affine3A::from_scale([2.0, 2.0, 2.0].into()));
+        let outer = Transform::new(
+            Box::new(Geometry::Transform(inner)),
+            Affine3A::from_translation([0.0, 5.0, 0.0].into()),
+        )
+        .flatten();
+        assert!(matches!(*outer.object, Geometry::Sphere(_)));
+        let bounds = outer.bounding_box(0.0, 1.0).unwrap();
+        assert!(bounds.min.abs_diff_eq(Vec3A::new(-2.0, 3.0, -2.0), 1e-5));
+        assert!(bounds.max.abs_diff_eq(Vec3A::new(2.0, 7.0, 2.0), 1e-5));
+    }
+}
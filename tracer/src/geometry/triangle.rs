@@ -0,0 +1,267 @@
+use crate::error::TracerResult;
+use crate::geometry::aabb::Aabb;
+use crate::geometry::Hittable;
+use crate::intersection::hit_record::HitRecord;
+use crate::intersection::ray::Ray;
+use crate::random::TracerRng;
+use crate::reference::{Reference, SceneNames};
+use crate::stats::{self, Primitive};
+use crate::validation::{self, Validator};
+
+use glam::{Vec2, Vec3A};
+use serde::{Deserialize, Serialize};
+
+/// A single triangle, vertices are counter clockwise when seen from the front.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Triangle {
+    pub vertices: [Vec3A; 3],
+    /// Per vertex shading normals, the face normal is used without them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normals: Option<[Vec3A; 3]>,
+    /// Per vertex texture coordinates, the barycentric coordinates are used without them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uvs: Option<[Vec2; 3]>,
+    #[serde(alias = "material")]
+    pub material_index: Reference,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Vec3A; 3], material_index: usize) -> Self {
+        Self {
+            vertices,
+            normals: None,
+            uvs: None,
+            material_index: Reference::Index(material_index),
+        }
+    }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.material_index.resolve(&names.materials)
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        let [p0, p1, p2] = self.vertices;
+        validator.check(
+            (p1 - p0).cross(p2 - p0).length_squared() > 0.0,
+            format!("{location}.vertices"),
+            "triangle has no area",
+        );
+        if let Some(normals) = &self.normals {
+            validator.check(
+                normals.iter().all(|n| n.length_squared() > 0.0),
+                format!("{location}.normals"),
+                "normals must not be zero",
+            );
+        }
+        validator.material(
+            validation::aliased(location, "material_index", "material"),
+            &self.material_index,
+        );
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, _rng: &mut TracerRng) -> Option<HitRecord> {
+        let (t, b1, b2) = intersect(&self.vertices, ray, t_min, t_max)?;
+        Some(hit_record(
+            ray,
+            t,
+            b1,
+            b2,
+            &self.vertices,
+            self.normals.as_ref(),
+            self.uvs.as_ref(),
+            self.material_index.index(),
+        ))
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(bounds(&self.vertices))
+    }
+}
+
+/// Box around a triangle, padded so axis aligned triangles don't produce a flat box.
+pub fn bounds(vertices: &[Vec3A; 3]) -> Aabb {
+    let [p0, p1, p2] = *vertices;
+    Aabb::new(
+        p0.min(p1).min(p2) - Vec3A::splat(0.0001),
+        p0.max(p1).max(p2) + Vec3A::splat(0.0001),
+    )
+}
+
+/// Möller-Trumbore intersection, returns the ray parameter and the barycentric coordinates of
+/// the second and third vertex.
+pub fn intersect(
+    vertices: &[Vec3A; 3],
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<(f32, f32, f32)> {
+    stats::count_intersection(Primitive::Triangle);
+    let [p0, p1, p2] = *vertices;
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let p = ray.direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inv_determinant = 1.0 / determinant;
+
+    let to_origin = ray.origin - p0;
+    let b1 = to_origin.dot(p) * inv_determinant;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let q = to_origin.cross(edge1);
+    let b2 = ray.direction.dot(q) * inv_determinant;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) * inv_determinant;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+/// Fills a hit record for a hit at barycentric `b1`, `b2`, interpolating the vertex attributes.
+#[allow(clippy::too_many_arguments)]
+pub fn hit_record(
+    ray: &Ray,
+    t: f32,
+    b1: f32,
+    b2: f32,
+    vertices: &[Vec3A; 3],
+    normals: Option<&[Vec3A; 3]>,
+    uvs: Option<&[Vec2; 3]>,
+    material_index: usize,
+) -> HitRecord {
+    let b0 = 1.0 - b1 - b2;
+    let [p0, p1, p2] = *vertices;
+    let face_normal = (p1 - p0).cross(p2 - p0).normalize();
+    let normal = match normals {
+        Some([n0, n1, n2]) => {
+            let normal = (b0 * *n0 + b1 * *n1 + b2 * *n2).normalize();
+            // keep the shading normal on the same side as the surface.
+            if normal.dot(face_normal) < 0.0 {
+                -normal
+            } else {
+                normal
+            }
+        }
+        None => face_normal,
+    };
+    let (u, v) = match uvs {
+        Some([uv0, uv1, uv2]) => {
+            let uv = b0 * *uv0 + b1 * *uv1 + b2 * *uv2;
+            (uv.x, uv.y)
+        }
+        None => (b1, b2),
+    };
+
+    let front_face = ray.direction.dot(face_normal) < 0.0;
+    HitRecord {
+        root: t,
+        position: ray.at(t),
+        normal: if front_face { normal } else { -normal },
+        front_face,
+        material_index,
+        object_index: 0,
+        u,
+        v,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::{self, TracerRng};
+
+    fn rng() -> TracerRng {
+        random::seeded_rng(0)
+    }
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            [
+                Vec3A::new(0.0, 0.0, 0.0),
+                Vec3A::new(1.0, 0.0, 0.0),
+                Vec3A::new(0.0, 1.0, 0.0),
+            ],
+            3,
+        )
+    }
+
+    fn down_z(x: f32, y: f32) -> Ray {
+        Ray::new(Vec3A::new(x, y, 1.0), Vec3A::new(0.0, 0.0, -1.0), 0.0)
+    }
+
+    #[test]
+    fn hits_with_barycentric_uvs() {
+        let hit = triangle()
+            .hit(&down_z(0.25, 0.5), 0.001, f32::INFINITY, &mut rng())
+            .unwrap();
+        assert!((hit.root - 1.0).abs() < 1e-6);
+        assert!(hit.position.abs_diff_eq(Vec3A::new(0.25, 0.5, 0.0), 1e-6));
+        assert!((hit.u - 0.25).abs() < 1e-6 && (hit.v - 0.5).abs() < 1e-6);
+        assert!(hit.front_face);
+        assert_eq!(hit.normal, Vec3A::Z);
+        assert_eq!(hit.material_index, 3);
+    }
+
+    #[test]
+    fn misses_outside_the_edges_and_range() {
+        let triangle = triangle();
+        assert!(triangle
+            .hit(&down_z(0.6, 0.6), 0.001, f32::INFINITY, &mut rng())
+            .is_none());
+        assert!(triangle
+            .hit(&down_z(-0.1, 0.5), 0.001, f32::INFINITY, &mut rng())
+            .is_none());
+        assert!(triangle
+            .hit(&down_z(0.25, 0.25), 0.001, 0.5, &mut rng())
+            .is_none());
+    }
+
+    #[test]
+    fn interpolates_uvs_and_shading_normals() {
+        let mut triangle = triangle();
+        triangle.uvs = Some([
+            Vec2::new(0.5, 0.5),
+            Vec2::new(1.0, 0.5),
+            Vec2::new(0.5, 1.0),
+        ]);
+        triangle.normals = Some([
+            Vec3A::Z,
+            Vec3A::new(1.0, 0.0, 1.0).normalize(),
+            Vec3A::new(0.0, 1.0, 1.0).normalize(),
+        ]);
+        let hit = triangle
+            .hit(&down_z(0.0, 0.0), 0.001, f32::INFINITY, &mut rng())
+            .unwrap();
+        assert!((hit.u - 0.5).abs() < 1e-6 && (hit.v - 0.5).abs() < 1e-6);
+        assert!(hit.normal.abs_diff_eq(Vec3A::Z, 1e-6));
+
+        let hit = triangle
+            .hit(&down_z(1.0, 0.0), 0.001, f32::INFINITY, &mut rng())
+            .unwrap();
+        assert!((hit.u - 1.0).abs() < 1e-6 && (hit.v - 0.5).abs() < 1e-6);
+        assert!(hit
+            .normal
+            .abs_diff_eq(Vec3A::new(1.0, 0.0, 1.0).normalize(), 1e-6));
+    }
+
+    #[test]
+    fn back_faces_flip_the_shading_normal() {
+        let mut triangle = triangle();
+        // a shading normal pointing away from the face normal is turned around first.
+        triangle.normals = Some([-Vec3A::Z; 3]);
+        let ray = Ray::new(Vec3A::new(0.25, 0.25, -1.0), Vec3A::Z, 0.0);
+        let hit = triangle
+            .hit(&ray, 0.001, f32::INFINITY, &mut rng())
+            .unwrap();
+        assert!(!hit.front_face);
+        assert!(hit.normal.abs_diff_eq(-Vec3A::Z, 1e-6));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::error::{TracerError, TracerResult};
+use crate::geometry::aabb::Aabb;
+use crate::geometry::bvh_tree::BvhTree;
+use crate::geometry::triangle;
+use crate::geometry::{Geometry, Hittable};
+use crate::intersection::hit_record::HitRecord;
+use crate::intersection::ray::Ray;
+use crate::random::TracerRng;
+use crate::reference::{Reference, SceneNames};
+use crate::validation::{self, Validator};
+use std::sync::Arc;
+
+use glam::{Vec2, Vec3A};
+use serde::{Deserialize, Serialize};
+
+/// Indexed triangle mesh. `normals` and `uvs` are optional, when present they hold one entry per
+/// position and are indexed by the same `indices`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TriangleMeshBuilder {
+    pub positions: Vec<Vec3A>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub normals: Vec<Vec3A>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uvs: Vec<Vec2>,
+    pub indices: Vec<[u32; 3]>,
+    #[serde(alias = "material")]
+    pub material_index: Reference,
+}
+
+impl TriangleMeshBuilder {
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.material_index.resolve(&names.materials)
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.check(
+            !self.indices.is_empty(),
+            format!("{location}.indices"),
+            "mesh has no triangles",
+        );
+        let count = self.positions.len();
+        if let Some(i) = self
+            .indices
+            .iter()
+            .position(|triangle| triangle.iter().any(|&index| index as usize >= count))
+        {
+            validator.error(
+                format!("{location}.indices[{i}]"),
+                format!("index out of range, the mesh has {count} positions"),
+            );
+        }
+        validator.check(
+            self.normals.is_empty() || self.normals.len() == count,
+            format!("{location}.normals"),
+            "there must be one normal per position",
+        );
+        validator.check(
+            self.uvs.is_empty() || self.uvs.len() == count,
+            format!("{location}.uvs"),
+            "there must be one uv per position",
+        );
+        validator.material(
+            validation::aliased(location, "material_index", "material"),
+            &self.material_index,
+        );
+    }
+}
+
+impl TryInto<Geometry> for TriangleMeshBuilder {
+    type Error = TracerError;
+
+    fn try_into(self) -> TracerResult<Geometry> {
+        let mesh = MeshData {
+            positions: self.positions,
+            normals: self.normals,
+            uvs: self.uvs,
+            indices: self.indices,
+        };
+        Ok(Geometry::TriangleMesh(TriangleMesh::new(
+            Arc::new(mesh),
+            self.material_index.index(),
+        )))
+    }
+}
+
+/// Vertex buffers of a mesh, shared between every object using it.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub positions: Vec<Vec3A>,
+    pub normals: Vec<Vec3A>,
+    pub uvs: Vec<Vec2>,
+    pub indices: Vec<[u32; 3]>,
+}
+
+impl MeshData {
+    fn attribute<T: Copy>(buffer: &[T], indices: [u32; 3]) -> [T; 3] {
+        indices.map(|index| buffer[index as usize])
+    }
+
+    pub fn vertices(&self, triangle: usize) -> [Vec3A; 3] {
+        Self::attribute(&self.positions, self.indices[triangle])
+    }
+
+    pub fn normals(&self, triangle: usize) -> Option<[Vec3A; 3]> {
+        (!self.normals.is_empty()).then(|| Self::attribute(&self.normals, self.indices[triangle]))
+    }
+
+    pub fn uvs(&self, triangle: usize) -> Option<[Vec2; 3]> {
+        (!self.uvs.is_empty()).then(|| Self::attribute(&self.uvs, self.indices[triangle]))
+    }
+}
+
+/// Triangle mesh with its own bvh over its triangles.
+#[derive(Debug, Clone)]
+pub struct TriangleMesh {
+    pub mesh: Arc<MeshData>,
+    pub bvh: Arc<BvhTree>,
+    pub material_index: usize,
+}
+
+impl TriangleMesh {
+    pub fn new(mesh: Arc<MeshData>, material_index: usize) -> Self {
+        let boxes = (0..mesh.indices.len())
+            .map(|i| triangle::bounds(&mesh.vertices(i)))
+            .collect::<Vec<Aabb>>();
+        Self {
+            bvh: Arc::new(BvhTree::build(&boxes)),
+            mesh,
+            material_index,
+        }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, _rng: &mut TracerRng) -> Option<HitRecord> {
+        self.bvh.hit(r, t_min, t_max, |i, r, t_min, t_max| {
+            let vertices = self.mesh.vertices(i);
+            let (t, b1, b2) = triangle::intersect(&vertices, r, t_min, t_max)?;
+            Some(triangle::hit_record(
+                r,
+                t,
+                b1,
+                b2,
+                &vertices,
+                self.mesh.normals(i).as_ref(),
+                self.mesh.uvs(i).as_ref(),
+                self.material_index,
+            ))
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        self.bvh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::{self, TracerRng};
+
+    fn rng() -> TracerRng {
+        random::seeded_rng(0)
+    }
+
+    /// Unit quad at z = 0 split along its diagonal, with uvs matching x and y.
+    fn quad() -> TriangleMesh {
+        let positions = vec![
+            Vec3A::new(0.0, 0.0, 0.0),
+            Vec3A::new(1.0, 0.0, 0.0),
+            Vec3A::new(1.0, 1.0, 0.0),
+            Vec3A::new(0.0, 1.0, 0.0),
+        ];
+        let uvs = positions.iter().map(|p| Vec2::new(p.x, p.y)).collect();
+        let mesh = MeshData {
+            positions,
+            normals: vec![Vec3A::Z; 4],
+            uvs,
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+        };
+        TriangleMesh::new(Arc::new(mesh), 2)
+    }
+
+    #[test]
+    fn hits_every_triangle_with_interpolated_uvs() {
+        let mesh = quad();
+        for (x, y) in [(0.75, 0.25), (0.25, 0.75), (0.5, 0.1), (0.1, 0.9)] {
+            let ray = Ray::new(Vec3A::new(x, y, 2.0), -Vec3A::Z, 0.0);
+            let hit = mesh.hit(&ray, 0.001, f32::INFINITY, &mut rng()).unwrap();
+            assert!((hit.root - 2.0).abs() < 1e-6);
+            assert!((hit.u - x).abs() < 1e-5 && (hit.v - y).abs() < 1e-5);
+            assert!(hit.normal.abs_diff_eq(Vec3A::Z, 1e-6));
+            assert_eq!(hit.material_index, 2);
+        }
+        let ray = Ray::new(Vec3A::new(1.5, 0.5, 2.0), -Vec3A::Z, 0.0);
+        assert!(mesh.hit(&ray, 0.001, f32::INFINITY, &mut rng()).is_none());
+    }
+
+    #[test]
+    fn bounds_cover_the_mesh() {
+        let bounds = quad().bounding_box(0.0, 1.0).unwrap();
+        assert!(bounds.min.cmple(Vec3A::new(0.0, 0.0, 0.0)).all());
+        assert!(bounds.max.cmpge(Vec3A::new(1.0, 1.0, 0.0)).all());
+    }
+}
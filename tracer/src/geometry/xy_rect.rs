@@ -1,15 +1,21 @@
+use crate::error::TracerResult;
 use crate::geometry::aabb::Aabb;
 use crate::geometry::Hittable;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
+use crate::random::TracerRng;
+use crate::reference::{Reference, SceneNames};
+use crate::stats::{self, Primitive};
+use crate::validation::{self, Validator};
 
-use glam::Vec3A;
+use glam::{Vec2, Vec3A};
 
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct XyRect {
-    material_index: usize,
+    #[serde(alias = "material")]
+    pub material_index: Reference,
     x0: f32,
     x1: f32,
     y0: f32,
@@ -20,7 +26,7 @@ pub struct XyRect {
 impl XyRect {
     pub fn new(x0: f32, x1: f32, y0: f32, y1: f32, k: f32, material_index: usize) -> Self {
         Self {
-            material_index,
+            material_index: Reference::Index(material_index),
             x0,
             x1,
             y0,
@@ -28,12 +34,33 @@ impl XyRect {
             k,
         }
     }
-}
 
-impl Hittable for XyRect {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.material_index.resolve(&names.materials)
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.check(
+            self.x0 < self.x1,
+            format!("{location}.x1"),
+            "x1 must be greater than x0",
+        );
+        validator.check(
+            self.y0 < self.y1,
+            format!("{location}.y1"),
+            "y1 must be greater than y0",
+        );
+        validator.material(
+            validation::aliased(location, "material_index", "material"),
+            &self.material_index,
+        );
+    }
+
+    /// `hit` without counting the test, for sampling.
+    fn intersect(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
         let t = (self.k - r.origin.z) / r.direction.z;
-        if t < t_min as f32 || t > t_max as f32 {
+        // nan when the ray runs inside of the plane.
+        if t.is_nan() || t < t_min || t > t_max {
             return None;
         }
 
@@ -47,11 +74,18 @@ impl Hittable for XyRect {
             t,
             r,
             Vec3A::new(0.0, 0.0, 1.0),
-            self.material_index,
+            self.material_index.index(),
             (x - self.x0) / (self.x1 - self.x0),
             (y - self.y0) / (self.y1 - self.y0),
         ))
     }
+}
+
+impl Hittable for XyRect {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, _rng: &mut TracerRng) -> Option<HitRecord> {
+        stats::count_intersection(Primitive::Rect);
+        self.intersect(r, t_min, t_max)
+    }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
         Some(Aabb::new(
@@ -59,4 +93,24 @@ impl Hittable for XyRect {
             Vec3A::new(self.x1, self.y1, self.k + 0.0001),
         ))
     }
+
+    fn pdf_value(&self, origin: Vec3A, direction: Vec3A, _rng: &mut TracerRng) -> f32 {
+        let ray = Ray::new(origin, direction, 0.0);
+        let Some(rec) = self.intersect(&ray, 0.001, f32::INFINITY) else {
+            return 0.0;
+        };
+        let area = (self.x1 - self.x0) * (self.y1 - self.y0);
+        let distance_squared = rec.root * rec.root * direction.length_squared();
+        let cosine = direction.z.abs() / direction.length();
+        distance_squared / (cosine * area)
+    }
+
+    fn random(&self, origin: Vec3A, u: Vec2) -> Vec3A {
+        let point = Vec3A::new(
+            self.x0 + u.x * (self.x1 - self.x0),
+            self.y0 + u.y * (self.y1 - self.y0),
+            self.k,
+        );
+        point - origin
+    }
 }
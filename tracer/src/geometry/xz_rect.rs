@@ -1,15 +1,21 @@
+use crate::error::TracerResult;
 use crate::geometry::aabb::Aabb;
 use crate::geometry::Hittable;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
+use crate::random::TracerRng;
+use crate::reference::{Reference, SceneNames};
+use crate::stats::{self, Primitive};
+use crate::validation::{self, Validator};
 
-use glam::Vec3A;
+use glam::{Vec2, Vec3A};
 
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct XzRect {
-    material_index: usize,
+    #[serde(alias = "material")]
+    pub material_index: Reference,
     x0: f32,
     x1: f32,
     z0: f32,
@@ -20,7 +26,7 @@ pub struct XzRect {
 impl XzRect {
     pub fn new(x0: f32, x1: f32, z0: f32, z1: f32, k: f32, material_index: usize) -> Self {
         Self {
-            material_index,
+            material_index: Reference::Index(material_index),
             x0,
             x1,
             z0,
@@ -28,12 +34,33 @@ impl XzRect {
             k,
         }
     }
-}
 
-impl Hittable for XzRect {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.material_index.resolve(&names.materials)
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.check(
+            self.x0 < self.x1,
+            format!("{location}.x1"),
+            "x1 must be greater than x0",
+        );
+        validator.check(
+            self.z0 < self.z1,
+            format!("{location}.z1"),
+            "z1 must be greater than z0",
+        );
+        validator.material(
+            validation::aliased(location, "material_index", "material"),
+            &self.material_index,
+        );
+    }
+
+    /// `hit` without counting the test, for sampling.
+    fn intersect(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
         let t = (self.k - r.origin.y) / r.direction.y;
-        if t < t_min as f32 || t > t_max as f32 {
+        // nan when the ray runs inside of the plane.
+        if t.is_nan() || t < t_min || t > t_max {
             return None;
         }
 
@@ -47,16 +74,43 @@ impl Hittable for XzRect {
             t,
             r,
             Vec3A::new(0.0, 1.0, 0.0),
-            self.material_index,
+            self.material_index.index(),
             (x - self.x0) / (self.x1 - self.x0),
             (z - self.z0) / (self.z1 - self.z0),
         ))
     }
+}
+
+impl Hittable for XzRect {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32, _rng: &mut TracerRng) -> Option<HitRecord> {
+        stats::count_intersection(Primitive::Rect);
+        self.intersect(r, t_min, t_max)
+    }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
         Some(Aabb::new(
-            Vec3A::new(self.x0, self.z0, self.k - 0.0001),
-            Vec3A::new(self.x1, self.z1, self.k + 0.0001),
+            Vec3A::new(self.x0, self.k - 0.0001, self.z0),
+            Vec3A::new(self.x1, self.k + 0.0001, self.z1),
         ))
     }
+
+    fn pdf_value(&self, origin: Vec3A, direction: Vec3A, _rng: &mut TracerRng) -> f32 {
+        let ray = Ray::new(origin, direction, 0.0);
+        let Some(rec) = self.intersect(&ray, 0.001, f32::INFINITY) else {
+            return 0.0;
+        };
+        let area = (self.x1 - self.x0) * (self.z1 - self.z0);
+        let distance_squared = rec.root * rec.root * direction.length_squared();
+        let cosine = direction.y.abs() / direction.length();
+        distance_squared / (cosine * area)
+    }
+
+    fn random(&self, origin: Vec3A, u: Vec2) -> Vec3A {
+        let point = Vec3A::new(
+            self.x0 + u.x * (self.x1 - self.x0),
+            self.k,
+            self.z0 + u.y * (self.z1 - self.z0),
+        );
+        point - origin
+    }
 }
@@ -8,6 +8,8 @@ pub struct HitRecord {
     pub normal: Vec3A,
     pub front_face: bool,
     pub material_index: usize,
+    /// Index of the world object that was hit, only set when the object id aov is rendered.
+    pub object_index: usize,
     pub u: f32,
     pub v: f32,
 }
@@ -36,6 +38,7 @@ impl HitRecord {
             u,
             v,
             material_index,
+            object_index: 0,
         }
     }
     pub fn set_face_normal(&mut self, ray: &Ray, outward_normal: Vec3A) {
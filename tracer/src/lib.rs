@@ -0,0 +1,66 @@
+//! Ray tracer following Ray Tracing In One Weekend + The Next Week.
+//!
+//! Scenes are described by a [`RenderBuilder`], usually deserialized from json, which builds a
+//! [`Renderer`]. [`Renderer::render_image`] returns the image as a linear [`Framebuffer`],
+//! [`Renderer::render`] additionally saves it to `settings.path`.
+//!
+//! ```no_run
+//! use tracer::{RenderBuilder, TracerResult};
+//!
+//! fn main() -> TracerResult<()> {
+//!     let mut render = RenderBuilder::from_path("./assets/world.json")?;
+//!     render.settings.samples = 16;
+//!     render.build()?.render()?;
+//!     Ok(())
+//! }
+//! ```
+
+pub mod adaptive;
+pub mod aov;
+pub mod background;
+pub mod camera;
+pub mod denoise;
+pub mod distribution;
+pub mod error;
+pub mod film;
+pub mod filter;
+pub mod framebuffer;
+pub mod geometry;
+pub mod intersection;
+pub mod light;
+pub mod material;
+pub mod random;
+pub mod reference;
+pub mod renderer;
+pub mod sampler;
+pub mod stats;
+pub mod texture;
+pub mod tile;
+pub mod tone_map;
+pub mod validation;
+pub mod vec3;
+
+pub use crate::adaptive::AdaptiveSettings;
+pub use crate::aov::Aov;
+pub use crate::background::{Background, BackgroundBuilder};
+pub use crate::camera::{CamerBuilder, Camera};
+pub use crate::denoise::DenoiseSettings;
+pub use crate::error::{TracerError, TracerResult};
+pub use crate::film::Film;
+pub use crate::filter::{Filter, FilterType};
+pub use crate::framebuffer::{Framebuffer, OutputFormat};
+pub use crate::geometry::bvh_tree::{BvhSplit, BvhStats};
+pub use crate::geometry::{Geometry, GeometryFile, Hittable};
+pub use crate::light::Lights;
+pub use crate::material::{Material, MaterialType};
+pub use crate::random::TracerRng;
+pub use crate::reference::{Named, Reference};
+pub use crate::renderer::{
+    ProgressCallback, RenderBuilder, RenderOutput, RenderSettings, Renderer, TileCallback,
+};
+pub use crate::sampler::{Sampler, SamplerType};
+pub use crate::stats::{Progress, RenderStats};
+pub use crate::texture::{Texture, TextureFile, TexturesType};
+pub use crate::tile::{Tile, TileOrder};
+pub use crate::tone_map::{DisplayTransform, ToneMapper};
+pub use crate::validation::ValidationError;
@@ -0,0 +1,160 @@
+use crate::background::Background;
+use crate::geometry::hittable::HittableList;
+use crate::geometry::{Geometry, Hittable};
+use crate::material::MaterialType;
+use crate::random::TracerRng;
+use glam::{Vec2, Vec3A};
+
+/// Emitters the renderer samples directly at every diffuse bounce.
+#[derive(Default)]
+pub struct Lights {
+    list: HittableList,
+    /// Background sampled along with `list`, an environment map or the sun of a sky.
+    environment: Option<Background>,
+    /// Per material, whether all primitives emitting with it are in `list`. Bounces that sampled
+    /// the lights skip the emission of these when they hit them, it's already been added.
+    sampled: Vec<bool>,
+}
+
+impl Lights {
+    /// Collects the spheres and rects of `geometry` with an emitting material. Materials also
+    /// emitting from primitives that can't be sampled, like triangles or transformed objects,
+    /// are left out completely so their light is neither missed nor counted twice. Environment
+    /// maps and skies of `background` are sampled too.
+    pub fn new(geometry: &Geometry, materials: &[MaterialType], background: &Background) -> Self {
+        let mut lights = Vec::new();
+        let mut unsampled = Vec::new();
+        geometry.collect_lights(materials, &mut lights, &mut unsampled);
+        lights.retain(|(_, material_index)| !unsampled.contains(material_index));
+        let mut sampled = vec![false; materials.len()];
+        for (_, material_index) in &lights {
+            sampled[*material_index] = true;
+        }
+        Self {
+            list: HittableList::new(lights.into_iter().map(|(light, _)| light).collect()),
+            sampled,
+            environment: background.is_sampled().then(|| background.clone()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.objects.is_empty() && self.environment.is_none()
+    }
+
+    /// Whether rays leaving the scene see a light.
+    pub fn has_environment(&self) -> bool {
+        self.environment.is_some()
+    }
+
+    /// Chance of `random` picking a direction towards the environment instead of `list`.
+    fn environment_weight(&self) -> f32 {
+        match (&self.environment, self.list.objects.is_empty()) {
+            (None, _) => 0.0,
+            (Some(_), true) => 1.0,
+            (Some(_), false) => 0.5,
+        }
+    }
+
+    /// Whether hits on `material_index` are on one of the lights.
+    pub fn is_sampled(&self, material_index: usize) -> bool {
+        self.sampled.get(material_index).copied().unwrap_or(false)
+    }
+
+    /// Direction from `origin` towards a point on one of the lights, picked uniformly, or towards
+    /// the environment.
+    pub fn random(&self, origin: Vec3A, u: Vec2) -> Vec3A {
+        let weight = self.environment_weight();
+        match &self.environment {
+            // `u.x` picks the environment or the list and is reused within them.
+            Some(environment) if u.x < weight => environment.random(Vec2::new(u.x / weight, u.y)),
+            _ => {
+                let x = ((u.x - weight) / (1.0 - weight)).min(1.0 - f32::EPSILON);
+                self.list.random(origin, Vec2::new(x, u.y))
+            }
+        }
+    }
+
+    /// Density over solid angle of `random` picking `direction`.
+    pub fn pdf_value(&self, origin: Vec3A, direction: Vec3A, rng: &mut TracerRng) -> f32 {
+        let weight = self.environment_weight();
+        let mut pdf = 0.0;
+        if let Some(environment) = &self.environment {
+            pdf += weight * environment.pdf_value(direction);
+        }
+        if weight < 1.0 {
+            pdf += (1.0 - weight) * self.list.pdf_value(origin, direction, rng);
+        }
+        pdf
+    }
+}
+
+/// Weight of a sample taken with density `f` when the same path could also have been sampled with
+/// density `g`, Veach's power heuristic with an exponent of two.
+pub fn power_heuristic(f: f32, g: f32) -> f32 {
+    if g <= 0.0 {
+        return 1.0;
+    }
+    // the ratio keeps huge densities, like grazing rects, from overflowing.
+    let ratio = g / f;
+    1.0 / (1.0 + ratio * ratio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::sphere::Sphere;
+    use crate::geometry::xy_rect::XyRect;
+    use crate::random;
+    use crate::stats;
+    use rand::Rng;
+    use std::f32::consts::PI;
+
+    /// A sphere behind and a rect in front of the origin, both emitting.
+    fn lights() -> Lights {
+        let material: MaterialType =
+            serde_json::from_str(r#"{ "DiffuseLight": { "texture_index": 0 } }"#).unwrap();
+        let geometry = Geometry::HittableList(HittableList::new(vec![
+            Geometry::Sphere(Sphere::new(Vec3A::new(0.0, 0.0, -4.0), 1.0, 0)),
+            Geometry::XyRect(XyRect::new(-1.0, 1.0, -1.0, 1.0, 4.0, 0)),
+        ]));
+        Lights::new(&geometry, &[material], &Background::Color(Vec3A::ZERO))
+    }
+
+    #[test]
+    fn pdf_integrates_to_one_over_all_directions() {
+        let lights = lights();
+        assert_eq!(lights.len(), 2);
+        let mut rng = random::seeded_rng(3);
+        let count = 200_000;
+        let mut sum = 0.0;
+        for _ in 0..count {
+            let z = 1.0 - 2.0 * rng.gen::<f32>();
+            let phi = 2.0 * PI * rng.gen::<f32>();
+            let r = (1.0 - z * z).sqrt();
+            let direction = Vec3A::new(r * phi.cos(), r * phi.sin(), z);
+            sum += lights.pdf_value(Vec3A::ZERO, direction, &mut rng);
+        }
+        // uniform directions have a density of 1 / 4pi.
+        let integral = sum * 4.0 * PI / count as f32;
+        assert!((integral - 1.0).abs() < 0.02, "{integral}");
+    }
+
+    #[test]
+    fn sampling_does_not_count_as_intersection_tests() {
+        let lights = lights();
+        let mut rng = random::seeded_rng(4);
+        stats::take();
+        for _ in 0..100 {
+            let direction = lights.random(Vec3A::ZERO, Vec2::new(rng.gen(), rng.gen()));
+            assert!(lights.pdf_value(Vec3A::ZERO, direction, &mut rng) > 0.0);
+        }
+        assert!(stats::take()
+            .intersection_tests
+            .iter()
+            .all(|&tests| tests == 0));
+    }
+}
@@ -1,188 +1,194 @@
-mod camera;
-mod error;
-mod geometry;
-pub mod intersection;
-mod material;
-mod renderer;
-mod texture;
-mod vec3;
+mod cli;
 
-use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use crate::renderer::RenderBuilder;
+use anyhow::Context;
+use clap::Parser;
+use tracer::aov::{self, Aov};
+use tracer::{BackgroundBuilder, DenoiseSettings, Framebuffer, OutputFormat, RenderBuilder};
 
-use error::TracerResult;
+use crate::cli::{Cli, Command};
 
-//
-// fn simple_light() -> HittableList {
-//     let noise = Rc::new(Noise::new(4.0));
-//     HittableList::new(vec![
-//         Rc::new(Sphere::new(
-//             Vec3A::new(0.0, -1000.0, 0.0),
-//             1000.0,
-//             Rc::new(Lambertian::new(noise.clone())),
-//         )),
-//         Rc::new(Sphere::new(
-//             Vec3A::new(0.0, 2.0, 0.0),
-//             2.0,
-//             Rc::new(Lambertian::new(noise.clone())),
-//         )),
-//         Rc::new(XyRect::new(
-//             3.0,
-//             5.0,
-//             1.0,
-//             3.0,
-//             -2.0,
-//             Rc::new(DiffuseLight::new(Rc::new(Solid::new(Vec3A::new(
-//                 4.0, 4.0, 4.0,
-//             ))))),
-//         )),
-//     ])
-// }
-//
-// fn empty_box() -> HittableList {
-//     let white = Rc::new(Lambertian::new(Rc::new(Solid::new(Vec3A::new(
-//         0.73, 0.73, 0.73,
-//     )))));
-//
-//     HittableList::new(vec![
-//         Rc::new(YzRect::new(
-//             0.0,
-//             555.0,
-//             0.0,
-//             555.0,
-//             555.0,
-//             Rc::new(Lambertian::new(Rc::new(Solid::new(Vec3A::new(
-//                 0.12, 0.45, 0.15,
-//             ))))),
-//         )),
-//         Rc::new(YzRect::new(
-//             0.0,
-//             555.0,
-//             0.0,
-//             555.0,
-//             0.0,
-//             Rc::new(Lambertian::new(Rc::new(Solid::new(Vec3A::new(
-//                 0.65, 0.05, 0.05,
-//             ))))),
-//         )),
-//         Rc::new(XzRect::new(
-//             113.,
-//             443.,
-//             127.,
-//             432.,
-//             554.,
-//             Rc::new(DiffuseLight::new(Rc::new(Solid::new(Vec3A::new(
-//                 15.0, 15.0, 15.0,
-//             ))))),
-//         )),
-//         Rc::new(XzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, white.clone())),
-//         Rc::new(XzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone())),
-//         Rc::new(XyRect::new(0.0, 555.0, 0.0, 555.0, 555., white.clone())),
-//     ])
-// }
-//
-// fn two_boxes() -> HittableList {
-//     let room = empty_box();
-//
-//     let white = Rc::new(Lambertian::new(Rc::new(Solid::new(Vec3A::new(
-//         0.73, 0.73, 0.73,
-//     )))));
-//
-//     HittableList::new(vec![
-//         Rc::new(CornellBox::new(
-//             Vec3A::new(130., 0.0, 65.0),
-//             Vec3A::new(295.0, 165.0, 230.0),
-//             white.clone(),
-//         )),
-//         Rc::new(CornellBox::new(
-//             Vec3A::new(265., 0.0, 295.0),
-//             Vec3A::new(430.0, 330.0, 460.0),
-//             white.clone(),
-//         )),
-//         Rc::new(room),
-//     ])
-// }
-//
-// fn rotate_box() -> HittableList {
-//     let room = empty_box();
-//     let white = Rc::new(Lambertian::new(Rc::new(Solid::new(Vec3A::new(
-//         0.73, 0.73, 0.73,
-//     )))));
-//     HittableList::new(vec![
-//         Rc::new(Translate::new(
-//             Rc::new(RotateY::new(
-//                 Rc::new(CornellBox::new(
-//                     Vec3A::new(0.0, 0.0, 0.0),
-//                     Vec3A::new(165.0, 165.0, 165.0),
-//                     white.clone(),
-//                 )),
-//                 -18.0,
-//             )),
-//             Vec3A::new(130.0, 0.0, 65.0),
-//         )),
-//         Rc::new(Translate::new(
-//             Rc::new(RotateY::new(
-//                 Rc::new(CornellBox::new(
-//                     Vec3A::new(0.0, 0.0, 0.0),
-//                     Vec3A::new(165.0, 330.0, 165.0),
-//                     white.clone(),
-//                 )),
-//                 15.0,
-//             )),
-//             Vec3A::new(265.0, 0.0, 295.0),
-//         )),
-//         Rc::new(room),
-//     ])
-// }
-//
-// fn rotate_cloudy_box() -> HittableList {
-//     let room = empty_box();
-//     let white = Rc::new(Lambertian::new(Rc::new(Solid::new(Vec3A::new(
-//         0.73, 0.73, 0.73,
-//     )))));
-//     HittableList::new(vec![
-//         Rc::new(ConstantMedium::new_from_density(
-//             Rc::new(Translate::new(
-//                 Rc::new(RotateY::new(
-//                     Rc::new(CornellBox::new(
-//                         Vec3A::new(0.0, 0.0, 0.0),
-//                         Vec3A::new(165.0, 165.0, 165.0),
-//                         white.clone(),
-//                     )),
-//                     -18.0,
-//                 )),
-//                 Vec3A::new(130.0, 0.0, 65.0),
-//             )),
-//             0.01,
-//             Vec3A::ZERO,
-//         )),
-//         Rc::new(ConstantMedium::new_from_density(
-//             Rc::new(Translate::new(
-//                 Rc::new(RotateY::new(
-//                     Rc::new(CornellBox::new(
-//                         Vec3A::new(0.0, 0.0, 0.0),
-//                         Vec3A::new(165.0, 330.0, 165.0),
-//                         white.clone(),
-//                     )),
-//                     15.0,
-//                 )),
-//                 Vec3A::new(265.0, 0.0, 295.0),
-//             )),
-//             0.01,
-//             Vec3A::ZERO,
-//         )),
-//         Rc::new(room),
-//     ])
-// }
+// how often the progress line is redrawn.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
 
-pub fn render_from_json() -> TracerResult<()> {
-    let file = fs::File::open("./assets/test.json")?;
-    let render: RenderBuilder = serde_json::from_reader(file)?;
-    render.build()?.render()?;
-    Ok(())
+fn load(scene: &Path) -> anyhow::Result<RenderBuilder> {
+    RenderBuilder::from_path(scene).with_context(|| format!("failed to load {}", scene.display()))
+}
+
+/// Guide image for `denoise`, from `path` or else from the layers of an exr input.
+fn load_guide(
+    input: &Path,
+    path: Option<PathBuf>,
+    aov: Aov,
+    image: &Framebuffer,
+) -> anyhow::Result<Option<Framebuffer>> {
+    let guide = match path {
+        Some(path) => Some(Framebuffer::open(path)?),
+        None if matches!(OutputFormat::from_path(input), Ok(OutputFormat::Exr)) => {
+            aov::read_exr_layer(input, aov)?
+        }
+        None => None,
+    };
+    if let Some(guide) = &guide {
+        if (guide.width, guide.height) != (image.width, image.height) {
+            anyhow::bail!(
+                "the {} guide is {}x{} but the image is {}x{}",
+                aov.name(),
+                guide.width,
+                guide.height,
+                image.width,
+                image.height
+            );
+        }
+    }
+    Ok(guide)
 }
 
-fn main() -> TracerResult<()> {
-    render_from_json()
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Render {
+            scene,
+            overrides,
+            quiet,
+        } => {
+            let mut render = load(&scene)?;
+            overrides.apply(&mut render.settings);
+            let mut renderer = render.build()?;
+            if quiet {
+                renderer.render()?;
+                return Ok(());
+            }
+            if let Some(stats) = renderer.bvh_stats() {
+                eprintln!("bvh: {stats}");
+            }
+            let last_report = Mutex::new(Instant::now());
+            renderer.on_progress(move |progress| {
+                let mut last_report = last_report.lock().unwrap();
+                if last_report.elapsed() >= PROGRESS_INTERVAL || progress.fraction() >= 1.0 {
+                    *last_report = Instant::now();
+                    eprint!("\r{progress}  ");
+                }
+            });
+            let output = renderer.render()?;
+            eprintln!();
+            eprintln!("{}", output.stats);
+        }
+        Command::Validate { scene } => {
+            load(&scene)?
+                .build()
+                .with_context(|| format!("{} is not a valid scene", scene.display()))?;
+            println!("{}: ok", scene.display());
+        }
+        Command::Info { scene, overrides } => {
+            let mut render = load(&scene)?;
+            overrides.apply(&mut render.settings);
+            let settings = &render.settings;
+            println!("scene:      {}", scene.display());
+            println!("output:     {}", settings.path);
+            println!(
+                "resolution: {}x{}",
+                settings.image_width,
+                settings.image_height()
+            );
+            match &settings.adaptive {
+                Some(adaptive) => println!(
+                    "samples:    {} to {}, threshold {}",
+                    settings.samples, adaptive.max_samples, adaptive.threshold
+                ),
+                None => println!("samples:    {}", settings.samples),
+            }
+            println!("max depth:  {}", settings.max_depth);
+            if let Some(roulette_depth) = settings.roulette_depth {
+                println!("roulette:   after {roulette_depth} bounces");
+            }
+            if let Some(clamp) = settings.clamp {
+                println!("clamp:      {clamp}");
+            }
+            println!(
+                "display:    {:?}, exposure {}",
+                settings.tone_mapper, settings.exposure
+            );
+            println!("seed:       {}", settings.seed);
+            println!("sampler:    {:?}", settings.sampler);
+            let filter = settings.filter();
+            println!(
+                "filter:     {:?}, radius {}",
+                filter.filter_type, filter.radius
+            );
+            match &render.background {
+                Some(BackgroundBuilder::Environment(environment)) => println!(
+                    "background: {}, rotation {}, intensity {}",
+                    environment.path, environment.rotation, environment.intensity
+                ),
+                Some(BackgroundBuilder::Sky(sky)) => println!(
+                    "background: sky, sun {}, turbidity {}, intensity {}",
+                    sky.sun_direction, sky.turbidity, sky.intensity
+                ),
+                Some(BackgroundBuilder::Gradient(gradient)) => println!(
+                    "background: gradient {} to {}",
+                    gradient.bottom, gradient.top
+                ),
+                None => println!("background: {}", settings.background_color),
+            }
+            let threads = match settings.threads {
+                Some(threads) => threads.to_string(),
+                None => "all".to_string(),
+            };
+            println!(
+                "tiles:      {}px, {:?}, {threads} threads",
+                settings.tile_size, settings.tile_order
+            );
+            if let Some(denoise) = &settings.denoise {
+                println!("denoise:    {} iterations", denoise.iterations);
+            }
+            if !settings.aovs.is_empty() {
+                let names = settings.aovs.iter().map(|aov| aov.name());
+                println!("aovs:       {}", names.collect::<Vec<_>>().join(", "));
+            }
+            println!("objects:    {}", render.world.objects.len());
+            println!("materials:  {}", render.materials.len());
+            println!("textures:   {}", render.textures.len());
+            let render_lights = render.settings.light_sampling;
+            if render.settings.bvh || render_lights {
+                let renderer = render.build()?;
+                if render_lights {
+                    let lights = renderer.lights();
+                    if lights.has_environment() {
+                        println!("lights:     {} sampled and the background", lights.len());
+                    } else {
+                        println!("lights:     {} sampled", lights.len());
+                    }
+                }
+                if let Some(stats) = renderer.bvh_stats() {
+                    println!("bvh:        {stats}");
+                }
+            }
+        }
+        Command::Denoise {
+            input,
+            output,
+            albedo,
+            normal,
+            iterations,
+        } => {
+            let image = Framebuffer::open(&input)?;
+            let albedo = load_guide(&input, albedo, Aov::Albedo, &image)?;
+            let normal = load_guide(&input, normal, Aov::Normal, &image)?;
+            if albedo.is_none() && normal.is_none() {
+                eprintln!("no guides, edges are only kept by color");
+            }
+            let mut settings = DenoiseSettings::default();
+            if let Some(iterations) = iterations {
+                settings.iterations = iterations;
+            }
+            settings
+                .denoise(&image, albedo.as_ref(), normal.as_ref())
+                .save(&output)?;
+        }
+    }
+    Ok(())
 }
@@ -1,25 +1,30 @@
+use crate::error::TracerResult;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
 use crate::material::{Material, ScatterRecord};
+use crate::reference::{Reference, SceneNames};
+use crate::sampler::Sampler;
 
 use crate::vec3;
 
-use serde::{Deserialize, Serialize};
 use crate::texture::TexturesType;
+use crate::validation::{self, Validator};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Dieletric {
     pub ref_idx: f32,
-    pub texture_index: usize,
-    pub emitted: Option<usize>,
+    #[serde(alias = "texture")]
+    pub texture_index: Reference,
+    pub emitted: Option<Reference>,
 }
 
 impl Dieletric {
     pub fn new(ref_idx: f32, texture_index: usize, emitted: Option<usize>) -> Self {
         Dieletric {
             ref_idx,
-            texture_index,
-            emitted
+            texture_index: Reference::Index(texture_index),
+            emitted: emitted.map(Reference::Index),
         }
     }
     fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
@@ -28,10 +33,39 @@ impl Dieletric {
         let r0 = r0 * r0;
         r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
     }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.texture_index.resolve(&names.textures)?;
+        if let Some(emitted) = &mut self.emitted {
+            emitted.resolve(&names.textures)?;
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.check(
+            self.ref_idx > 0.0,
+            format!("{location}.ref_idx"),
+            "ref_idx must be positive",
+        );
+        validator.texture(
+            validation::aliased(location, "texture_index", "texture"),
+            &self.texture_index,
+        );
+        if let Some(emitted) = &self.emitted {
+            validator.texture(format!("{location}.emitted"), emitted);
+        }
+    }
 }
 
 impl Material for Dieletric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, textures: &Vec<TexturesType>) -> Option<ScatterRecord> {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        _textures: &[TexturesType],
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
         let reflected = vec3::reflect(r_in.direction.normalize(), rec.normal);
         // let attenuation = Vec3A::new(1.0, 1.0, 1.0);
 
@@ -47,7 +81,7 @@ impl Material for Dieletric {
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let direction = if cannot_refract
-            || Self::reflectance(cos_theta, refraction_ratio) > rand::random::<f32>()
+            || Self::reflectance(cos_theta, refraction_ratio) > sampler.get_1d()
         {
             reflected
         } else {
@@ -55,15 +89,15 @@ impl Material for Dieletric {
         };
 
         Some(ScatterRecord {
-            texture_index: self.texture_index,
+            texture_index: self.texture_index.index(),
             scattered: Ray::new(rec.position, direction, r_in.time),
         })
     }
     fn emitted(&self) -> Option<usize> {
-        if let Some(emitted) = self.emitted {
-            Some(emitted)
-        } else {
-            None
-        }
+        self.emitted.as_ref().map(Reference::index)
+    }
+
+    fn albedo(&self) -> Option<usize> {
+        Some(self.texture_index.index())
     }
 }
@@ -1,27 +1,51 @@
+use crate::error::TracerResult;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
 use crate::material::{Material, ScatterRecord};
+use crate::reference::{Reference, SceneNames};
+use crate::sampler::Sampler;
 
-use serde::{Deserialize, Serialize};
 use crate::texture::TexturesType;
+use crate::validation::{self, Validator};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DiffuseLight {
-    texture_index: usize,
+    #[serde(alias = "texture")]
+    texture_index: Reference,
 }
 
 impl DiffuseLight {
     pub fn new(texture_index: usize) -> Self {
-        Self { texture_index }
+        Self {
+            texture_index: Reference::Index(texture_index),
+        }
+    }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.texture_index.resolve(&names.textures)
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.texture(
+            validation::aliased(location, "texture_index", "texture"),
+            &self.texture_index,
+        );
     }
 }
 
 impl Material for DiffuseLight {
-    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, textures: &Vec<TexturesType>) -> Option<ScatterRecord> {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _textures: &[TexturesType],
+        _sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
         None
     }
 
     fn emitted(&self) -> Option<usize> {
-        Some(self.texture_index)
+        Some(self.texture_index.index())
     }
 }
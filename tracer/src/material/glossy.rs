@@ -0,0 +1,154 @@
+use crate::error::TracerResult;
+use crate::intersection::hit_record::HitRecord;
+use crate::intersection::ray::Ray;
+use crate::material::microfacet::Ggx;
+use crate::material::{BsdfSample, Material, ScatterRecord};
+use crate::reference::{Reference, SceneNames};
+use crate::sampler::Sampler;
+use crate::texture::{Texture, TexturesType};
+use crate::validation::{self, Validator};
+use glam::Vec3A;
+
+use serde::{Deserialize, Serialize};
+
+/// Conductor reflecting with a GGX microfacet distribution, the texture is its reflectance at
+/// normal incidence.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Glossy {
+    #[serde(alias = "texture")]
+    pub texture_index: Reference,
+    /// Zero is a perfect mirror, one is very rough.
+    pub roughness: f32,
+    pub emitted: Option<Reference>,
+}
+
+impl Glossy {
+    pub fn new(texture_index: usize, roughness: f32, emitted: Option<usize>) -> Self {
+        Self {
+            texture_index: Reference::Index(texture_index),
+            roughness,
+            emitted: emitted.map(Reference::Index),
+        }
+    }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.texture_index.resolve(&names.textures)?;
+        if let Some(emitted) = &mut self.emitted {
+            emitted.resolve(&names.textures)?;
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.texture(
+            validation::aliased(location, "texture_index", "texture"),
+            &self.texture_index,
+        );
+        validator.check(
+            (0.0..=1.0).contains(&self.roughness),
+            format!("{location}.roughness"),
+            "roughness must be between 0 and 1",
+        );
+        if let Some(emitted) = &self.emitted {
+            validator.texture(format!("{location}.emitted"), emitted);
+        }
+    }
+
+    fn ggx(&self, rec: &HitRecord, textures: &[TexturesType]) -> Ggx {
+        let f0 = textures[self.texture_index.index()].value(rec.u, rec.v, rec.position);
+        Ggx::from_roughness(self.roughness, f0)
+    }
+}
+
+impl Material for Glossy {
+    /// The direction of `sample`, attenuated by the texture alone.
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        textures: &[TexturesType],
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let sample = self.sample(r_in, rec, textures, sampler)?;
+        Some(ScatterRecord::new(
+            sample.scattered,
+            self.texture_index.index(),
+        ))
+    }
+
+    fn emitted(&self) -> Option<usize> {
+        self.emitted.as_ref().map(Reference::index)
+    }
+
+    fn albedo(&self) -> Option<usize> {
+        Some(self.texture_index.index())
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        direction: Vec3A,
+        textures: &[TexturesType],
+    ) -> Vec3A {
+        let wo = -r_in.direction.normalize();
+        self.ggx(rec, textures)
+            .eval(rec.normal, wo, direction.normalize())
+    }
+
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        direction: Vec3A,
+        textures: &[TexturesType],
+    ) -> Option<f32> {
+        let ggx = self.ggx(rec, textures);
+        let wo = -r_in.direction.normalize();
+        (!ggx.is_specular()).then(|| ggx.pdf(rec.normal, wo, direction.normalize()))
+    }
+
+    fn sample(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        textures: &[TexturesType],
+        sampler: &mut dyn Sampler,
+    ) -> Option<BsdfSample> {
+        let ggx = self.ggx(rec, textures);
+        let wo = -r_in.direction.normalize();
+        let (direction, weight) = ggx.sample(rec.normal, wo, sampler.get_2d())?;
+        Some(BsdfSample {
+            scattered: Ray::new(rec.position, direction, r_in.time),
+            weight,
+            pdf: (!ggx.is_specular()).then(|| ggx.pdf(rec.normal, wo, direction)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::SamplerType;
+    use crate::texture::solid::Solid;
+
+    #[test]
+    fn zero_roughness_is_a_mirror() {
+        let textures = [TexturesType::Solid(Solid::new(Vec3A::ONE))];
+        let r_in = Ray::new(Vec3A::new(-0.6, 0.8, 0.0), Vec3A::new(0.6, -0.8, 0.0), 0.0);
+        let rec = HitRecord::new(1.0, &r_in, Vec3A::Y, 0, 0.0, 0.0);
+        let glossy = Glossy::new(0, 0.0, None);
+        let mut sampler = SamplerType::Independent.build(1, 1);
+        sampler.start_pixel_sample(0, 0, 0);
+        let sample = glossy.sample(&r_in, &rec, &textures, &mut sampler).unwrap();
+        assert!(sample
+            .scattered
+            .direction
+            .abs_diff_eq(Vec3A::new(0.6, 0.8, 0.0), 1e-6));
+        assert!(sample.pdf.is_none());
+        assert!(glossy.pdf(&r_in, &rec, Vec3A::Y, &textures).is_none());
+
+        let rough = Glossy::new(0, 0.5, None);
+        assert!(rough.pdf(&r_in, &rec, Vec3A::Y, &textures).unwrap() > 0.0);
+    }
+}
@@ -1,36 +1,96 @@
+use crate::error::TracerResult;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
 use crate::material::{Material, ScatterRecord};
+use crate::reference::{Reference, SceneNames};
+use crate::sampler::Sampler;
 
-use crate::vec3::random_in_unit_sphere;
+use crate::vec3::sample_unit_sphere;
 
+use crate::texture::{Texture, TexturesType};
+use crate::validation::{self, Validator};
+use glam::Vec3A;
 use serde::{Deserialize, Serialize};
-use crate::texture::TexturesType;
+use std::f32::consts::PI;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Isotropic {
-    pub texture_index: usize,
-    pub emitted: Option<usize>,
+    #[serde(alias = "texture")]
+    pub texture_index: Reference,
+    pub emitted: Option<Reference>,
 }
 
 impl Isotropic {
     pub fn new(texture_index: usize, emitted: Option<usize>) -> Self {
-        Self { texture_index, emitted }
+        Self {
+            texture_index: Reference::Index(texture_index),
+            emitted: emitted.map(Reference::Index),
+        }
+    }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.texture_index.resolve(&names.textures)?;
+        if let Some(emitted) = &mut self.emitted {
+            emitted.resolve(&names.textures)?;
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.texture(
+            validation::aliased(location, "texture_index", "texture"),
+            &self.texture_index,
+        );
+        if let Some(emitted) = &self.emitted {
+            validator.texture(format!("{location}.emitted"), emitted);
+        }
     }
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, textures: &Vec<TexturesType>) -> Option<ScatterRecord> {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        _textures: &[TexturesType],
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
         Some(ScatterRecord::new(
-            Ray::new(rec.position, random_in_unit_sphere(), r_in.time),
-            self.texture_index,
+            Ray::new(
+                rec.position,
+                sample_unit_sphere(sampler.get_2d()),
+                r_in.time,
+            ),
+            self.texture_index.index(),
         ))
     }
     fn emitted(&self) -> Option<usize> {
-        if let Some(emitted) = self.emitted {
-            Some(emitted)
-        } else {
-            None
-        }
+        self.emitted.as_ref().map(Reference::index)
+    }
+
+    fn albedo(&self) -> Option<usize> {
+        Some(self.texture_index.index())
+    }
+
+    fn eval(
+        &self,
+        _r_in: &Ray,
+        rec: &HitRecord,
+        _direction: Vec3A,
+        textures: &[TexturesType],
+    ) -> Vec3A {
+        let albedo = textures[self.texture_index.index()].value(rec.u, rec.v, rec.position);
+        albedo / (4.0 * PI)
+    }
+
+    // scatters into every direction alike.
+    fn pdf(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _direction: Vec3A,
+        _textures: &[TexturesType],
+    ) -> Option<f32> {
+        Some(1.0 / (4.0 * PI))
     }
 }
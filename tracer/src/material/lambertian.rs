@@ -1,28 +1,61 @@
+use crate::error::TracerResult;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
 use crate::material::{Material, ScatterRecord};
+use crate::reference::{Reference, SceneNames};
+use crate::sampler::Sampler;
 
 use crate::vec3;
 use glam::Vec3A;
 
+use crate::texture::{Texture, TexturesType};
+use crate::validation::{self, Validator};
 use serde::{Deserialize, Serialize};
-use crate::texture::TexturesType;
+use std::f32::consts::PI;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Lambertian {
-    pub texture_index: usize,
-    pub emitted: Option<usize>,
+    #[serde(alias = "texture")]
+    pub texture_index: Reference,
+    pub emitted: Option<Reference>,
 }
 
 impl Lambertian {
     pub fn new(texture_index: usize, emitted: Option<usize>) -> Self {
-        Lambertian { texture_index, emitted }
+        Lambertian {
+            texture_index: Reference::Index(texture_index),
+            emitted: emitted.map(Reference::Index),
+        }
+    }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.texture_index.resolve(&names.textures)?;
+        if let Some(emitted) = &mut self.emitted {
+            emitted.resolve(&names.textures)?;
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.texture(
+            validation::aliased(location, "texture_index", "texture"),
+            &self.texture_index,
+        );
+        if let Some(emitted) = &self.emitted {
+            validator.texture(format!("{location}.emitted"), emitted);
+        }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, textures: &Vec<TexturesType>) -> Option<ScatterRecord> {
-        let scatter_direction = rec.normal + vec3::random_in_unit_sphere().normalize();
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        _textures: &[TexturesType],
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let scatter_direction = rec.normal + vec3::sample_unit_sphere(sampler.get_2d());
 
         // Catch degenerate scatter direction
         let scatter_direction = if scatter_direction.abs_diff_eq(Vec3A::ZERO, 0.0001) {
@@ -32,17 +65,40 @@ impl Material for Lambertian {
         };
 
         Some(ScatterRecord {
-            texture_index: self.texture_index,
+            texture_index: self.texture_index.index(),
             scattered: Ray::new(rec.position, scatter_direction, r_in.time),
         })
     }
 
-
     fn emitted(&self) -> Option<usize> {
-        if let Some(emitted) = self.emitted {
-            Some(emitted)
-        } else {
-            None
-        }
+        self.emitted.as_ref().map(Reference::index)
+    }
+
+    fn albedo(&self) -> Option<usize> {
+        Some(self.texture_index.index())
+    }
+
+    fn eval(
+        &self,
+        _r_in: &Ray,
+        rec: &HitRecord,
+        direction: Vec3A,
+        textures: &[TexturesType],
+    ) -> Vec3A {
+        let albedo = textures[self.texture_index.index()].value(rec.u, rec.v, rec.position);
+        let cosine = rec.normal.dot(direction.normalize());
+        albedo * cosine.max(0.0) / PI
+    }
+
+    // the scattered rays follow the cosine around the normal.
+    fn pdf(
+        &self,
+        _r_in: &Ray,
+        rec: &HitRecord,
+        direction: Vec3A,
+        _textures: &[TexturesType],
+    ) -> Option<f32> {
+        let cosine = rec.normal.dot(direction.normalize());
+        Some(cosine.max(0.0) / PI)
     }
 }
@@ -1,37 +1,95 @@
+use crate::error::TracerResult;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
 use crate::material::{Material, ScatterRecord};
+use crate::reference::{Reference, SceneNames};
+use crate::sampler::Sampler;
 
 use crate::vec3;
+use glam::Vec3A;
 
+use crate::texture::{Texture, TexturesType};
+use crate::validation::{self, Validator};
 use serde::{Deserialize, Serialize};
-use crate::texture::TexturesType;
+use std::f32::consts::PI;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Metal {
-    pub texture_index: usize,
+    #[serde(alias = "texture")]
+    pub texture_index: Reference,
     pub fuzz: f32,
-    pub emitted: Option<usize>,
+    pub emitted: Option<Reference>,
 }
 
 impl Metal {
     pub fn new(texture_index: usize, fuzz: f32, emitted: Option<usize>) -> Self {
         Metal {
-            texture_index,
+            texture_index: Reference::Index(texture_index),
             fuzz,
-            emitted,
+            emitted: emitted.map(Reference::Index),
+        }
+    }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.texture_index.resolve(&names.textures)?;
+        if let Some(emitted) = &mut self.emitted {
+            emitted.resolve(&names.textures)?;
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.texture(
+            validation::aliased(location, "texture_index", "texture"),
+            &self.texture_index,
+        );
+        validator.check(
+            self.fuzz >= 0.0,
+            format!("{location}.fuzz"),
+            "fuzz must not be negative",
+        );
+        if let Some(emitted) = &self.emitted {
+            validator.texture(format!("{location}.emitted"), emitted);
         }
     }
 }
 
+impl Metal {
+    /// Density over solid angle of `scatter` picking the unit vector `direction`. The scattered
+    /// direction points at a uniform point of the ball of radius `fuzz` around the mirror
+    /// direction, so the density is the part of the ball along `direction`, weighted by the
+    /// squared distance, over its volume.
+    fn fuzz_pdf(&self, reflected: Vec3A, direction: Vec3A) -> f32 {
+        let cos = direction.dot(reflected);
+        let discriminant = cos * cos - 1.0 + self.fuzz * self.fuzz;
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let far = cos + discriminant.sqrt();
+        if far <= 0.0 {
+            return 0.0;
+        }
+        // the ball contains the hit point when fuzz is above one.
+        let near = (cos - discriminant.sqrt()).max(0.0);
+        (far.powi(3) - near.powi(3)) / (4.0 * PI * self.fuzz.powi(3))
+    }
+}
+
 impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, textures: &Vec<TexturesType>) -> Option<ScatterRecord> {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        _textures: &[TexturesType],
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
         let reflected_direction = vec3::reflect(r_in.direction.normalize(), rec.normal);
-        let fuzzed_direction = reflected_direction + vec3::random_in_unit_sphere() * self.fuzz;
+        let fuzzed_direction = reflected_direction
+            + vec3::sample_unit_ball(sampler.get_2d(), sampler.get_1d()) * self.fuzz;
 
         if fuzzed_direction.dot(rec.normal) > 0.0 {
             Some(ScatterRecord {
-                texture_index: self.texture_index,
+                texture_index: self.texture_index.index(),
                 scattered: Ray::new(rec.position, fuzzed_direction, r_in.time),
             })
         } else {
@@ -39,10 +97,122 @@ impl Material for Metal {
         }
     }
     fn emitted(&self) -> Option<usize> {
-        if let Some(emitted) = self.emitted {
-            Some(emitted)
-        } else {
-            None
+        self.emitted.as_ref().map(Reference::index)
+    }
+
+    fn albedo(&self) -> Option<usize> {
+        Some(self.texture_index.index())
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        direction: Vec3A,
+        textures: &[TexturesType],
+    ) -> Vec3A {
+        let albedo = textures[self.texture_index.index()].value(rec.u, rec.v, rec.position);
+        let pdf = self.pdf(r_in, rec, direction, textures).unwrap_or(0.0);
+        albedo * pdf
+    }
+
+    /// Directions below the surface are absorbed, so the light `scatter` reflects is the albedo
+    /// times its density and every kept sample is weighted by the albedo alone.
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        direction: Vec3A,
+        _textures: &[TexturesType],
+    ) -> Option<f32> {
+        if self.fuzz <= 0.0 {
+            return None;
+        }
+        if direction.dot(rec.normal) <= 0.0 {
+            return Some(0.0);
+        }
+        let reflected = vec3::reflect(r_in.direction.normalize(), rec.normal);
+        Some(self.fuzz_pdf(reflected, direction.normalize()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random;
+    use crate::sampler::SamplerType;
+    use crate::texture::solid::Solid;
+    use rand::Rng;
+
+    #[test]
+    fn pdf_matches_the_fuzzed_reflections() {
+        let textures = [TexturesType::Solid(Solid::new(Vec3A::splat(0.5)))];
+        let r_in = Ray::new(Vec3A::new(-0.6, 0.8, 0.0), Vec3A::new(0.6, -0.8, 0.0), 0.0);
+        let rec = HitRecord::new(1.0, &r_in, Vec3A::Y, 0, 0.0, 0.0);
+        let mirror = Vec3A::new(0.6, 0.8, 0.0);
+        let mut sampler = SamplerType::Independent.build(8, 1);
+        let mut rng = random::seeded_rng(9);
+        let count = 200_000;
+        for fuzz in [0.3, 1.0, 1.5] {
+            let metal = Metal::new(0, fuzz, None);
+            // the share of reflections kept above the surface and their mean cosine to the mirror
+            // direction, sampled and integrated from the pdf over uniform directions.
+            let (mut kept, mut cosine) = (0.0, 0.0);
+            for index in 0..count {
+                sampler.start_pixel_sample(0, 0, index);
+                if let Some(r) = metal.scatter(&r_in, &rec, &textures, &mut sampler) {
+                    kept += 1.0;
+                    cosine += r.scattered.direction.normalize().dot(mirror);
+                }
+            }
+            // uniform directions in the cone around the mirror direction the ball covers.
+            let min_cos = if fuzz < 1.0 {
+                (1.0 - fuzz * fuzz).sqrt()
+            } else {
+                -1.0
+            };
+            let (a, b) = mirror.any_orthonormal_pair();
+            let (mut pdf_kept, mut pdf_cosine) = (0.0, 0.0);
+            for _ in 0..count {
+                let cos = min_cos + (1.0 - min_cos) * rng.gen::<f32>();
+                let sin = (1.0 - cos * cos).sqrt();
+                let phi = 2.0 * PI * rng.gen::<f32>();
+                let direction = a * sin * phi.cos() + b * sin * phi.sin() + mirror * cos;
+                let pdf = metal.pdf(&r_in, &rec, direction, &textures).unwrap();
+                pdf_kept += pdf;
+                pdf_cosine += pdf * cos;
+            }
+            let scale = 2.0 * PI * (1.0 - min_cos) / count as f32;
+            let (pdf_kept, pdf_cosine) = (pdf_kept * scale, pdf_cosine * scale);
+            let (kept, cosine) = (kept / count as f32, cosine / count as f32);
+            assert!((kept - pdf_kept).abs() < 0.02, "{fuzz}: {kept} {pdf_kept}");
+            assert!(
+                (cosine - pdf_cosine).abs() < 0.02,
+                "{fuzz}: {cosine} {pdf_cosine}"
+            );
+        }
+    }
+
+    #[test]
+    fn kept_reflections_are_weighted_by_the_albedo() {
+        let textures = [TexturesType::Solid(Solid::new(Vec3A::new(0.9, 0.5, 0.1)))];
+        let r_in = Ray::new(Vec3A::new(-0.6, 0.8, 0.0), Vec3A::new(0.6, -0.8, 0.0), 0.0);
+        let rec = HitRecord::new(1.0, &r_in, Vec3A::Y, 0, 0.0, 0.0);
+        let metal = Metal::new(0, 0.5, None);
+        let mut sampler = SamplerType::Independent.build(10, 1);
+        for index in 0..100 {
+            sampler.start_pixel_sample(0, 0, index);
+            let Some(sample) = metal.sample(&r_in, &rec, &textures, &mut sampler) else {
+                continue;
+            };
+            let direction = sample.scattered.direction;
+            let pdf = sample.pdf.unwrap();
+            let expected = metal.eval(&r_in, &rec, direction, &textures) / pdf;
+            assert!(sample.weight.abs_diff_eq(expected, 1e-4), "{expected}");
+            assert_eq!(sample.weight, Vec3A::new(0.9, 0.5, 0.1));
         }
+        assert!(Metal::new(0, 0.0, None)
+            .pdf(&r_in, &rec, Vec3A::Y, &textures)
+            .is_none());
     }
 }
@@ -0,0 +1,168 @@
+use crate::vec3;
+use glam::{Vec2, Vec3A};
+use std::f32::consts::PI;
+
+// narrower distributions are treated as perfect mirrors.
+const MIN_ALPHA: f32 = 1e-3;
+
+/// Trowbridge-Reitz (GGX) microfacet reflection off a conductor, Walter et al. 2007, with
+/// Schlick's approximation of the fresnel term. Directions point away from the surface.
+pub struct Ggx {
+    /// Width of the distribution of microfacet normals.
+    pub alpha: f32,
+    /// Reflectance at normal incidence.
+    pub f0: Vec3A,
+}
+
+impl Ggx {
+    /// Distribution with `alpha` the square of `roughness`, which is clamped to `[0, 1]`.
+    pub fn from_roughness(roughness: f32, f0: Vec3A) -> Self {
+        let roughness = roughness.clamp(0.0, 1.0);
+        Self {
+            alpha: roughness * roughness,
+            f0,
+        }
+    }
+
+    /// Reflects like a mirror, `pdf` and `eval` can't be used.
+    pub fn is_specular(&self) -> bool {
+        self.alpha < MIN_ALPHA
+    }
+
+    fn distribution(&self, cos_h: f32) -> f32 {
+        let alpha2 = self.alpha * self.alpha;
+        let d = cos_h * cos_h * (alpha2 - 1.0) + 1.0;
+        alpha2 / (PI * d * d)
+    }
+
+    fn masking(&self, cos: f32) -> f32 {
+        let alpha2 = self.alpha * self.alpha;
+        2.0 * cos / (cos + (alpha2 + (1.0 - alpha2) * cos * cos).sqrt())
+    }
+
+    fn fresnel(&self, cos: f32) -> Vec3A {
+        self.f0 + (1.0 - self.f0) * (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+    }
+
+    /// Light from `wi` reflected towards `wo` off a surface with `normal`, times the cosine.
+    pub fn eval(&self, normal: Vec3A, wo: Vec3A, wi: Vec3A) -> Vec3A {
+        let cos_o = normal.dot(wo);
+        let cos_i = normal.dot(wi);
+        if cos_o <= 0.0 || cos_i <= 0.0 {
+            return Vec3A::ZERO;
+        }
+        let h = (wo + wi).normalize();
+        self.fresnel(wi.dot(h))
+            * self.distribution(normal.dot(h))
+            * self.masking(cos_o)
+            * self.masking(cos_i)
+            / (4.0 * cos_o)
+    }
+
+    /// Density of `sample` picking `wi`.
+    pub fn pdf(&self, normal: Vec3A, wo: Vec3A, wi: Vec3A) -> f32 {
+        if normal.dot(wo) <= 0.0 || normal.dot(wi) <= 0.0 {
+            return 0.0;
+        }
+        let h = (wo + wi).normalize();
+        let cos_h = normal.dot(h);
+        self.distribution(cos_h) * cos_h / (4.0 * wo.dot(h))
+    }
+
+    /// Reflects `wo` off a microfacet normal picked with `u`, returning the direction and `eval`
+    /// over `pdf`. `None` when the reflection points into the surface.
+    pub fn sample(&self, normal: Vec3A, wo: Vec3A, u: Vec2) -> Option<(Vec3A, Vec3A)> {
+        let cos_o = normal.dot(wo);
+        if cos_o <= 0.0 {
+            return None;
+        }
+        if self.is_specular() {
+            return Some((vec3::reflect(-wo, normal), self.fresnel(cos_o)));
+        }
+        let alpha2 = self.alpha * self.alpha;
+        let cos_h = ((1.0 - u.x) / (1.0 + (alpha2 - 1.0) * u.x)).sqrt();
+        let sin_h = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.y;
+        let (a, b) = normal.any_orthonormal_pair();
+        let h = a * phi.cos() * sin_h + b * phi.sin() * sin_h + normal * cos_h;
+        let wi = vec3::reflect(-wo, h);
+        let cos_i = normal.dot(wi);
+        if cos_i <= 0.0 {
+            return None;
+        }
+        let cos_oh = wo.dot(h);
+        let weight = self.fresnel(cos_oh) * self.masking(cos_o) * self.masking(cos_i) * cos_oh
+            / (cos_o * cos_h);
+        Some((wi, weight))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random;
+    use rand::Rng;
+
+    fn random_wo(normal: Vec3A, rng: &mut random::TracerRng) -> Vec3A {
+        loop {
+            let wo = vec3::random_in_unit_sphere(rng).normalize();
+            if wo.dot(normal) > 0.05 {
+                return wo;
+            }
+        }
+    }
+
+    #[test]
+    fn sample_weight_is_eval_over_pdf() {
+        let normal = Vec3A::new(0.3, 0.9, -0.2).normalize();
+        let mut rng = random::seeded_rng(22);
+        for roughness in [0.1, 0.4, 0.7, 1.0] {
+            let ggx = Ggx::from_roughness(roughness, Vec3A::new(0.9, 0.6, 0.3));
+            for _ in 0..5_000 {
+                let wo = random_wo(normal, &mut rng);
+                let Some((wi, weight)) = ggx.sample(normal, wo, Vec2::new(rng.gen(), rng.gen()))
+                else {
+                    continue;
+                };
+                let pdf = ggx.pdf(normal, wo, wi);
+                assert!(pdf > 0.0);
+                let expected = ggx.eval(normal, wo, wi) / pdf;
+                assert!(
+                    (weight - expected).abs().max_element() <= 1e-3 * expected.max_element(),
+                    "weight {weight} is not {expected} at roughness {roughness}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_the_samples_kept() {
+        let normal = Vec3A::Y;
+        let wo = Vec3A::new(0.6, 0.8, 0.0);
+        let mut rng = random::seeded_rng(23);
+        for roughness in [0.3, 0.8] {
+            let ggx = Ggx::from_roughness(roughness, Vec3A::ONE);
+            let samples = 200_000;
+            let kept = (0..samples)
+                .filter(|_| {
+                    ggx.sample(normal, wo, Vec2::new(rng.gen(), rng.gen()))
+                        .is_some()
+                })
+                .count() as f32
+                / samples as f32;
+            // the pdf over the hemisphere above the surface, uniformly sampled.
+            let integral = (0..samples)
+                .map(|_| {
+                    let wi = vec3::random_in_unit_sphere(&mut rng).normalize();
+                    ggx.pdf(normal, wo, wi * wi.dot(normal).signum())
+                })
+                .sum::<f32>()
+                / samples as f32;
+            let integral = integral * 2.0 * PI;
+            assert!(
+                (integral - kept).abs() < 0.02,
+                "pdf integrates to {integral} with {kept} of the samples kept"
+            );
+        }
+    }
+}
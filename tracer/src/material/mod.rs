@@ -1,22 +1,29 @@
 pub mod dieletric;
 pub mod diffuse_light;
+pub mod glossy;
 pub mod isotropic;
 pub mod lambertian;
 pub mod metal;
+pub mod microfacet;
 pub mod pbr;
 
+use crate::error::TracerResult;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
 use crate::material::dieletric::Dieletric;
 use crate::material::diffuse_light::DiffuseLight;
+use crate::material::glossy::Glossy;
 use crate::material::isotropic::Isotropic;
 use crate::material::lambertian::Lambertian;
 use crate::material::metal::Metal;
 use glam::Vec3A;
 
-use serde::{Deserialize, Serialize};
 use crate::material::pbr::Pbr;
-use crate::texture::TexturesType;
+use crate::reference::SceneNames;
+use crate::sampler::Sampler;
+use crate::texture::{Texture, TexturesType};
+use crate::validation::Validator;
+use serde::{Deserialize, Serialize};
 
 pub struct ScatterRecord {
     pub texture_index: usize,
@@ -32,16 +39,75 @@ impl ScatterRecord {
     }
 }
 
+/// Ray picked by `Material::sample`.
+pub struct BsdfSample {
+    pub scattered: Ray,
+    /// `eval` over the density, what the light arriving along `scattered` is multiplied with.
+    pub weight: Vec3A,
+    /// Density the direction was picked with, `None` for mirrors and glass.
+    pub pdf: Option<f32>,
+}
+
 // todo move to rc over box - consider once we add threading
 // starting to doubt if using pointers to trait objects is the best approach
 pub trait Material {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, textures: &Vec<TexturesType>) -> Option<ScatterRecord>;
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        textures: &[TexturesType],
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord>;
     fn color(&self, _u: f32, _v: f32) -> Vec3A {
         Vec3A::ZERO
     }
     fn emitted(&self) -> Option<usize> {
         None
     }
+    /// Texture of the surface color, used for the albedo aov.
+    fn albedo(&self) -> Option<usize> {
+        None
+    }
+    /// Light arriving from `direction` that's scattered back along `r_in`, the bsdf times the
+    /// cosine. Only called for materials `pdf` returns a density for.
+    fn eval(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _direction: Vec3A,
+        _textures: &[TexturesType],
+    ) -> Vec3A {
+        Vec3A::ZERO
+    }
+    /// Density of `sample` picking `direction`. `None` for materials that only scatter into
+    /// single directions, like mirrors and glass, these don't sample the lights.
+    fn pdf(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _direction: Vec3A,
+        _textures: &[TexturesType],
+    ) -> Option<f32> {
+        None
+    }
+    /// Picks the scattered ray with its weight and density. By default the ray of `scatter`
+    /// weighted by its attenuation, which holds when `scatter` follows `eval` exactly.
+    fn sample(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        textures: &[TexturesType],
+        sampler: &mut dyn Sampler,
+    ) -> Option<BsdfSample> {
+        let r = self.scatter(r_in, rec, textures, sampler)?;
+        let weight = textures[r.texture_index].value(rec.u, rec.v, rec.position);
+        let pdf = self.pdf(r_in, rec, r.scattered.direction, textures);
+        Some(BsdfSample {
+            scattered: r.scattered,
+            weight,
+            pdf,
+        })
+    }
 }
 
 pub struct MaterialList {
@@ -57,17 +123,55 @@ pub enum MaterialType {
     Isotropic(Isotropic),
     DiffuseLight(DiffuseLight),
     Pbr(Pbr),
+    Glossy(Glossy),
+}
+
+impl MaterialType {
+    /// Replaces named texture references with their index.
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        match self {
+            MaterialType::Lambertian(l) => l.resolve(names),
+            MaterialType::Metal(m) => m.resolve(names),
+            MaterialType::Dieletric(d) => d.resolve(names),
+            MaterialType::Isotropic(i) => i.resolve(names),
+            MaterialType::DiffuseLight(d) => d.resolve(names),
+            MaterialType::Pbr(p) => p.resolve(names),
+            MaterialType::Glossy(g) => g.resolve(names),
+        }
+    }
+
+    /// Records every problem with this material, `location` is its json path.
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        match self {
+            MaterialType::Lambertian(l) => l.validate(&format!("{location}.Lambertian"), validator),
+            MaterialType::Metal(m) => m.validate(&format!("{location}.Metal"), validator),
+            MaterialType::Dieletric(d) => d.validate(&format!("{location}.Dieletric"), validator),
+            MaterialType::Isotropic(i) => i.validate(&format!("{location}.Isotropic"), validator),
+            MaterialType::DiffuseLight(d) => {
+                d.validate(&format!("{location}.DiffuseLight"), validator)
+            }
+            MaterialType::Pbr(p) => p.validate(&format!("{location}.Pbr"), validator),
+            MaterialType::Glossy(g) => g.validate(&format!("{location}.Glossy"), validator),
+        }
+    }
 }
 
 impl Material for MaterialType {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, textures: &Vec<TexturesType>) -> Option<ScatterRecord> {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        textures: &[TexturesType],
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
         match self {
-            MaterialType::Lambertian(l) => l.scatter(r_in, rec, textures),
-            MaterialType::Metal(m) => m.scatter(r_in, rec, textures),
-            MaterialType::Dieletric(d) => d.scatter(r_in, rec, textures),
-            MaterialType::Isotropic(i) => i.scatter(r_in, rec, textures),
-            MaterialType::DiffuseLight(d) => d.scatter(r_in, rec, textures),
-            MaterialType::Pbr(p) => p.scatter(r_in, rec, textures),
+            MaterialType::Lambertian(l) => l.scatter(r_in, rec, textures, sampler),
+            MaterialType::Metal(m) => m.scatter(r_in, rec, textures, sampler),
+            MaterialType::Dieletric(d) => d.scatter(r_in, rec, textures, sampler),
+            MaterialType::Isotropic(i) => i.scatter(r_in, rec, textures, sampler),
+            MaterialType::DiffuseLight(d) => d.scatter(r_in, rec, textures, sampler),
+            MaterialType::Pbr(p) => p.scatter(r_in, rec, textures, sampler),
+            MaterialType::Glossy(g) => g.scatter(r_in, rec, textures, sampler),
         }
     }
     fn color(&self, u: f32, v: f32) -> Vec3A {
@@ -78,6 +182,7 @@ impl Material for MaterialType {
             MaterialType::Isotropic(i) => i.color(u, v),
             MaterialType::DiffuseLight(d) => d.color(u, v),
             MaterialType::Pbr(p) => p.color(u, v),
+            MaterialType::Glossy(g) => g.color(u, v),
         }
     }
     fn emitted(&self) -> Option<usize> {
@@ -88,6 +193,69 @@ impl Material for MaterialType {
             MaterialType::Isotropic(i) => i.emitted(),
             MaterialType::DiffuseLight(d) => d.emitted(),
             MaterialType::Pbr(p) => p.emitted(),
+            MaterialType::Glossy(g) => g.emitted(),
+        }
+    }
+    fn albedo(&self) -> Option<usize> {
+        match self {
+            MaterialType::Lambertian(l) => l.albedo(),
+            MaterialType::Metal(m) => m.albedo(),
+            MaterialType::Dieletric(d) => d.albedo(),
+            MaterialType::Isotropic(i) => i.albedo(),
+            MaterialType::DiffuseLight(d) => d.albedo(),
+            MaterialType::Pbr(p) => p.albedo(),
+            MaterialType::Glossy(g) => g.albedo(),
+        }
+    }
+    fn eval(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        direction: Vec3A,
+        textures: &[TexturesType],
+    ) -> Vec3A {
+        match self {
+            MaterialType::Lambertian(l) => l.eval(r_in, rec, direction, textures),
+            MaterialType::Metal(m) => m.eval(r_in, rec, direction, textures),
+            MaterialType::Dieletric(d) => d.eval(r_in, rec, direction, textures),
+            MaterialType::Isotropic(i) => i.eval(r_in, rec, direction, textures),
+            MaterialType::DiffuseLight(d) => d.eval(r_in, rec, direction, textures),
+            MaterialType::Pbr(p) => p.eval(r_in, rec, direction, textures),
+            MaterialType::Glossy(g) => g.eval(r_in, rec, direction, textures),
+        }
+    }
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        direction: Vec3A,
+        textures: &[TexturesType],
+    ) -> Option<f32> {
+        match self {
+            MaterialType::Lambertian(l) => l.pdf(r_in, rec, direction, textures),
+            MaterialType::Metal(m) => m.pdf(r_in, rec, direction, textures),
+            MaterialType::Dieletric(d) => d.pdf(r_in, rec, direction, textures),
+            MaterialType::Isotropic(i) => i.pdf(r_in, rec, direction, textures),
+            MaterialType::DiffuseLight(d) => d.pdf(r_in, rec, direction, textures),
+            MaterialType::Pbr(p) => p.pdf(r_in, rec, direction, textures),
+            MaterialType::Glossy(g) => g.pdf(r_in, rec, direction, textures),
+        }
+    }
+    fn sample(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        textures: &[TexturesType],
+        sampler: &mut dyn Sampler,
+    ) -> Option<BsdfSample> {
+        match self {
+            MaterialType::Lambertian(l) => l.sample(r_in, rec, textures, sampler),
+            MaterialType::Metal(m) => m.sample(r_in, rec, textures, sampler),
+            MaterialType::Dieletric(d) => d.sample(r_in, rec, textures, sampler),
+            MaterialType::Isotropic(i) => i.sample(r_in, rec, textures, sampler),
+            MaterialType::DiffuseLight(d) => d.sample(r_in, rec, textures, sampler),
+            MaterialType::Pbr(p) => p.sample(r_in, rec, textures, sampler),
+            MaterialType::Glossy(g) => g.sample(r_in, rec, textures, sampler),
         }
     }
 }
@@ -1,17 +1,22 @@
-use glam::Vec3A;
+use crate::error::TracerResult;
 use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
 use crate::material::{Material, ScatterRecord};
+use crate::reference::{Reference, SceneNames};
+use crate::sampler::Sampler;
 use crate::texture::{Texture, TexturesType};
+use crate::validation::{self, Validator};
 use crate::vec3;
 
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Pbr {
-    texture_index: usize, // albedo
-    roughness_index: usize,
-    emitted: Option<usize>,
+    #[serde(alias = "texture")]
+    texture_index: Reference, // albedo
+    #[serde(alias = "roughness")]
+    roughness_index: Reference,
+    emitted: Option<Reference>,
     // metalness: usize,
     // ao: usize,
 }
@@ -19,36 +24,66 @@ pub struct Pbr {
 impl Pbr {
     pub fn new(texture_index: usize, roughness_index: usize, emitted: Option<usize>) -> Self {
         Self {
-            texture_index,
-            roughness_index,
-            emitted
+            texture_index: Reference::Index(texture_index),
+            roughness_index: Reference::Index(roughness_index),
+            emitted: emitted.map(Reference::Index),
             // metalness,
         }
     }
+
+    pub fn resolve(&mut self, names: &SceneNames) -> TracerResult<()> {
+        self.texture_index.resolve(&names.textures)?;
+        self.roughness_index.resolve(&names.textures)?;
+        if let Some(emitted) = &mut self.emitted {
+            emitted.resolve(&names.textures)?;
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.texture(
+            validation::aliased(location, "texture_index", "texture"),
+            &self.texture_index,
+        );
+        validator.texture(
+            validation::aliased(location, "roughness_index", "roughness"),
+            &self.roughness_index,
+        );
+        if let Some(emitted) = &self.emitted {
+            validator.texture(format!("{location}.emitted"), emitted);
+        }
+    }
 }
 
 impl Material for Pbr {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, textures: &Vec<TexturesType>) -> Option<ScatterRecord> {
-
-        let roughness = textures[self.roughness_index].value(rec.u, rec.v, rec.position);
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        textures: &[TexturesType],
+        sampler: &mut dyn Sampler,
+    ) -> Option<ScatterRecord> {
+        let roughness = textures[self.roughness_index.index()].value(rec.u, rec.v, rec.position);
         // let color = self.albedo.value(rec.u, rec.v, rec.position);
         // let metalness = self.metalness.value(rec.u, rec.v, rec.position);
         // todo check if we need to validate for degenerate cases
 
         // reflect based on roughness
-        let scatter_direction = vec3::reflect(r_in.direction, rec.normal + roughness * vec3::random_in_unit_sphere().normalize());
+        let scatter_direction = vec3::reflect(
+            r_in.direction,
+            rec.normal + roughness * vec3::sample_unit_sphere(sampler.get_2d()),
+        );
         Some(ScatterRecord {
-            texture_index: self.texture_index,
+            texture_index: self.texture_index.index(),
             scattered: Ray::new(rec.position, scatter_direction, r_in.time),
         })
-
     }
 
     fn emitted(&self) -> Option<usize> {
-        if let Some(emitted) = self.emitted {
-            Some(emitted)
-        } else {
-            None
-        }
+        self.emitted.as_ref().map(Reference::index)
     }
-}
\ No newline at end of file
+
+    fn albedo(&self) -> Option<usize> {
+        Some(self.texture_index.index())
+    }
+}
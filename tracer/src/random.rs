@@ -0,0 +1,30 @@
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+/// Random number generator used while rendering.
+pub type TracerRng = Pcg32;
+
+/// SplitMix64 finalizer, spreads nearby inputs over the whole range.
+pub fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Hash of a pixel under `seed`, the base of every per pixel random choice.
+pub fn pixel_hash(seed: u64, x: u32, y: u32) -> u64 {
+    let pixel = (u64::from(y) << 32) | u64::from(x);
+    mix(mix(seed) ^ pixel)
+}
+
+/// Generator for one sample of one pixel. Every sample gets its own stream so the result doesn't
+/// depend on which thread renders the pixel or in which order.
+pub fn sample_rng(seed: u64, x: u32, y: u32, sample: u32) -> TracerRng {
+    TracerRng::seed_from_u64(mix(pixel_hash(seed, x, y) ^ u64::from(sample)))
+}
+
+/// Generator for work outside of the per pixel loop, like building noise textures.
+pub fn seeded_rng(seed: u64) -> TracerRng {
+    TracerRng::seed_from_u64(mix(seed))
+}
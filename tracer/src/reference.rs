@@ -0,0 +1,158 @@
+use crate::error::{TracerError, TracerResult};
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Reference from a scene entry to a material or texture, either by its position in the list or
+/// by its name. Names are resolved to indices in `RenderBuilder::build`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Reference {
+    Index(usize),
+    Name(String),
+}
+
+impl Reference {
+    /// The index this reference points at, only valid once the reference has been resolved.
+    pub fn index(&self) -> usize {
+        match self {
+            Reference::Index(index) => *index,
+            Reference::Name(name) => panic!("unresolved reference {name:?}"),
+        }
+    }
+
+    pub fn resolve(&mut self, names: &NameTable) -> TracerResult<()> {
+        if let Reference::Name(name) = self {
+            *self = Reference::Index(names.get(name)?);
+        }
+        Ok(())
+    }
+}
+
+impl From<usize> for Reference {
+    fn from(index: usize) -> Self {
+        Reference::Index(index)
+    }
+}
+
+impl From<&str> for Reference {
+    fn from(name: &str) -> Self {
+        Reference::Name(name.to_string())
+    }
+}
+
+/// Scene list entry with an optional name other entries can refer to it by.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Named<T> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub value: T,
+}
+
+impl<T> Named<T> {
+    pub fn new(name: Option<String>, value: T) -> Self {
+        Self { name, value }
+    }
+}
+
+impl<T> From<T> for Named<T> {
+    fn from(value: T) -> Self {
+        Self { name: None, value }
+    }
+}
+
+/// Lookup from names to indices for one kind of scene entry.
+#[derive(Debug, Clone)]
+pub struct NameTable {
+    kind: &'static str,
+    indices: HashMap<String, usize>,
+}
+
+impl NameTable {
+    pub fn new<T>(kind: &'static str, entries: &[Named<T>]) -> TracerResult<Self> {
+        let mut indices = HashMap::new();
+        for (index, entry) in entries.iter().enumerate() {
+            if let Some(name) = &entry.name {
+                if indices.insert(name.clone(), index).is_some() {
+                    return Err(TracerError::DuplicateName {
+                        kind,
+                        name: name.clone(),
+                    });
+                }
+            }
+        }
+        Ok(Self { kind, indices })
+    }
+
+    pub fn get(&self, name: &str) -> TracerResult<usize> {
+        self.indices
+            .get(name)
+            .copied()
+            .ok_or_else(|| TracerError::UnknownReference {
+                kind: self.kind,
+                name: name.to_string(),
+            })
+    }
+}
+
+/// Name tables for everything a scene entry can refer to.
+#[derive(Debug, Clone)]
+pub struct SceneNames {
+    pub materials: NameTable,
+    pub textures: NameTable,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<Named<()>> {
+        vec![
+            Named::new(Some("red".to_string()), ()),
+            Named::new(None, ()),
+            Named::new(Some("blue".to_string()), ()),
+        ]
+    }
+
+    #[test]
+    fn references_are_numbers_or_names() {
+        let index: Reference = serde_json::from_str("2").unwrap();
+        assert_eq!(index, Reference::Index(2));
+        let name: Reference = serde_json::from_str("\"red\"").unwrap();
+        assert_eq!(name, Reference::from("red"));
+    }
+
+    #[test]
+    fn names_resolve_to_their_position() {
+        let names = NameTable::new("material", &entries()).unwrap();
+        let mut reference = Reference::from("blue");
+        reference.resolve(&names).unwrap();
+        assert_eq!(reference, Reference::Index(2));
+        // indices are kept as they are.
+        let mut reference = Reference::Index(7);
+        reference.resolve(&names).unwrap();
+        assert_eq!(reference, Reference::Index(7));
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        let names = NameTable::new("texture", &entries()).unwrap();
+        let error = Reference::from("green").resolve(&names).unwrap_err();
+        assert!(matches!(
+            error,
+            TracerError::UnknownReference { kind: "texture", name } if name == "green"
+        ));
+    }
+
+    #[test]
+    fn duplicate_names_are_errors() {
+        let mut entries = entries();
+        entries.push(Named::new(Some("red".to_string()), ()));
+        let error = NameTable::new("material", &entries).unwrap_err();
+        assert!(matches!(
+            error,
+            TracerError::DuplicateName { kind: "material", name } if name == "red"
+        ));
+    }
+}
@@ -1,13 +1,33 @@
+use crate::adaptive::{AdaptiveSettings, PixelVariance};
+use crate::aov::{self, Aov, AovPixel, AovSample};
+use crate::background::{Background, BackgroundBuilder};
 use crate::camera::{CamerBuilder, Camera};
-use crate::error::TracerResult;
+use crate::denoise::DenoiseSettings;
+use crate::error::{TracerError, TracerResult};
+use crate::film::Film;
+use crate::filter::{Filter, FilterType};
+use crate::framebuffer::{Framebuffer, OutputFormat};
+use crate::geometry::bvh_tree::{BvhSplit, BvhStats};
 use crate::geometry::hittable::HittableListBuilder;
 use crate::geometry::{Geometry, Hittable};
+use crate::intersection::hit_record::HitRecord;
 use crate::intersection::ray::Ray;
+use crate::light::{power_heuristic, Lights};
 use crate::material::{Material, MaterialList, MaterialType};
+use crate::reference::{NameTable, Named, SceneNames};
+use crate::sampler::{Sampler, SamplerType};
+use crate::stats::{self, PhaseTimes, Progress, RayCounters, RenderStats};
 use crate::texture::{Texture, TextureFile, TexturesType};
+use crate::tile::{self, Tile, TileOrder};
+use crate::tone_map::{DisplayTransform, ToneMapper};
+use crate::validation::{ValidationError, Validator};
 use crate::vec3;
-use glam::Vec3A;
-use rayon::prelude::*;
+use glam::{Vec2, Vec3A};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use std::{fs, io};
 
 use serde::{Deserialize, Serialize};
 
@@ -16,32 +36,130 @@ pub struct RenderBuilder {
     pub settings: RenderSettings,
     pub world: HittableListBuilder,
     pub camera: CamerBuilder,
-    pub materials: Vec<MaterialType>,
-    pub textures: Vec<TextureFile>,
+    pub materials: Vec<Named<MaterialType>>,
+    pub textures: Vec<Named<TextureFile>>,
+    /// Replaces `settings.background_color` when given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<BackgroundBuilder>,
 }
 
 impl RenderBuilder {
-    pub fn build(self) -> TracerResult<Renderer> {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> TracerResult<Self> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).map_err(|source| TracerError::FileError {
+            path: path.into(),
+            source,
+        })?;
+        Ok(serde_json::from_reader(io::BufReader::new(file))?)
+    }
+
+    /// Checks the whole scene without building it, returning every problem found.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut validator = Validator::new(&self.materials, &self.textures);
+        self.settings.validate("settings", &mut validator);
+        self.camera.validate("camera", &mut validator);
+        self.world.validate("world", &mut validator);
+        if let Some(background) = &self.background {
+            background.validate("background", &mut validator);
+        }
+        for (i, material) in self.materials.iter().enumerate() {
+            material
+                .value
+                .validate(&format!("materials[{i}]"), &mut validator);
+        }
+        for (i, texture) in self.textures.iter().enumerate() {
+            texture
+                .value
+                .validate(&format!("textures[{i}]"), &mut validator);
+        }
+        validator.finish()
+    }
+
+    /// Name lookups for the scene's materials and textures.
+    pub fn names(&self) -> TracerResult<SceneNames> {
+        Ok(SceneNames {
+            materials: NameTable::new("material", &self.materials)?,
+            textures: NameTable::new("texture", &self.textures)?,
+        })
+    }
+
+    /// Loads every `ObjMesh` in the world, appending the materials and textures of their mtl files
+    /// to the scene's lists.
+    pub fn load_meshes(&mut self) -> TracerResult<()> {
+        self.world
+            .load_meshes(&mut self.materials, &mut self.textures)
+    }
+
+    /// Replaces every named material and texture reference in the scene with its index.
+    pub fn resolve(&mut self) -> TracerResult<()> {
+        let names = self.names()?;
+        for material in &mut self.materials {
+            material.value.resolve(&names)?;
+        }
+        self.world.resolve(&names)
+    }
+
+    pub fn build(mut self) -> TracerResult<Renderer> {
+        let start = Instant::now();
+        let errors = self.validate();
+        if !errors.is_empty() {
+            return Err(TracerError::InvalidScene(errors));
+        }
+        self.load_meshes()?;
+        self.resolve()?;
         let textures = self
             .textures
             .into_iter()
-            .map(|t| t.try_into())
+            .map(|t| t.value.try_into())
             .collect::<TracerResult<Vec<TexturesType>>>()?;
+        let background = match self.background {
+            Some(background) => background.try_into()?,
+            None => Background::Color(self.settings.background_color),
+        };
         let camera = self.camera.build();
-        let geometry = self.world.try_into()?;
+        let geometry: Geometry = self.world.try_into()?;
+        let geometry = if self.settings.aovs.contains(&Aov::ObjectId) {
+            geometry.tag_objects()
+        } else {
+            geometry
+        };
+        let mut bvh_stats = None;
+        let bvh_start = Instant::now();
+        let geometry = if self.settings.bvh {
+            let mut stats = BvhStats::default();
+            let (t0, t1) = (self.camera.time0, self.camera.time1);
+            let geometry = geometry.accelerate(t0, t1, self.settings.bvh_split, &mut stats)?;
+            bvh_stats = Some(stats);
+            geometry
+        } else {
+            geometry
+        };
+        let bvh_time = bvh_start.elapsed();
         let settings = self.settings.clone();
+        let pool = match settings.threads {
+            Some(threads) => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()?,
+            ),
+            None => None,
+        };
 
-        Ok(Renderer::new(
-
-            MaterialList{
-                materials: self.materials.clone(),
+        let mut renderer = Renderer::new(
+            MaterialList {
+                materials: self.materials.into_iter().map(|m| m.value).collect(),
                 textures,
             },
-
             geometry,
             camera,
+            background,
             settings,
-        ))
+        );
+        renderer.bvh_stats = bvh_stats;
+        renderer.pool = pool;
+        renderer.times.bvh = bvh_time;
+        renderer.times.scene = start.elapsed() - bvh_time;
+        Ok(renderer)
     }
 }
 
@@ -62,6 +180,12 @@ fn default_samples() -> u32 {
 fn default_background_color() -> Vec3A {
     Vec3A::new(0.0, 0.0, 0.0)
 }
+fn default_bvh() -> bool {
+    true
+}
+fn default_tile_size() -> u32 {
+    16
+}
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RenderSettings {
@@ -76,6 +200,64 @@ pub struct RenderSettings {
     #[serde(default = "default_background_color")]
     pub background_color: Vec3A,
     pub path: String,
+    /// Build a bvh over the world's objects, turning it off is only useful for debugging.
+    #[serde(default = "default_bvh")]
+    pub bvh: bool,
+    #[serde(default)]
+    pub bvh_split: BvhSplit,
+    /// Seed of every random choice made while rendering, the same seed gives the same image.
+    #[serde(default)]
+    pub seed: u64,
+    /// Sampler for the pixel, lens, time and scattering dimensions.
+    #[serde(default)]
+    pub sampler: SamplerType,
+    /// Filter samples are weighted with when they're added to the image.
+    #[serde(default)]
+    pub filter: FilterType,
+    /// Radius of the filter in pixels, each filter has its own default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter_radius: Option<f32>,
+    /// Exposure of 8 bit outputs in stops.
+    #[serde(default)]
+    pub exposure: f32,
+    /// Tone mapper of 8 bit outputs, hdr outputs keep the linear values.
+    #[serde(default)]
+    pub tone_mapper: ToneMapper,
+    /// Adapt the samples per pixel to the noise, `samples` is then the minimum.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptive: Option<AdaptiveSettings>,
+    /// Extra passes rendered from the first camera hit. They're layers of the output when it's an
+    /// exr, otherwise they're saved next to it as `<name>.<pass>.exr`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aovs: Vec<Aov>,
+    /// Save the passes as separate images even when the output is an exr.
+    #[serde(default)]
+    pub aov_files: bool,
+    /// Denoise the image with the albedo and normal of the first hits as guides.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denoise: Option<DenoiseSettings>,
+    /// Width and height of the tiles the image is split into, every tile is rendered by one
+    /// thread.
+    #[serde(default = "default_tile_size")]
+    pub tile_size: u32,
+    #[serde(default)]
+    pub tile_order: TileOrder,
+    /// Threads rendering tiles, all cores when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+    /// Sample the spheres and rects with an emitting material directly at every diffuse bounce
+    /// instead of waiting for a bounce to hit them. Off by default so scenes render as before.
+    #[serde(default)]
+    pub light_sampling: bool,
+    /// Bounces after which paths are ended at random, the fewer the more light they still carry.
+    /// Paths only end at `max_depth` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roulette_depth: Option<u32>,
+    /// Highest luminance of the light a sample reflects towards the camera, brighter samples are
+    /// scaled down to it to remove fireflies at the cost of some energy. Lights and background
+    /// seen directly aren't clamped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clamp: Option<f32>,
 }
 
 impl RenderSettings {
@@ -94,19 +276,155 @@ impl RenderSettings {
             max_depth,
             background_color,
             path,
+            bvh: default_bvh(),
+            bvh_split: BvhSplit::default(),
+            seed: 0,
+            sampler: SamplerType::default(),
+            filter: FilterType::default(),
+            filter_radius: None,
+            exposure: 0.0,
+            tone_mapper: ToneMapper::default(),
+            adaptive: None,
+            aovs: Vec::new(),
+            aov_files: false,
+            denoise: None,
+            tile_size: default_tile_size(),
+            tile_order: TileOrder::default(),
+            threads: None,
+            light_sampling: false,
+            roulette_depth: None,
+            clamp: None,
+        }
+    }
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.check(
+            self.image_width >= 2,
+            format!("{location}.image_width"),
+            "image_width must be at least 2",
+        );
+        validator.check(
+            self.aspect_ratio > 0.0,
+            format!("{location}.aspect_ratio"),
+            "aspect_ratio must be positive",
+        );
+        if self.aspect_ratio > 0.0 && self.image_width >= 2 {
+            validator.check(
+                self.image_height() >= 2,
+                format!("{location}.aspect_ratio"),
+                "image height must be at least 2",
+            );
+        }
+        validator.check(
+            self.samples > 0,
+            format!("{location}.samples"),
+            "samples must be positive",
+        );
+        if let Err(e) = OutputFormat::from_path(&self.path) {
+            validator.error(format!("{location}.path"), e.to_string());
+        }
+        let radius = self.filter().radius;
+        validator.check(
+            radius.is_finite() && radius >= 0.5,
+            format!("{location}.filter_radius"),
+            "filter_radius must be at least 0.5 so every pixel gets samples",
+        );
+        validator.check(
+            self.exposure.is_finite(),
+            format!("{location}.exposure"),
+            "exposure must be finite",
+        );
+        self.tone_mapper
+            .validate(&format!("{location}.tone_mapper"), validator);
+        if let Some(adaptive) = &self.adaptive {
+            adaptive.validate(&format!("{location}.adaptive"), self.samples, validator);
+        }
+        aov::validate(&format!("{location}.aovs"), &self.aovs, validator);
+        if let Some(denoise) = &self.denoise {
+            denoise.validate(&format!("{location}.denoise"), validator);
+        }
+        validator.check(
+            self.tile_size > 0,
+            format!("{location}.tile_size"),
+            "tile_size must be positive",
+        );
+        validator.check(
+            self.threads != Some(0),
+            format!("{location}.threads"),
+            "threads must be positive",
+        );
+        if let Some(clamp) = self.clamp {
+            validator.check(
+                clamp.is_finite() && clamp > 0.0,
+                format!("{location}.clamp"),
+                "clamp must be positive",
+            );
         }
     }
+
     pub fn image_height(&self) -> u32 {
         (self.image_width as f32 / self.aspect_ratio) as u32
     }
+
+    pub fn filter(&self) -> Filter {
+        let radius = self
+            .filter_radius
+            .unwrap_or_else(|| self.filter.default_radius());
+        Filter::new(self.filter, radius)
+    }
+
+    pub fn display_transform(&self) -> DisplayTransform {
+        DisplayTransform::new(self.exposure, self.tone_mapper)
+    }
+
+    /// Most samples a pixel can take.
+    pub fn max_samples(&self) -> u32 {
+        self.adaptive
+            .as_ref()
+            .map_or(self.samples, |adaptive| adaptive.max_samples)
+    }
+}
+
+/// Everything a render produces.
+pub struct RenderOutput {
+    /// Denoised when `settings.denoise` is set.
+    pub image: Framebuffer,
+    /// Samples taken by every pixel in row major order.
+    pub sample_counts: Vec<u32>,
+    /// One image per pass of `settings.aovs`, in the same order.
+    pub aovs: Vec<(Aov, Framebuffer)>,
+    pub stats: RenderStats,
+}
+
+/// Called from the render threads whenever a part of the image is finished.
+pub type ProgressCallback = Box<dyn Fn(&Progress) + Send + Sync>;
+
+/// Called from the render threads with every finished tile and its pixels.
+pub type TileCallback = Box<dyn Fn(&Tile, &Framebuffer) + Send + Sync>;
+
+/// Everything a tile renders, merged into the image once all tiles are done.
+struct TileOutput {
+    tile: Tile,
+    /// Covers the tile and the pixels around it its samples reach.
+    film: Film,
+    counts: Vec<u32>,
+    aov_pixels: Vec<AovPixel>,
+    counters: RayCounters,
 }
 
 pub struct Renderer {
     materials: MaterialList,
     geometry: Geometry,
     camera: Camera,
+    background: Background,
+    lights: Lights,
 
     settings: RenderSettings,
+    bvh_stats: Option<BvhStats>,
+    times: PhaseTimes,
+    progress: Option<ProgressCallback>,
+    tile_callback: Option<TileCallback>,
+    /// Threads of `settings.threads`, the global pool is used when unset.
+    pool: Option<rayon::ThreadPool>,
 }
 
 impl Renderer {
@@ -114,92 +432,674 @@ impl Renderer {
         materials: MaterialList,
         geometry: Geometry,
         camera: Camera,
+        background: Background,
         settings: RenderSettings,
     ) -> Self {
+        let lights = if settings.light_sampling {
+            Lights::new(&geometry, &materials.materials, &background)
+        } else {
+            Lights::default()
+        };
         Self {
             materials,
             geometry,
             camera,
+            background,
+            lights,
             settings,
+            bvh_stats: None,
+            times: PhaseTimes::default(),
+            progress: None,
+            tile_callback: None,
+            pool: None,
         }
     }
 
-    pub fn render(&self) -> TracerResult<()> {
+    /// Calls `callback` with the progress of every render, from the render threads.
+    pub fn on_progress<F>(&mut self, callback: F)
+    where
+        F: Fn(&Progress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Box::new(callback));
+    }
+
+    /// Calls `callback` with every tile as soon as it's rendered, from the render threads. The
+    /// pixels only hold the tile's own samples, at its edges wide filters also get samples from
+    /// the neighbouring tiles in the final image.
+    pub fn on_tile<F>(&mut self, callback: F)
+    where
+        F: Fn(&Tile, &Framebuffer) + Send + Sync + 'static,
+    {
+        self.tile_callback = Some(Box::new(callback));
+    }
+
+    /// Emitters sampled at every diffuse bounce, empty if `settings.light_sampling` is off.
+    pub fn lights(&self) -> &Lights {
+        &self.lights
+    }
+
+    /// Statistics of the bvh built over the world, `None` if `settings.bvh` is off.
+    pub fn bvh_stats(&self) -> Option<&BvhStats> {
+        self.bvh_stats.as_ref()
+    }
+
+    /// Renders the scene into a linear framebuffer without touching the filesystem.
+    pub fn render_image(&self) -> Framebuffer {
+        self.render_output().image
+    }
+
+    /// Renders the scene along with the sample counts and the passes of `settings.aovs`.
+    pub fn render_output(&self) -> RenderOutput {
+        let render_start = Instant::now();
+        let width = self.settings.image_width;
         let height = self.settings.image_height();
-        let mut imgbuf = image::ImageBuffer::new(self.settings.image_width, height);
-        let path = self.settings.path.clone();
-        let _rng = rand::thread_rng();
-        // todo evaluate if we should change how we're iterating.
-        imgbuf
-            .enumerate_pixels_mut()
-            .par_bridge()
-            .for_each(|(x, y, pixel)| self.per_pixel(x, y, pixel, height));
-        imgbuf.save(path)?;
-
-        Ok(())
-    }
-
-    pub fn per_pixel(&self, x: u32, y: u32, pixel: &mut image::Rgb<u8>, height: u32) {
-        let mut color = Vec3A::ZERO;
-        for _s in 0..self.settings.samples {
-            let u = (x as f32 + rand::random::<f32>()) / (self.settings.image_width - 1) as f32;
-            let v = (y as f32 + rand::random::<f32>()) / (height - 1) as f32;
-            let v = 1.0 - v; // flip height
-            let ray = self.camera.get_ray(u, v);
-            let rc = self.ray_color(&ray, self.settings.max_depth as i32);
-            // let rc = renderer.ray_color(u, v);
-            color += rc;
-        }
-        let scaled = vec3::sqrt(color * (1.0 / self.settings.samples as f32));
-        let clamped = scaled.clamp(Vec3A::ZERO, Vec3A::new(0.999, 0.999, 0.999)) * 255.99;
-        *pixel = image::Rgb([clamped.x as u8, clamped.y as u8, clamped.z as u8]);
-    }
-
-    // todo add energy conservation
-    // pub fn ray_color(
-    //     &self,
-    //     u: f32,
-    //     v: f32,
-    // ) -> Vec3A {
-    //     let mut ray = self.camera.get_ray(u, v);
-    //     let mut s_emitted = Vec3A::ZERO;
-    //     let mut multiplier = 1.0;
-    //     for i in 0..self.depth {
-    //         if let Some(t) = self.geometry.hit(&ray, 0.001, f32::INFINITY) {
-    //             let material = &self.materials[t.material_index as usize];
-    //             let emitted = material.emitted(t.u, t.v, t.position);
-    //             if let Some(r) = material.scatter(&ray, &t) {
-    //                 ray = r.scattered;
-    //                 // emitted + r.attenuation * self.ray_color(&r.scattered, depth - 1)
-    //                 s_emitted += (emitted  * r.attenuation) * multiplier;
-    //                 multiplier *= 0.5;
-    //             } else {
-    //                 // emitted
-    //                 return s_emitted * emitted * multiplier;
-    //             }
-    //         } else {
-    //             return s_emitted + self.background_color * multiplier;
-    //         }
-    //     }
-    //     s_emitted
-    // }
-
-    pub fn ray_color(&self, ray: &Ray, depth: i32) -> Vec3A {
-        if let Some(t) = self.geometry.hit(ray, 0.001, f32::INFINITY) {
-            let material = &self.materials.materials[t.material_index as usize];
-            let emitted = if let Some(id) = material.emitted() {
-                self.materials.textures[id].value(t.u, t.v, t.position)
-            } else {
-                Vec3A::ZERO
+        let filter = self.settings.filter();
+        // the denoiser needs the albedo and normal passes as guides.
+        let with_aovs = !self.settings.aovs.is_empty() || self.settings.denoise.is_some();
+        let tiles = tile::tiles(
+            width,
+            height,
+            self.settings.tile_size,
+            self.settings.tile_order,
+        );
+        let total_pixels = (width * height) as u64;
+        let finished_pixels = AtomicU64::new(0);
+        let finished_samples = AtomicU64::new(0);
+        // every thread takes the next tile in order when it's done with its last one.
+        let next_tile = AtomicUsize::new(0);
+        let outputs = Mutex::new(Vec::with_capacity(tiles.len()));
+        let worker = || {
+            while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                let output = self.render_tile(tile, &filter, with_aovs);
+                if let Some(callback) = &self.tile_callback {
+                    let mut film = Film::region(tile.x, tile.y, tile.width, tile.height);
+                    film.merge(&output.film);
+                    callback(tile, &film.to_framebuffer());
+                }
+                if let Some(progress) = &self.progress {
+                    let pixels = output.counts.len() as u64;
+                    let samples = output.counts.iter().map(|&count| count as u64).sum();
+                    progress(&Progress {
+                        pixels: finished_pixels.fetch_add(pixels, Ordering::Relaxed) + pixels,
+                        total_pixels,
+                        samples: finished_samples.fetch_add(samples, Ordering::Relaxed) + samples,
+                        elapsed: render_start.elapsed(),
+                    });
+                }
+                outputs.lock().unwrap().push(output);
+            }
+        };
+        let run = || {
+            rayon::scope(|scope| {
+                for _ in 0..rayon::current_num_threads() {
+                    scope.spawn(|_| worker());
+                }
+            })
+        };
+        match &self.pool {
+            Some(pool) => pool.install(run),
+            None => run(),
+        }
+        let mut outputs = outputs.into_inner().unwrap();
+
+        // merged top to bottom so the sums depend neither on the thread scheduling nor on the
+        // tile order.
+        outputs.sort_by_key(|output| (output.tile.y, output.tile.x));
+        let mut film = Film::new(width, height);
+        let mut counts = vec![0; (width * height) as usize];
+        let mut aov_pixels = vec![AovPixel::default(); if with_aovs { counts.len() } else { 0 }];
+        let mut counters = RayCounters::default();
+        for output in outputs {
+            let tile = output.tile;
+            film.merge(&output.film);
+            for row in 0..tile.height {
+                let from = (row * tile.width) as usize..((row + 1) * tile.width) as usize;
+                let to = ((tile.y + row) * width + tile.x) as usize;
+                counts[to..to + from.len()].copy_from_slice(&output.counts[from.clone()]);
+                if with_aovs {
+                    aov_pixels[to..to + from.len()].copy_from_slice(&output.aov_pixels[from]);
+                }
+            }
+            counters.merge(&output.counters);
+        }
+        let layer = |aov| {
+            let pixels = aov_pixels.iter().map(|pixel| pixel.value(aov)).collect();
+            Framebuffer::from_pixels(width, height, pixels)
+        };
+        let aovs = self
+            .settings
+            .aovs
+            .iter()
+            .map(|&aov| (aov, layer(aov)))
+            .collect();
+        let image = film.to_framebuffer();
+        let image = match &self.settings.denoise {
+            Some(denoise) => {
+                denoise.denoise(&image, Some(&layer(Aov::Albedo)), Some(&layer(Aov::Normal)))
+            }
+            None => image,
+        };
+        let stats = RenderStats {
+            counters,
+            samples: counts.iter().map(|&count| count as u64).sum(),
+            times: PhaseTimes {
+                render: render_start.elapsed(),
+                ..self.times
+            },
+        };
+        RenderOutput {
+            image,
+            sample_counts: counts,
+            aovs,
+            stats,
+        }
+    }
+
+    /// Renders the scene and saves it to `settings.path` with its passes, and the sample counts to
+    /// `settings.adaptive.sample_map` when set. The returned statistics include the time taken to
+    /// save.
+    pub fn render(&self) -> TracerResult<RenderOutput> {
+        let mut output = self.render_output();
+        let start = Instant::now();
+        let RenderOutput {
+            image,
+            sample_counts: counts,
+            aovs,
+            ..
+        } = &output;
+        let path = &self.settings.path;
+        let layered = !aovs.is_empty()
+            && !self.settings.aov_files
+            && matches!(OutputFormat::from_path(path), Ok(OutputFormat::Exr));
+        if layered {
+            aov::write_layered_exr(path, image, aovs)?;
+        } else {
+            image.save_with(path, &self.settings.display_transform())?;
+            for (aov, layer) in aovs {
+                layer.save(aov.path(path))?;
+            }
+        }
+        if let Some(adaptive) = &self.settings.adaptive {
+            if let Some(path) = &adaptive.sample_map {
+                let scale = 1.0 / adaptive.max_samples as f32;
+                let pixels = counts
+                    .iter()
+                    .map(|&count| Vec3A::splat(count as f32 * scale))
+                    .collect();
+                Framebuffer::from_pixels(image.width, image.height, pixels).save(path)?;
+            }
+        }
+        output.stats.times.output = start.elapsed();
+        Ok(output)
+    }
+
+    /// Renders the pixels of `tile` with a sampler of its own. Samples near the tile's edges are
+    /// also splatted into the pixels around it that the filter reaches.
+    fn render_tile(&self, tile: &Tile, filter: &Filter, with_aovs: bool) -> TileOutput {
+        let width = self.settings.image_width;
+        let height = self.settings.image_height();
+        let reach = filter.radius.ceil() as u32;
+        let x = tile.x.saturating_sub(reach);
+        let y = tile.y.saturating_sub(reach);
+        let mut film = Film::region(
+            x,
+            y,
+            (tile.x + tile.width + reach).min(width) - x,
+            (tile.y + tile.height + reach).min(height) - y,
+        );
+        // drops anything counted on this thread outside of a tile.
+        stats::take();
+        // stratified over one round, adaptive rounds are stratified on their own.
+        let mut sampler = self
+            .settings
+            .sampler
+            .build(self.settings.seed, self.settings.samples);
+        let pixels = (tile.width * tile.height) as usize;
+        let mut counts = Vec::with_capacity(pixels);
+        let mut aov_pixels = Vec::with_capacity(if with_aovs { pixels } else { 0 });
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                let mut aov_pixel = AovPixel::default();
+                let count = self.per_pixel(
+                    x,
+                    y,
+                    height,
+                    filter,
+                    &mut sampler,
+                    &mut film,
+                    with_aovs.then_some(&mut aov_pixel),
+                );
+                counts.push(count);
+                if with_aovs {
+                    aov_pixels.push(aov_pixel);
+                }
+            }
+        }
+        TileOutput {
+            tile: *tile,
+            film,
+            counts,
+            aov_pixels,
+            counters: stats::take(),
+        }
+    }
+
+    /// Samples one pixel into `film`, returning the number of samples taken. Adaptive renders
+    /// sample in rounds of `settings.samples` until the pixel converges. The first hits of the
+    /// samples are added to `aovs` when given.
+    #[allow(clippy::too_many_arguments)]
+    pub fn per_pixel(
+        &self,
+        x: u32,
+        y: u32,
+        height: u32,
+        filter: &Filter,
+        sampler: &mut dyn Sampler,
+        film: &mut Film,
+        mut aovs: Option<&mut AovPixel>,
+    ) -> u32 {
+        let round = self.settings.samples;
+        let max_samples = self.settings.max_samples();
+        let mut pixel = PixelVariance::default();
+        while pixel.count < max_samples {
+            let aovs = aovs.as_deref_mut();
+            let (position, color) = self.sample(x, y, height, pixel.count, sampler, aovs);
+            film.add_sample(position, color, filter);
+            pixel.add(color);
+            if pixel.count % round == 0 {
+                let converged = match &self.settings.adaptive {
+                    Some(adaptive) => pixel.converged(adaptive.threshold),
+                    None => true,
+                };
+                if converged {
+                    break;
+                }
+            }
+        }
+        pixel.count
+    }
+
+    /// Position on the image in pixels and color of sample `index` of pixel `(x, y)`, its first hit
+    /// is added to `aovs` when given.
+    pub fn sample(
+        &self,
+        x: u32,
+        y: u32,
+        height: u32,
+        index: u32,
+        sampler: &mut dyn Sampler,
+        aovs: Option<&mut AovPixel>,
+    ) -> (Vec2, Vec3A) {
+        sampler.start_pixel_sample(x, y, index);
+        let position = Vec2::new(x as f32, y as f32) + sampler.get_2d();
+        let u = position.x / (self.settings.image_width - 1) as f32;
+        let v = position.y / (height - 1) as f32;
+        let v = 1.0 - v; // flip height
+        let ray = self.camera.get_ray(u, v, sampler);
+        let mut first_hit = aovs
+            .is_some()
+            .then(|| AovSample::miss(self.background.value(ray.direction)));
+        let color = self.trace(ray, sampler, first_hit.as_mut());
+        if let (Some(aovs), Some(first_hit)) = (aovs, &first_hit) {
+            aovs.add(first_hit);
+        }
+        (position, color)
+    }
+
+    /// The aov sample of a camera `ray` that hit `hit`, or nothing.
+    fn aov_sample(&self, ray: &Ray, hit: Option<&HitRecord>) -> AovSample {
+        let Some(t) = hit else {
+            return AovSample::miss(self.background.value(ray.direction));
+        };
+        let material = &self.materials.materials[t.material_index];
+        let albedo = match material.albedo() {
+            Some(id) => self.materials.textures[id].value(t.u, t.v, t.position),
+            None => Vec3A::ZERO,
+        };
+        AovSample {
+            depth: t.root * ray.direction.length(),
+            normal: t.normal,
+            position: t.position,
+            uv: Vec2::new(t.u, t.v),
+            albedo,
+            material: Some(t.material_index),
+            object: Some(t.object_index),
+        }
+    }
+
+    /// Light arriving along the camera ray `ray`, traced bounce by bounce with the path's
+    /// throughput. Paths end after `settings.max_depth` rays, or randomly once they're
+    /// `settings.roulette_depth` bounces long. The light reflected towards the camera is clamped
+    /// to `settings.clamp`, lights seen directly are kept as they are. With roulette, clamping and
+    /// light sampling off, as they are by default, this is the recursive estimator of the book
+    /// unrolled and gives the same radiance.
+    pub fn ray_color(&self, ray: Ray, sampler: &mut dyn Sampler) -> Vec3A {
+        self.trace(ray, sampler, None)
+    }
+
+    /// `ray_color`, also recording what the camera ray hits in `first_hit` when given.
+    fn trace(
+        &self,
+        mut ray: Ray,
+        sampler: &mut dyn Sampler,
+        mut first_hit: Option<&mut AovSample>,
+    ) -> Vec3A {
+        let mut emitted_first = Vec3A::ZERO;
+        let mut reflected = Vec3A::ZERO;
+        let mut throughput = Vec3A::ONE;
+        // density the last bounce picked `ray` with when it also sampled `self.lights`, the
+        // light `ray` hits is then weighted against the light sample.
+        let mut bsdf_pdf = None;
+        for depth in 0..self.settings.max_depth as usize {
+            stats::count_ray(depth);
+            let hit = self.geometry.hit(&ray, 0.001, f32::INFINITY, sampler.rng());
+            if let Some(first_hit) = first_hit.take() {
+                *first_hit = self.aov_sample(&ray, hit.as_ref());
+            }
+            let Some(t) = hit else {
+                let mut background = self.background.value(ray.direction);
+                if let Some(bsdf_pdf) = bsdf_pdf {
+                    if self.lights.has_environment() {
+                        let light_pdf =
+                            self.lights
+                                .pdf_value(ray.origin, ray.direction, sampler.rng());
+                        background *= power_heuristic(bsdf_pdf, light_pdf);
+                    }
+                }
+                if depth == 0 {
+                    emitted_first = background;
+                } else {
+                    reflected += throughput * background;
+                }
+                break;
             };
-            if let Some(r) = material.scatter(ray, &t, &self.materials.textures) {
-                let attenuation = self.materials.textures[r.texture_index].value(t.u, t.v, t.position);
-                emitted + attenuation * self.ray_color(&r.scattered, depth - 1)
+            let material = &self.materials.materials[t.material_index];
+            let mut emitted = match material.emitted() {
+                Some(id) => self.materials.textures[id].value(t.u, t.v, t.position),
+                None => Vec3A::ZERO,
+            };
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                if self.lights.is_sampled(t.material_index) {
+                    let light_pdf = self
+                        .lights
+                        .pdf_value(ray.origin, ray.direction, sampler.rng());
+                    emitted *= power_heuristic(bsdf_pdf, light_pdf);
+                }
+            }
+            // glossy materials fail to sample directions that point into the surface, the light
+            // they reflect is still counted.
+            let direct = self.direct_light(&ray, &t, material, sampler);
+            if depth == 0 {
+                emitted_first = emitted;
             } else {
-                emitted
+                reflected += throughput * emitted;
             }
-        } else {
-            self.settings.background_color
+            reflected += throughput * direct.unwrap_or(Vec3A::ZERO);
+            let textures = &self.materials.textures;
+            let Some(sample) = material.sample(&ray, &t, textures, sampler) else {
+                break;
+            };
+            throughput *= sample.weight;
+            if matches!(self.settings.roulette_depth, Some(min) if depth + 1 >= min as usize) {
+                // paths carrying little light are likely to end, the survivors make up for them.
+                let survival = throughput.max_element().min(0.95);
+                if sampler.get_1d() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+            if throughput == Vec3A::ZERO {
+                break;
+            }
+            bsdf_pdf = direct.and(sample.pdf);
+            ray = sample.scattered;
+        }
+        let luminance = vec3::luminance(reflected);
+        match self.settings.clamp {
+            Some(max) if luminance > max => emitted_first + reflected * (max / luminance),
+            _ => emitted_first + reflected,
+        }
+    }
+
+    /// Light of `self.lights` scattered at the hit `t` along `ray`, estimated with one shadow ray
+    /// and weighted against sampling the material. `None` if there are no lights or the material
+    /// doesn't sample them.
+    fn direct_light(
+        &self,
+        ray: &Ray,
+        t: &HitRecord,
+        material: &MaterialType,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Vec3A> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let textures = &self.materials.textures;
+        let direction = self.lights.random(t.position, sampler.get_2d());
+        let bsdf_pdf = material.pdf(ray, t, direction, textures)?;
+        let light_pdf = self.lights.pdf_value(t.position, direction, sampler.rng());
+        if light_pdf <= 0.0 {
+            return Some(Vec3A::ZERO);
+        }
+        let scattered = material.eval(ray, t, direction, textures);
+        if scattered == Vec3A::ZERO {
+            return Some(Vec3A::ZERO);
+        }
+        stats::count_shadow_ray();
+        let shadow = Ray::new(t.position, direction, ray.time);
+        match self
+            .geometry
+            .hit(&shadow, 0.001, f32::INFINITY, sampler.rng())
+        {
+            // whatever is hit first along the direction has to be one of the lights.
+            Some(hit) if self.lights.is_sampled(hit.material_index) => {
+                let material = &self.materials.materials[hit.material_index];
+                let emitted = match material.emitted() {
+                    Some(id) => textures[id].value(hit.u, hit.v, hit.position),
+                    None => Vec3A::ZERO,
+                };
+                Some(scattered * emitted * power_heuristic(light_pdf, bsdf_pdf) / light_pdf)
+            }
+            None if self.lights.has_environment() => {
+                let background = self.background.value(direction);
+                Some(scattered * background * power_heuristic(light_pdf, bsdf_pdf) / light_pdf)
+            }
+            _ => Some(Vec3A::ZERO),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Camera looking down -z at a light covering the upper half of the view.
+    fn half_lit() -> RenderBuilder {
+        serde_json::from_str(
+            r#"{
+                "settings": {
+                    "image_width": 8,
+                    "aspect_ratio": 2.0,
+                    "samples": 4,
+                    "background_color": [0.25, 0.5, 1.0],
+                    "path": "unused.png"
+                },
+                "world": {
+                    "objects": [
+                        { "XyRect": { "x0": -100.0, "x1": 100.0, "y0": 0.0, "y1": 100.0, "k": 0.0, "material_index": 0 } }
+                    ]
+                },
+                "camera": {
+                    "look_from": [0.0, 0.0, 5.0],
+                    "look_at": [0.0, 0.0, 0.0],
+                    "vfov": 90.0,
+                    "aspect_ratio": 2.0,
+                    "aperture": 0.0
+                },
+                "materials": [{ "DiffuseLight": { "texture_index": 0 } }],
+                "textures": [{ "Solid": { "color": [2.0, 3.0, 4.0] } }]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    /// A light over a diffuse floor with a fuzzy metal and a glass sphere on it.
+    fn mixed() -> RenderBuilder {
+        serde_json::from_str(
+            r#"{
+                "settings": {
+                    "image_width": 16,
+                    "aspect_ratio": 1.0,
+                    "samples": 8,
+                    "max_depth": 8,
+                    "background_color": [0.1, 0.2, 0.3],
+                    "path": "unused.png"
+                },
+                "world": {
+                    "objects": [
+                        { "Sphere": { "center": [0.0, -100.5, 0.0], "radius": 100.0, "material": "floor" } },
+                        { "Sphere": { "center": [-0.6, 0.0, 0.0], "radius": 0.5, "material": "metal" } },
+                        { "Sphere": { "center": [0.6, 0.0, 0.0], "radius": 0.5, "material": "glass" } },
+                        { "XzRect": { "x0": -1.0, "x1": 1.0, "z0": -1.0, "z1": 1.0, "k": 2.0, "material": "light" } }
+                    ]
+                },
+                "camera": {
+                    "look_from": [0.0, 0.5, 3.0],
+                    "look_at": [0.0, 0.0, 0.0],
+                    "vfov": 60.0,
+                    "aspect_ratio": 1.0,
+                    "aperture": 0.1
+                },
+                "materials": [
+                    { "name": "floor", "Lambertian": { "texture": 0 } },
+                    { "name": "metal", "Metal": { "texture": 1, "fuzz": 0.3 } },
+                    { "name": "glass", "Dieletric": { "ref_idx": 1.5, "texture": 2 } },
+                    { "name": "light", "DiffuseLight": { "texture": 3 } }
+                ],
+                "textures": [
+                    { "Solid": { "color": [0.5, 0.6, 0.7] } },
+                    { "Solid": { "color": [0.9, 0.8, 0.5] } },
+                    { "Solid": { "color": [1.0, 1.0, 1.0] } },
+                    { "Solid": { "color": [4.0, 4.0, 4.0] } }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    /// The recursive estimator the renderer started out with.
+    fn recursive_color(
+        renderer: &Renderer,
+        ray: &Ray,
+        depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> Vec3A {
+        if depth == 0 {
+            return Vec3A::ZERO;
         }
+        let textures = &renderer.materials.textures;
+        let Some(t) = renderer
+            .geometry
+            .hit(ray, 0.001, f32::INFINITY, sampler.rng())
+        else {
+            return renderer.background.value(ray.direction);
+        };
+        let material = &renderer.materials.materials[t.material_index];
+        let emitted = match material.emitted() {
+            Some(id) => textures[id].value(t.u, t.v, t.position),
+            None => Vec3A::ZERO,
+        };
+        match material.scatter(ray, &t, textures, sampler) {
+            Some(r) => {
+                let attenuation = textures[r.texture_index].value(t.u, t.v, t.position);
+                emitted + attenuation * recursive_color(renderer, &r.scattered, depth - 1, sampler)
+            }
+            None => emitted,
+        }
+    }
+
+    #[test]
+    fn default_paths_match_the_recursive_estimator() {
+        let renderer = mixed().build().unwrap();
+        let (width, height) = (16, 16);
+        let mut sampler = renderer
+            .settings
+            .sampler
+            .build(0, renderer.settings.samples);
+        let mut lit = 0;
+        for y in 0..height {
+            for x in 0..width {
+                for index in 0..renderer.settings.samples {
+                    let (_, color) = renderer.sample(x, y, height, index, &mut sampler, None);
+                    // the same camera ray and random numbers again.
+                    sampler.start_pixel_sample(x, y, index);
+                    let position = Vec2::new(x as f32, y as f32) + sampler.get_2d();
+                    let u = position.x / (width - 1) as f32;
+                    let v = 1.0 - position.y / (height - 1) as f32;
+                    let ray = renderer.camera.get_ray(u, v, &mut sampler);
+                    let depth = renderer.settings.max_depth;
+                    let expected = recursive_color(&renderer, &ray, depth, &mut sampler);
+                    assert!(
+                        (color - expected).abs().max_element()
+                            <= 1e-5 * expected.max_element().max(1.0),
+                        "{color} is not {expected} at ({x}, {y}) sample {index}"
+                    );
+                    lit += (expected.max_element() > 0.0) as u32;
+                }
+            }
+        }
+        assert!(lit > 0);
+    }
+
+    #[test]
+    fn renders_linear_rows_from_the_top() {
+        let image = half_lit().build().unwrap().render_image();
+        assert_eq!((image.width, image.height), (8, 4));
+        assert_eq!(image.pixels.len(), 32);
+        for x in 0..image.width {
+            // light and background come through without any display transform.
+            assert_eq!(image.get(x, 0), Vec3A::new(2.0, 3.0, 4.0));
+            assert_eq!(image.get(x, 3), Vec3A::new(0.25, 0.5, 1.0));
+        }
+    }
+
+    #[test]
+    fn aovs_come_from_the_first_hit_without_changing_the_image() {
+        let image = half_lit().build().unwrap().render_image();
+        let mut builder = half_lit();
+        builder.settings.aovs = vec![Aov::Depth, Aov::Normal, Aov::MaterialId, Aov::Albedo];
+        let output = builder.build().unwrap().render_output();
+        assert_eq!(output.image.pixels, image.pixels);
+
+        let layer = |aov: Aov| &output.aovs.iter().find(|(a, _)| *a == aov).unwrap().1;
+        for x in 0..image.width {
+            // the light is 5 units in front of the camera and less than 10 to the sides.
+            let depth = layer(Aov::Depth).get(x, 0).x;
+            assert!((5.0..15.0).contains(&depth), "{depth}");
+            assert_eq!(layer(Aov::Normal).get(x, 0), Vec3A::Z);
+            assert_eq!(layer(Aov::MaterialId).get(x, 0), Vec3A::ZERO);
+            assert_eq!(layer(Aov::Albedo).get(x, 0), Vec3A::ZERO);
+
+            assert_eq!(layer(Aov::Depth).get(x, 3), Vec3A::ZERO);
+            assert_eq!(layer(Aov::MaterialId).get(x, 3), Vec3A::splat(-1.0));
+            assert_eq!(layer(Aov::Albedo).get(x, 3), Vec3A::new(0.25, 0.5, 1.0));
+        }
+    }
+
+    #[test]
+    fn adaptive_rounds_stop_on_converged_pixels() {
+        let mut builder = half_lit();
+        // every first round has samples on both sides of the edge.
+        builder.settings.sampler = SamplerType::Stratified;
+        builder.settings.adaptive = Some(AdaptiveSettings {
+            max_samples: 64,
+            ..AdaptiveSettings::default()
+        });
+        let counts = builder.build().unwrap().render_output().sample_counts;
+        let row = |y: usize| &counts[y * 8..(y + 1) * 8];
+        // flat rows converge after the first round, the row on the light's edge never does.
+        assert!(row(0).iter().all(|&count| count == 4), "{counts:?}");
+        assert!(row(1).iter().all(|&count| count == 64), "{counts:?}");
+        assert!(row(3).iter().all(|&count| count == 4), "{counts:?}");
     }
 }
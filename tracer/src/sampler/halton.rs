@@ -0,0 +1,68 @@
+use crate::random::{self, TracerRng};
+use crate::sampler::{permutation_element, SampleState, Sampler, ONE_MINUS_EPSILON};
+use glam::Vec2;
+use rand::Rng;
+
+/// Bases of the Halton dimensions, later dimensions use random values.
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// Halton points, each pixel and dimension gets its own scrambling of the digits.
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: SampleState::new(seed),
+        }
+    }
+}
+
+/// Radical inverse of `index` in `base` with every digit permuted depending on the digits before
+/// it, the base `b` version of Owen scrambling.
+fn scrambled_radical_inverse(base: u32, mut index: u64, hash: u64) -> f32 {
+    let inv_base = 1.0 / base as f32;
+    let mut inv_base_m = 1.0;
+    let mut reversed_digits = 0u64;
+    // stop once further digits no longer change the f32 result.
+    while 1.0 - (base - 1) as f32 * inv_base_m < 1.0 {
+        let next = index / u64::from(base);
+        let digit = (index - next * u64::from(base)) as u32;
+        let digit_hash = random::mix(hash ^ reversed_digits) as u32;
+        let digit = permutation_element(digit, base, digit_hash);
+        reversed_digits = reversed_digits * u64::from(base) + u64::from(digit);
+        inv_base_m *= inv_base;
+        index = next;
+    }
+    (inv_base_m * reversed_digits as f32).min(ONE_MINUS_EPSILON)
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dimension = self.state.dimension as usize;
+        let hash = self.state.next_dimension();
+        match PRIMES.get(dimension) {
+            Some(&base) => scrambled_radical_inverse(base, u64::from(self.state.index), hash),
+            None => self.state.rng.gen(),
+        }
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        Vec2::new(self.get_1d(), self.get_1d())
+    }
+
+    fn rng(&mut self) -> &mut TracerRng {
+        &mut self.state.rng
+    }
+}
@@ -0,0 +1,36 @@
+use crate::random::TracerRng;
+use crate::sampler::{SampleState, Sampler};
+use glam::Vec2;
+use rand::Rng;
+
+/// Uniform random values from the sample's own generator.
+#[derive(Debug, Clone)]
+pub struct IndependentSampler {
+    state: SampleState,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: SampleState::new(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.state.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        Vec2::new(self.state.rng.gen(), self.state.rng.gen())
+    }
+
+    fn rng(&mut self) -> &mut TracerRng {
+        &mut self.state.rng
+    }
+}
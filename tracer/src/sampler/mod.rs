@@ -0,0 +1,314 @@
+pub mod halton;
+pub mod independent;
+pub mod sobol;
+pub mod stratified;
+
+use crate::random::{self, TracerRng};
+use crate::sampler::halton::HaltonSampler;
+use crate::sampler::independent::IndependentSampler;
+use crate::sampler::sobol::SobolSampler;
+use crate::sampler::stratified::StratifiedSampler;
+use glam::Vec2;
+
+use serde::{Deserialize, Serialize};
+
+/// Largest f32 below one.
+pub const ONE_MINUS_EPSILON: f32 = 0.999_999_94;
+
+/// Source of the values used for one sample of a pixel. Every `get_1d` or `get_2d` call moves to
+/// the next dimension, the camera takes the first ones and every bounce the ones after that.
+pub trait Sampler {
+    /// Starts sample `index` of pixel `(x, y)` at the first dimension.
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32);
+    fn get_1d(&mut self) -> f32;
+    fn get_2d(&mut self) -> Vec2;
+    /// Generator of the current sample for choices that can't use a dimension, like medium hits.
+    fn rng(&mut self) -> &mut TracerRng;
+}
+
+/// Which sampler the renderer uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum SamplerType {
+    /// Uniform random values.
+    #[default]
+    Independent,
+    /// Jittered strata, shuffled per dimension.
+    Stratified,
+    /// Halton points with scrambled digits.
+    Halton,
+    /// Sobol points with Owen scrambling.
+    Sobol,
+}
+
+impl SamplerType {
+    /// Sampler for renders with `samples` samples per pixel.
+    pub fn build(self, seed: u64, samples: u32) -> PixelSampler {
+        match self {
+            SamplerType::Independent => PixelSampler::Independent(IndependentSampler::new(seed)),
+            SamplerType::Stratified => {
+                PixelSampler::Stratified(StratifiedSampler::new(seed, samples))
+            }
+            SamplerType::Halton => PixelSampler::Halton(HaltonSampler::new(seed)),
+            SamplerType::Sobol => PixelSampler::Sobol(SobolSampler::new(seed, samples)),
+        }
+    }
+}
+
+pub enum PixelSampler {
+    Independent(IndependentSampler),
+    Stratified(StratifiedSampler),
+    Halton(HaltonSampler),
+    Sobol(SobolSampler),
+}
+
+impl Sampler for PixelSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        match self {
+            PixelSampler::Independent(s) => s.start_pixel_sample(x, y, index),
+            PixelSampler::Stratified(s) => s.start_pixel_sample(x, y, index),
+            PixelSampler::Halton(s) => s.start_pixel_sample(x, y, index),
+            PixelSampler::Sobol(s) => s.start_pixel_sample(x, y, index),
+        }
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        match self {
+            PixelSampler::Independent(s) => s.get_1d(),
+            PixelSampler::Stratified(s) => s.get_1d(),
+            PixelSampler::Halton(s) => s.get_1d(),
+            PixelSampler::Sobol(s) => s.get_1d(),
+        }
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        match self {
+            PixelSampler::Independent(s) => s.get_2d(),
+            PixelSampler::Stratified(s) => s.get_2d(),
+            PixelSampler::Halton(s) => s.get_2d(),
+            PixelSampler::Sobol(s) => s.get_2d(),
+        }
+    }
+
+    fn rng(&mut self) -> &mut TracerRng {
+        match self {
+            PixelSampler::Independent(s) => s.rng(),
+            PixelSampler::Stratified(s) => s.rng(),
+            PixelSampler::Halton(s) => s.rng(),
+            PixelSampler::Sobol(s) => s.rng(),
+        }
+    }
+}
+
+/// Position and progress of the current sample, shared by the samplers.
+#[derive(Debug, Clone)]
+pub struct SampleState {
+    pub seed: u64,
+    pub pixel: u64,
+    pub index: u32,
+    pub dimension: u32,
+    pub rng: TracerRng,
+}
+
+impl SampleState {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+            rng: random::seeded_rng(seed),
+        }
+    }
+
+    pub fn start(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = random::pixel_hash(self.seed, x, y);
+        self.index = index;
+        self.dimension = 0;
+        self.rng = random::sample_rng(self.seed, x, y, index);
+    }
+
+    /// Hash of the next dimension of this pixel, the same for every sample of the pixel.
+    pub fn next_dimension(&mut self) -> u64 {
+        let hash = random::mix(self.pixel ^ u64::from(self.dimension));
+        self.dimension += 1;
+        hash
+    }
+}
+
+/// Maps the bits of `value` to `[0, 1)`.
+pub fn to_unit_float(value: u32) -> f32 {
+    (value >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+}
+
+/// Element `i` of a random permutation of `0..length` selected by `seed` (Kensler, "Correlated
+/// Multi-Jittered Sampling").
+pub fn permutation_element(mut i: u32, length: u32, seed: u32) -> u32 {
+    let mut w = length - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        if i < length {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % length
+}
+
+/// Base 2 Owen scrambling of the bits of `value`, higher bits flip lower ones but never the other
+/// way around (Laine and Karras hash).
+pub fn owen_scramble(value: u32, seed: u32) -> u32 {
+    let mut v = value.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20_adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x0552_6c56);
+    v ^= v.wrapping_mul(0x53a2_2864);
+    v.reverse_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [SamplerType; 4] = [
+        SamplerType::Independent,
+        SamplerType::Stratified,
+        SamplerType::Halton,
+        SamplerType::Sobol,
+    ];
+
+    /// `dimensions` 1d and 2d values of every sample of pixel `(x, y)`, by dimension.
+    fn values(
+        sampler: SamplerType,
+        samples: u32,
+        x: u32,
+        y: u32,
+    ) -> (Vec<Vec<f32>>, Vec<Vec<Vec2>>) {
+        let mut sampler = sampler.build(5, samples);
+        let (mut ones, mut twos) = (vec![Vec::new(); 8], vec![Vec::new(); 8]);
+        for index in 0..samples {
+            sampler.start_pixel_sample(x, y, index);
+            for dimension in 0..8 {
+                ones[dimension].push(sampler.get_1d());
+                twos[dimension].push(sampler.get_2d());
+            }
+        }
+        (ones, twos)
+    }
+
+    #[test]
+    fn values_are_in_the_unit_interval() {
+        for sampler in ALL {
+            for samples in [1, 7, 16] {
+                let (ones, twos) = values(sampler, samples, 3, 9);
+                assert!(ones.iter().flatten().all(|v| (0.0..1.0).contains(v)));
+                assert!(twos
+                    .iter()
+                    .flatten()
+                    .all(|v| (0.0..1.0).contains(&v.x) && (0.0..1.0).contains(&v.y)));
+            }
+        }
+    }
+
+    #[test]
+    fn samples_are_repeatable() {
+        for sampler in ALL {
+            assert_eq!(values(sampler, 8, 1, 2), values(sampler, 8, 1, 2));
+            assert_ne!(values(sampler, 8, 1, 2), values(sampler, 8, 2, 1));
+        }
+    }
+
+    /// Whether no two of the cells are the same, `count` is the number of cells.
+    fn one_per_cell(cells: impl IntoIterator<Item = u32>, count: u32) -> bool {
+        let mut hit = vec![false; count as usize];
+        cells
+            .into_iter()
+            .all(|cell| !std::mem::replace(&mut hit[cell as usize], true))
+    }
+
+    /// Whether every value lands in its own of `count` equal strata.
+    fn stratified(values: &[f32], count: u32) -> bool {
+        one_per_cell(values.iter().map(|v| (v * count as f32) as u32), count)
+    }
+
+    /// Whether every point lands in its own cell of a `columns` by `rows` grid.
+    fn stratified_2d(points: &[Vec2], columns: u32, rows: u32) -> bool {
+        let cells = points
+            .iter()
+            .map(|p| (p.y * rows as f32) as u32 * columns + (p.x * columns as f32) as u32);
+        one_per_cell(cells, columns * rows)
+    }
+
+    #[test]
+    fn stratified_sampler_fills_every_stratum() {
+        for (samples, columns, rows) in [(16, 4, 4), (12, 4, 3)] {
+            let (ones, twos) = values(SamplerType::Stratified, samples, 4, 2);
+            for dimension in 0..8 {
+                assert!(stratified(&ones[dimension], samples));
+                assert!(stratified_2d(&twos[dimension], columns, rows));
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_sampler_is_a_net() {
+        let (ones, twos) = values(SamplerType::Sobol, 16, 4, 2);
+        for dimension in 0..8 {
+            assert!(stratified(&ones[dimension], 16));
+            // every elementary interval of area 1 / 16 holds one point.
+            for (columns, rows) in [(16, 1), (8, 2), (4, 4), (2, 8), (1, 16)] {
+                assert!(stratified_2d(&twos[dimension], columns, rows));
+            }
+        }
+    }
+
+    #[test]
+    fn later_rounds_are_stratified_on_their_own() {
+        for sampler_type in [SamplerType::Stratified, SamplerType::Sobol] {
+            let mut sampler = sampler_type.build(5, 16);
+            for round in 0..3 {
+                let (mut ones, mut twos) = (Vec::new(), Vec::new());
+                for index in round * 16..(round + 1) * 16 {
+                    sampler.start_pixel_sample(6, 1, index);
+                    ones.push(sampler.get_1d());
+                    twos.push(sampler.get_2d());
+                }
+                assert!(stratified(&ones, 16), "{sampler_type:?} round {round}");
+                assert!(stratified_2d(&twos, 4, 4), "{sampler_type:?} round {round}");
+            }
+        }
+    }
+
+    #[test]
+    fn permutation_elements_are_a_permutation() {
+        for length in [1, 5, 16, 100] {
+            for seed in [0, 7, 0xdead_beef] {
+                let mut seen = (0..length)
+                    .map(|i| permutation_element(i, length, seed))
+                    .collect::<Vec<u32>>();
+                seen.sort_unstable();
+                assert_eq!(seen, (0..length).collect::<Vec<u32>>());
+            }
+        }
+    }
+}
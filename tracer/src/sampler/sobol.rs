@@ -0,0 +1,70 @@
+use crate::random::TracerRng;
+use crate::sampler::{owen_scramble, permutation_element, to_unit_float, SampleState, Sampler};
+use glam::Vec2;
+
+/// The first two dimensions of the Sobol sequence, padded to any number of dimensions by giving
+/// every dimension its own Owen scrambling and sample order. Converges best with a power of two
+/// samples. Samples are shuffled within blocks of `samples`, each block is a net of its own so
+/// every round of an adaptive render is stratified.
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    state: SampleState,
+    samples: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64, samples: u32) -> Self {
+        Self {
+            state: SampleState::new(seed),
+            samples: samples.max(1).next_power_of_two(),
+        }
+    }
+
+    /// Index of the current sample in the dimension with `hash`.
+    fn shuffled_index(&self, hash: u64) -> u32 {
+        let block = self.state.index / self.samples;
+        let position = self.state.index % self.samples;
+        block * self.samples
+            + permutation_element(position, self.samples, (hash ^ u64::from(block)) as u32)
+    }
+}
+
+/// Second dimension of the Sobol sequence, the first is `index.reverse_bits()`.
+fn sobol_second(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1u32 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let hash = self.state.next_dimension();
+        let index = self.shuffled_index(hash);
+        to_unit_float(owen_scramble(index.reverse_bits(), (hash >> 32) as u32))
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        let hash = self.state.next_dimension();
+        let index = self.shuffled_index(hash);
+        let seed = (hash >> 32) as u32;
+        Vec2::new(
+            to_unit_float(owen_scramble(index.reverse_bits(), seed)),
+            to_unit_float(owen_scramble(sobol_second(index), seed ^ 0x9e37_79b9)),
+        )
+    }
+
+    fn rng(&mut self) -> &mut TracerRng {
+        &mut self.state.rng
+    }
+}
@@ -0,0 +1,63 @@
+use crate::random::TracerRng;
+use crate::sampler::{permutation_element, SampleState, Sampler, ONE_MINUS_EPSILON};
+use glam::Vec2;
+use rand::Rng;
+
+/// Splits every dimension into one stratum per sample and jitters inside it. 2d dimensions use
+/// a grid of at least `samples` cells. Strata are visited in a different order per pixel and
+/// dimension so dimensions don't correlate. Samples past `samples` start another pass over the
+/// strata in a new order, so every round of an adaptive render is stratified on its own.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    state: SampleState,
+    samples: u32,
+    columns: u32,
+    rows: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, samples: u32) -> Self {
+        let samples = samples.max(1);
+        let columns = (samples as f32).sqrt().ceil() as u32;
+        Self {
+            state: SampleState::new(seed),
+            samples,
+            columns,
+            rows: (samples as f32 / columns as f32).ceil() as u32,
+        }
+    }
+
+    fn stratum(&mut self, count: u32) -> u32 {
+        let hash = self.state.next_dimension();
+        let pass = u64::from(self.state.index / self.samples);
+        let hash = hash ^ pass.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        permutation_element(self.state.index % self.samples, count, hash as u32)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let stratum = self.stratum(self.samples);
+        let jitter = self.state.rng.gen::<f32>();
+        ((stratum as f32 + jitter) / self.samples as f32).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        let stratum = self.stratum(self.columns * self.rows);
+        let jitter = Vec2::new(self.state.rng.gen(), self.state.rng.gen());
+        let cell = Vec2::new(
+            (stratum % self.columns) as f32,
+            (stratum / self.columns) as f32,
+        );
+        ((cell + jitter) / Vec2::new(self.columns as f32, self.rows as f32))
+            .min(Vec2::splat(ONE_MINUS_EPSILON))
+    }
+
+    fn rng(&mut self) -> &mut TracerRng {
+        &mut self.state.rng
+    }
+}
@@ -0,0 +1,234 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::time::Duration;
+
+/// Primitive kinds counted by `RayCounters::intersection_tests`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    Sphere,
+    MovingSphere,
+    Rect,
+    Triangle,
+    Medium,
+}
+
+impl Primitive {
+    pub const ALL: [Primitive; 5] = [
+        Primitive::Sphere,
+        Primitive::MovingSphere,
+        Primitive::Rect,
+        Primitive::Triangle,
+        Primitive::Medium,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Primitive::Sphere => "sphere",
+            Primitive::MovingSphere => "moving sphere",
+            Primitive::Rect => "rect",
+            Primitive::Triangle => "triangle",
+            Primitive::Medium => "medium",
+        }
+    }
+}
+
+// every thread counts into its own counters, render tasks take them when they finish. The
+// counters don't need dropping, which keeps them cheap to reach.
+thread_local! {
+    static COUNTERS: Counters = const { Counters::new() };
+    static RAYS_PER_DEPTH: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Counters {
+    shadow_rays: Cell<u64>,
+    intersection_tests: [Cell<u64>; Primitive::ALL.len()],
+    bvh_node_visits: Cell<u64>,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Self {
+            shadow_rays: Cell::new(0),
+            intersection_tests: [
+                Cell::new(0),
+                Cell::new(0),
+                Cell::new(0),
+                Cell::new(0),
+                Cell::new(0),
+            ],
+            bvh_node_visits: Cell::new(0),
+        }
+    }
+}
+
+fn increment(counter: &Cell<u64>) {
+    counter.set(counter.get() + 1);
+}
+
+/// Counts a ray traced at `depth` bounces from the camera, zero for camera rays.
+pub fn count_ray(depth: usize) {
+    RAYS_PER_DEPTH.with(|rays| {
+        let mut rays = rays.borrow_mut();
+        if rays.len() <= depth {
+            rays.resize(depth + 1, 0);
+        }
+        rays[depth] += 1;
+    })
+}
+
+pub fn count_shadow_ray() {
+    COUNTERS.with(|counters| increment(&counters.shadow_rays))
+}
+
+pub fn count_intersection(primitive: Primitive) {
+    COUNTERS.with(|counters| increment(&counters.intersection_tests[primitive as usize]))
+}
+
+pub fn count_bvh_visit() {
+    COUNTERS.with(|counters| increment(&counters.bvh_node_visits))
+}
+
+/// Takes the counts of the current thread, resetting them.
+pub fn take() -> RayCounters {
+    COUNTERS.with(|counters| RayCounters {
+        rays_per_depth: RAYS_PER_DEPTH.with(RefCell::take),
+        shadow_rays: counters.shadow_rays.take(),
+        intersection_tests: Primitive::ALL.map(|p| counters.intersection_tests[p as usize].take()),
+        bvh_node_visits: counters.bvh_node_visits.take(),
+    })
+}
+
+/// Work done while tracing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RayCounters {
+    /// Rays traced per bounce, the first entry are the camera rays.
+    pub rays_per_depth: Vec<u64>,
+    pub shadow_rays: u64,
+    /// Intersection tests per primitive kind, indexed by `Primitive`.
+    pub intersection_tests: [u64; Primitive::ALL.len()],
+    pub bvh_node_visits: u64,
+}
+
+impl RayCounters {
+    pub fn merge(&mut self, other: &RayCounters) {
+        if self.rays_per_depth.len() < other.rays_per_depth.len() {
+            self.rays_per_depth.resize(other.rays_per_depth.len(), 0);
+        }
+        for (rays, other) in self.rays_per_depth.iter_mut().zip(&other.rays_per_depth) {
+            *rays += other;
+        }
+        self.shadow_rays += other.shadow_rays;
+        for (tests, other) in self
+            .intersection_tests
+            .iter_mut()
+            .zip(&other.intersection_tests)
+        {
+            *tests += other;
+        }
+        self.bvh_node_visits += other.bvh_node_visits;
+    }
+
+    pub fn primary_rays(&self) -> u64 {
+        self.rays_per_depth.first().copied().unwrap_or(0)
+    }
+
+    pub fn secondary_rays(&self) -> u64 {
+        self.rays_per_depth.iter().skip(1).sum()
+    }
+
+    pub fn total_rays(&self) -> u64 {
+        self.rays_per_depth.iter().sum::<u64>() + self.shadow_rays
+    }
+}
+
+/// Wall time of every phase of a render.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhaseTimes {
+    /// Loading meshes and textures, resolving names and building the geometry.
+    pub scene: Duration,
+    pub bvh: Duration,
+    pub render: Duration,
+    /// Saving the images, zero if nothing was saved.
+    pub output: Duration,
+}
+
+/// Statistics of a finished render.
+#[derive(Debug, Clone, Default)]
+pub struct RenderStats {
+    pub counters: RayCounters,
+    pub samples: u64,
+    pub times: PhaseTimes,
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counters = &self.counters;
+        let render_seconds = self.times.render.as_secs_f64();
+        writeln!(f, "samples:            {}", self.samples)?;
+        write!(f, "rays:               {}", counters.total_rays())?;
+        if render_seconds > 0.0 {
+            let rate = counters.total_rays() as f64 / render_seconds / 1e6;
+            write!(f, " ({rate:.2} M/s)")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "  primary:          {}", counters.primary_rays())?;
+        writeln!(f, "  secondary:        {}", counters.secondary_rays())?;
+        writeln!(f, "  shadow:           {}", counters.shadow_rays)?;
+        for (depth, rays) in counters.rays_per_depth.iter().enumerate() {
+            writeln!(f, "  {:<17} {rays}", format!("depth {depth}:"))?;
+        }
+        writeln!(f, "intersection tests:")?;
+        for primitive in Primitive::ALL {
+            let tests = counters.intersection_tests[primitive as usize];
+            if tests > 0 {
+                writeln!(f, "  {:<17} {tests}", format!("{}:", primitive.name()))?;
+            }
+        }
+        writeln!(f, "bvh node visits:    {}", counters.bvh_node_visits)?;
+        writeln!(f, "scene build:        {:.2?}", self.times.scene)?;
+        writeln!(f, "bvh build:          {:.2?}", self.times.bvh)?;
+        writeln!(f, "render:             {:.2?}", self.times.render)?;
+        write!(f, "output:             {:.2?}", self.times.output)
+    }
+}
+
+/// Snapshot of a render in flight, passed to the progress callback.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    pub pixels: u64,
+    pub total_pixels: u64,
+    pub samples: u64,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Finished fraction of the image, between zero and one.
+    pub fn fraction(&self) -> f64 {
+        self.pixels as f64 / self.total_pixels.max(1) as f64
+    }
+
+    /// Time left if the remaining pixels render as fast as the finished ones.
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction();
+        (fraction > 0.0).then(|| self.elapsed.mul_f64((1.0 - fraction) / fraction))
+    }
+
+    pub fn samples_per_second(&self) -> f64 {
+        self.samples as f64 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:5.1}% elapsed {:.1?}",
+            self.fraction() * 100.0,
+            self.elapsed
+        )?;
+        if let Some(eta) = self.eta() {
+            write!(f, " eta {:.1?}", eta)?;
+        }
+        write!(f, " {:.0} samples/s", self.samples_per_second())
+    }
+}
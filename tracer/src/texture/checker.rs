@@ -3,6 +3,7 @@ use glam::Vec3A;
 use std::boxed::Box;
 
 use crate::error::{TracerError, TracerResult};
+use crate::validation::Validator;
 use serde::{Deserialize, Serialize};
 
 fn default_scale() -> f32 {
@@ -21,6 +22,11 @@ impl CheckerBuilder {
     pub fn new(odd: Box<TextureFile>, even: Box<TextureFile>, scale: f32) -> Self {
         CheckerBuilder { odd, even, scale }
     }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        self.odd.validate(&format!("{location}.odd"), validator);
+        self.even.validate(&format!("{location}.even"), validator);
+    }
 }
 
 impl TryInto<TexturesType> for CheckerBuilder {
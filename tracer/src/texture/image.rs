@@ -1,5 +1,7 @@
 use crate::error::{TracerError, TracerResult};
+use crate::framebuffer;
 use crate::texture::{Texture, TexturesType};
+use crate::validation::Validator;
 use glam::Vec3A;
 use image;
 use image::io::Reader;
@@ -16,6 +18,10 @@ impl ImageBuilder {
     pub fn new(path: String) -> Self {
         ImageBuilder { path }
     }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        validator.file(format!("{location}.path"), &self.path);
+    }
 }
 
 impl TryInto<TexturesType> for ImageBuilder {
@@ -35,7 +41,16 @@ pub struct Image {
 const CHANNELS: u32 = 3;
 impl Image {
     pub fn new(path: &str) -> TracerResult<Self> {
-        let image = Reader::open(path)?.decode()?;
+        let image = Reader::open(path)
+            .map_err(|source| TracerError::FileError {
+                path: path.into(),
+                source,
+            })?
+            .decode()
+            .map_err(|source| TracerError::ImageFileError {
+                path: path.into(),
+                source,
+            })?;
         let (width, height) = image.dimensions();
         // image.get_pixel(0, 0);
         // let image = image.to_rgb32f().into_raw(); // todo use this see below
@@ -48,10 +63,19 @@ impl Image {
     }
 }
 
+impl Image {
+    /// Linear colors of the pixels in row major order from the top left. Float images, like hdr
+    /// and exr, are already linear, the others are decoded from sRGB.
+    pub fn linear_pixels(&self) -> Vec<Vec3A> {
+        framebuffer::linear_pixels(&self.image)
+    }
+}
+
 impl Texture for Image {
     fn value(&self, u: f32, v: f32, _p: Vec3A) -> Vec3A {
         let uu = u.clamp(0.0, 1.0);
-        let vv = (-1.0 * v).clamp(0.0, 1.0);
+        // v runs bottom to top, image rows top to bottom.
+        let vv = (1.0 - v).clamp(0.0, 1.0);
         let i = (uu * self.width as f32) as u32;
         let j = (vv * self.height as f32) as u32;
         let ii = i.min(self.width - 1);
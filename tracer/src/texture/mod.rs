@@ -14,6 +14,7 @@ pub trait Texture {
     fn value(&self, u: f32, v: f32, p: Vec3A) -> Vec3A;
 }
 use crate::error::{TracerError, TracerResult};
+use crate::validation::Validator;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -24,6 +25,17 @@ pub enum TextureFile {
     Solid(Solid),
 }
 
+impl TextureFile {
+    /// Records every problem with this texture, `location` is its json path.
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        match self {
+            TextureFile::Checker(c) => c.validate(&format!("{location}.Checker"), validator),
+            TextureFile::Image(i) => i.validate(&format!("{location}.Image"), validator),
+            TextureFile::Noise(_) | TextureFile::Solid(_) => {}
+        }
+    }
+}
+
 impl TryInto<TexturesType> for TextureFile {
     type Error = TracerError;
 
@@ -50,10 +62,6 @@ impl TryInto<Box<TexturesType>> for Box<TextureFile> {
     }
 }
 
-struct TextureType {
-    textures: Vec<TextureType>,
-}
-
 pub enum TexturesType {
     Checker(Checker),
     Image(Image),
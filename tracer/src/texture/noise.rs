@@ -13,11 +13,14 @@ fn default_scale() -> f32 {
 pub struct NoiseBuilder {
     #[serde(default = "default_scale")]
     scale: f32,
+    /// Seed of the noise pattern, kept apart from the render seed so the pattern stays put.
+    #[serde(default)]
+    seed: u64,
 }
 
 impl NoiseBuilder {
     pub fn new(scale: f32) -> Self {
-        NoiseBuilder { scale }
+        NoiseBuilder { scale, seed: 0 }
     }
 }
 
@@ -25,7 +28,7 @@ impl TryInto<TexturesType> for NoiseBuilder {
     type Error = TracerError;
 
     fn try_into(self) -> TracerResult<TexturesType> {
-        Ok(TexturesType::Noise(Noise::new(self.scale)))
+        Ok(TexturesType::Noise(Noise::new(self.scale, self.seed)))
     }
 }
 
@@ -35,10 +38,10 @@ pub struct Noise {
 }
 
 impl Noise {
-    pub fn new(scale: f32) -> Self {
+    pub fn new(scale: f32, seed: u64) -> Self {
         Noise {
             scale,
-            noise: Perlin::new(),
+            noise: Perlin::new(seed),
         }
     }
 }
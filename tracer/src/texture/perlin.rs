@@ -1,3 +1,4 @@
+use crate::random::{self, TracerRng};
 use glam::Vec3A;
 use rand::Rng;
 static POINT_COUNT: usize = 256;
@@ -9,38 +10,43 @@ pub struct Perlin {
     pub perm_z: Vec<i32>,
 }
 
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
 impl Perlin {
-    pub fn new() -> Self {
+    /// Noise with gradients and permutations drawn from `seed`.
+    pub fn new(seed: u64) -> Self {
         let mut ran = Vec::with_capacity(POINT_COUNT);
-        let mut rng = rand::thread_rng();
+        let mut rng = random::seeded_rng(seed);
         for _ in 0..256 {
             ran.push(rng.gen::<Vec3A>().normalize());
         }
 
         Self {
             ran,
-            perm_x: Self::perlin_generate_perm(),
-            perm_y: Self::perlin_generate_perm(),
-            perm_z: Self::perlin_generate_perm(),
+            perm_x: Self::perlin_generate_perm(&mut rng),
+            perm_y: Self::perlin_generate_perm(&mut rng),
+            perm_z: Self::perlin_generate_perm(&mut rng),
         }
     }
 
-    fn perlin_generate_perm() -> Vec<i32> {
+    fn perlin_generate_perm(rng: &mut TracerRng) -> Vec<i32> {
         let mut p = Vec::with_capacity(POINT_COUNT);
         for i in 0..256 {
             p.push(i);
         }
-        Self::permute(&mut p);
+        Self::permute(&mut p, rng);
 
         p
     }
 
-    fn permute(p: &mut Vec<i32>) {
+    fn permute(p: &mut [i32], rng: &mut TracerRng) {
         for i in (1..POINT_COUNT).rev() {
-            let target = (rand::random::<f32>() * (i as f32 + 1.0)) as usize;
-            let tmp = p[i];
-            p[i] = p[target];
-            p[target] = tmp;
+            let target = rng.gen_range(0..=i);
+            p.swap(i, target);
         }
     }
 
@@ -87,16 +93,14 @@ impl Perlin {
 
     pub fn trilinear_interp(c: [[[Vec3A; 2]; 2]; 2], u: f32, v: f32, w: f32) -> f32 {
         let mut accum = 0.0;
-        for i in 0..2 {
-            for j in 0..2 {
-                for k in 0..2 {
-                    accum += (i as f32 * u + (1 - i) as f32 * (1.0 - u))
-                        * (j as f32 * v + (1 - j) as f32 * (1.0 - v))
-                        * (k as f32 * w + (1 - k) as f32 * (1.0 - w))
-                        * Vec3A::dot(
-                            c[i][j][k],
-                            Vec3A::new(u - i as f32, v - j as f32, w - k as f32),
-                        );
+        for (i, ci) in c.iter().enumerate() {
+            for (j, cij) in ci.iter().enumerate() {
+                for (k, cijk) in cij.iter().enumerate() {
+                    let (i, j, k) = (i as f32, j as f32, k as f32);
+                    accum += (i * u + (1.0 - i) * (1.0 - u))
+                        * (j * v + (1.0 - j) * (1.0 - v))
+                        * (k * w + (1.0 - k) * (1.0 - w))
+                        * Vec3A::dot(*cijk, Vec3A::new(u - i, v - j, w - k));
                 }
             }
         }
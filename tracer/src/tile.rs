@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+
+/// Order tiles are handed to the render threads in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum TileOrder {
+    /// Rows of tiles from the top left.
+    #[default]
+    Scanline,
+    /// Outwards from the center of the image, where the subject usually is.
+    Spiral,
+    /// Along a Hilbert curve over the smallest power of two square covering the tiles, so
+    /// consecutive tiles are neighbours on such square grids and mostly close by on others.
+    Hilbert,
+}
+
+/// Rectangle of the image rendered as one task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    /// Position of the tile in the render order.
+    pub index: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Splits an image into tiles of `size` pixels, the last row and column of tiles are cut to the
+/// image. The tiles are returned in `order`.
+pub fn tiles(width: u32, height: u32, size: u32, order: TileOrder) -> Vec<Tile> {
+    let columns = (0..width).step_by(size as usize).len() as u32;
+    let rows = (0..height).step_by(size as usize).len() as u32;
+    let positions = match order {
+        TileOrder::Scanline => (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .collect(),
+        TileOrder::Spiral => spiral(columns, rows),
+        TileOrder::Hilbert => {
+            let mut positions = (0..rows)
+                .flat_map(|row| (0..columns).map(move |column| (column, row)))
+                .collect::<Vec<(u32, u32)>>();
+            let n = columns.max(rows).next_power_of_two();
+            positions.sort_by_key(|&(column, row)| hilbert_index(n, column, row));
+            positions
+        }
+    };
+    positions
+        .into_iter()
+        .enumerate()
+        .map(|(index, (column, row))| {
+            let (x, y) = (column * size, row * size);
+            Tile {
+                index,
+                x,
+                y,
+                width: size.min(width - x),
+                height: size.min(height - y),
+            }
+        })
+        .collect()
+}
+
+/// Walks a square spiral from the center tile, right, down, left, up with the legs growing by
+/// one every second turn, keeping the positions inside the grid.
+fn spiral(columns: u32, rows: u32) -> Vec<(u32, u32)> {
+    let total = (columns * rows) as usize;
+    let mut positions = Vec::with_capacity(total);
+    let (mut x, mut y) = (((columns - 1) / 2) as i64, ((rows - 1) / 2) as i64);
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut leg = 1;
+    let mut turn = 0;
+    let visit = |x: i64, y: i64, positions: &mut Vec<(u32, u32)>| {
+        if (0..columns as i64).contains(&x) && (0..rows as i64).contains(&y) {
+            positions.push((x as u32, y as u32));
+        }
+    };
+    visit(x, y, &mut positions);
+    while positions.len() < total {
+        let (dx, dy) = directions[turn % 4];
+        for _ in 0..leg {
+            x += dx;
+            y += dy;
+            visit(x, y, &mut positions);
+        }
+        turn += 1;
+        if turn % 2 == 0 {
+            leg += 1;
+        }
+    }
+    positions
+}
+
+/// Distance along the Hilbert curve filling an `n` by `n` grid, `n` a power of two.
+fn hilbert_index(n: u32, x: u32, y: u32) -> u64 {
+    let (mut x, mut y) = (x, y);
+    let mut index = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = u32::from(x & s > 0);
+        let ry = u32::from(y & s > 0);
+        index += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+        // rotate the quadrant so the curve inside it has the standard orientation.
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x &= s - 1;
+        y &= s - 1;
+        s /= 2;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: [TileOrder; 3] = [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert];
+
+    /// Every tile of the grid is handed out exactly once, and the tiles cover the image.
+    fn assert_covers(width: u32, height: u32, size: u32, order: TileOrder) {
+        let tiles = tiles(width, height, size, order);
+        let mut covered = vec![0; (width * height) as usize];
+        for (index, tile) in tiles.iter().enumerate() {
+            assert_eq!(tile.index, index);
+            for y in tile.y..tile.y + tile.height {
+                for x in tile.x..tile.x + tile.width {
+                    covered[(y * width + x) as usize] += 1;
+                }
+            }
+        }
+        assert!(
+            covered.iter().all(|&count| count == 1),
+            "{order:?} on {width}x{height} with {size} pixel tiles"
+        );
+    }
+
+    #[test]
+    fn every_tile_is_visited_once() {
+        for order in ORDERS {
+            for (width, height, size) in [
+                (64, 64, 16),
+                (100, 37, 16),
+                (37, 100, 16),
+                (200, 10, 8),
+                (1, 90, 4),
+                (7, 7, 16),
+            ] {
+                assert_covers(width, height, size, order);
+            }
+        }
+    }
+
+    #[test]
+    fn spiral_visits_each_position_once() {
+        for (columns, rows) in [(1, 1), (4, 4), (5, 3), (2, 9), (12, 1)] {
+            let mut positions = spiral(columns, rows);
+            assert_eq!(positions.len(), (columns * rows) as usize);
+            positions.sort();
+            positions.dedup();
+            assert_eq!(positions.len(), (columns * rows) as usize);
+        }
+    }
+
+    #[test]
+    fn hilbert_index_is_a_bijection() {
+        for n in [1, 2, 4, 8, 16] {
+            let mut indices = (0..n)
+                .flat_map(|y| (0..n).map(move |x| hilbert_index(n, x, y)))
+                .collect::<Vec<_>>();
+            indices.sort();
+            assert!(indices.iter().copied().eq(0..(n * n) as u64));
+        }
+    }
+
+    #[test]
+    fn hilbert_steps_to_neighbours_on_square_grids() {
+        let tiles = tiles(128, 128, 16, TileOrder::Hilbert);
+        for pair in tiles.windows(2) {
+            let distance = pair[0].x.abs_diff(pair[1].x) + pair[0].y.abs_diff(pair[1].y);
+            assert_eq!(distance, 16);
+        }
+    }
+}
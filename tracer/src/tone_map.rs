@@ -0,0 +1,213 @@
+use crate::validation::Validator;
+use crate::vec3;
+use glam::{Mat3A, Vec3A};
+
+use serde::{Deserialize, Serialize};
+
+fn default_white() -> f32 {
+    4.0
+}
+
+/// Operator compressing linear radiance into the displayable range.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+pub enum ToneMapper {
+    /// Cuts everything above one.
+    #[default]
+    Clamp,
+    /// `L / (1 + L)` on the luminance, never reaches white.
+    Reinhard,
+    /// Reinhard reaching white at a luminance of `white`.
+    ExtendedReinhard {
+        #[serde(default = "default_white")]
+        white: f32,
+    },
+    /// John Hable's filmic curve from Uncharted 2.
+    Hable,
+    /// Stephen Hill's fit of the ACES reference and output transforms.
+    Aces,
+}
+
+impl ToneMapper {
+    pub fn extended_reinhard() -> Self {
+        ToneMapper::ExtendedReinhard {
+            white: default_white(),
+        }
+    }
+
+    /// Maps a linear color to linear values in `[0, 1]`.
+    pub fn apply(&self, color: Vec3A) -> Vec3A {
+        let mapped = match *self {
+            ToneMapper::Clamp => color,
+            ToneMapper::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMapper::ExtendedReinhard { white } => {
+                scale_luminance(color, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            ToneMapper::Hable => {
+                // exposure bias and linear white point from the original talk.
+                hable(color * 2.0) / hable(Vec3A::splat(11.2))
+            }
+            ToneMapper::Aces => aces_fitted(color),
+        };
+        mapped.clamp(Vec3A::ZERO, Vec3A::ONE)
+    }
+
+    pub fn validate(&self, location: &str, validator: &mut Validator) {
+        if let ToneMapper::ExtendedReinhard { white } = self {
+            validator.check(
+                white.is_finite() && *white > 0.0,
+                format!("{location}.ExtendedReinhard.white"),
+                "white must be positive",
+            );
+        }
+    }
+}
+
+/// Scales `color` so its luminance becomes `map(luminance)`, keeping the hue.
+fn scale_luminance(color: Vec3A, map: impl Fn(f32) -> f32) -> Vec3A {
+    let luminance = vec3::luminance(color);
+    if luminance <= 0.0 {
+        Vec3A::ZERO
+    } else {
+        color * (map(luminance) / luminance)
+    }
+}
+
+fn hable(x: Vec3A) -> Vec3A {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+fn aces_fitted(color: Vec3A) -> Vec3A {
+    // sRGB to the ACES reference input and back, given as rows.
+    let input = Mat3A::from_cols_array_2d(&[
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ])
+    .transpose();
+    let output = Mat3A::from_cols_array_2d(&[
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ])
+    .transpose();
+    let v = input * color;
+    let v = (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.432951) + 0.238081);
+    output * v
+}
+
+/// sRGB transfer function, encodes a linear value in `[0, 1]` for display.
+pub fn srgb_encode(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of `srgb_encode`, turns a display value in `[0, 1]` back into a linear one.
+pub fn srgb_decode(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// How the linear image is turned into display values for 8 bit outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DisplayTransform {
+    /// In stops, every stop doubles the brightness.
+    pub exposure: f32,
+    pub tone_mapper: ToneMapper,
+}
+
+impl DisplayTransform {
+    pub fn new(exposure: f32, tone_mapper: ToneMapper) -> Self {
+        Self {
+            exposure,
+            tone_mapper,
+        }
+    }
+
+    /// Exposes, tone maps and sRGB encodes a linear color.
+    pub fn apply(&self, color: Vec3A) -> Vec3A {
+        let mapped = self.tone_mapper.apply(color * self.exposure.exp2());
+        Vec3A::new(
+            srgb_encode(mapped.x),
+            srgb_encode(mapped.y),
+            srgb_encode(mapped.z),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [ToneMapper; 5] = [
+        ToneMapper::Clamp,
+        ToneMapper::Reinhard,
+        ToneMapper::ExtendedReinhard { white: 4.0 },
+        ToneMapper::Hable,
+        ToneMapper::Aces,
+    ];
+
+    #[test]
+    fn tone_mappers_are_monotonic_into_the_unit_range() {
+        for mapper in ALL {
+            assert!(
+                mapper.apply(Vec3A::ZERO).abs_diff_eq(Vec3A::ZERO, 1e-3),
+                "{mapper:?}"
+            );
+            let mut previous = -1.0;
+            for i in 0..200 {
+                let grey = mapper.apply(Vec3A::splat(i as f32 * 0.05));
+                assert!(grey.cmpge(Vec3A::ZERO).all() && grey.cmple(Vec3A::ONE).all());
+                assert!(grey.x >= previous, "{mapper:?} at {i}");
+                previous = grey.x;
+            }
+        }
+    }
+
+    #[test]
+    fn tone_mappers_hit_their_end_points() {
+        let half = Vec3A::splat(0.5);
+        assert_eq!(ToneMapper::Clamp.apply(half), half);
+        assert_eq!(ToneMapper::Clamp.apply(Vec3A::splat(3.0)), Vec3A::ONE);
+        assert!(ToneMapper::Reinhard
+            .apply(Vec3A::ONE)
+            .abs_diff_eq(half, 1e-5));
+        assert!(ToneMapper::extended_reinhard()
+            .apply(Vec3A::splat(4.0))
+            .abs_diff_eq(Vec3A::ONE, 1e-5));
+        // luminance based operators keep the ratio between the channels.
+        let color = ToneMapper::Reinhard.apply(Vec3A::new(0.4, 0.2, 0.1));
+        assert!((color.x / color.y - 2.0).abs() < 1e-5);
+        assert!((color.y / color.z - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn srgb_round_trips_every_8_bit_value() {
+        for i in 0..=255 {
+            let value = i as f32 / 255.0;
+            assert!(
+                (srgb_encode(srgb_decode(value)) - value).abs() < 1e-5,
+                "{i}"
+            );
+        }
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_encode(0.5) - 0.735_357).abs() < 1e-5);
+        // the two pieces meet at the break point.
+        let below = 12.92 * 0.0031308;
+        assert!((below - (1.055 * 0.0031308f32.powf(1.0 / 2.4) - 0.055)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let transform = DisplayTransform::new(1.0, ToneMapper::Clamp);
+        let encoded = transform.apply(Vec3A::splat(0.25));
+        assert!(encoded.abs_diff_eq(Vec3A::splat(srgb_encode(0.5)), 1e-6));
+    }
+}
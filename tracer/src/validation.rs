@@ -0,0 +1,206 @@
+use crate::reference::{Named, Reference};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// A problem found in a scene, located by the json path of the offending value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Location of a value the scene may give under `key` or its `alias`, naming both spellings.
+pub fn aliased(location: &str, key: &str, alias: &str) -> String {
+    format!("{location}.{key} (or {alias})")
+}
+
+/// Collects every problem in a scene rather than stopping at the first one.
+pub struct Validator {
+    material_count: usize,
+    texture_count: usize,
+    material_names: HashMap<String, usize>,
+    texture_names: HashMap<String, usize>,
+    errors: Vec<ValidationError>,
+}
+
+impl Validator {
+    pub fn new<M, T>(materials: &[Named<M>], textures: &[Named<T>]) -> Self {
+        let mut validator = Self {
+            material_count: materials.len(),
+            texture_count: textures.len(),
+            material_names: HashMap::new(),
+            texture_names: HashMap::new(),
+            errors: Vec::new(),
+        };
+        validator.material_names = validator.collect_names("materials", materials);
+        validator.texture_names = validator.collect_names("textures", textures);
+        validator
+    }
+
+    fn collect_names<T>(&mut self, list: &str, entries: &[Named<T>]) -> HashMap<String, usize> {
+        let mut names = HashMap::new();
+        for (index, entry) in entries.iter().enumerate() {
+            if let Some(name) = &entry.name {
+                if let Some(first) = names.get(name) {
+                    self.error(
+                        format!("{list}[{index}].name"),
+                        format!("{name:?} is already used by {list}[{first}]"),
+                    );
+                } else {
+                    names.insert(name.clone(), index);
+                }
+            }
+        }
+        names
+    }
+
+    pub fn error(&mut self, location: impl Into<String>, message: impl Into<String>) {
+        self.errors.push(ValidationError {
+            location: location.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Records an error unless `condition` holds.
+    pub fn check(&mut self, condition: bool, location: impl Into<String>, message: &str) {
+        if !condition {
+            self.error(location, message);
+        }
+    }
+
+    pub fn material(&mut self, location: impl Into<String>, reference: &Reference) {
+        let (count, names) = (self.material_count, &self.material_names);
+        if let Some(message) = Self::reference_error("material", reference, count, names) {
+            self.error(location, message);
+        }
+    }
+
+    pub fn texture(&mut self, location: impl Into<String>, reference: &Reference) {
+        let (count, names) = (self.texture_count, &self.texture_names);
+        if let Some(message) = Self::reference_error("texture", reference, count, names) {
+            self.error(location, message);
+        }
+    }
+
+    fn reference_error(
+        kind: &str,
+        reference: &Reference,
+        count: usize,
+        names: &HashMap<String, usize>,
+    ) -> Option<String> {
+        match reference {
+            Reference::Index(index) if *index >= count => Some(format!(
+                "{kind} index {index} is out of range, the scene has {count} {kind}s"
+            )),
+            Reference::Name(name) if !names.contains_key(name) => {
+                Some(format!("unknown {kind} {name:?}"))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn file(&mut self, location: impl Into<String>, path: &str) {
+        if !Path::new(path).is_file() {
+            self.error(location, format!("file {path:?} does not exist"));
+        }
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn finish(self) -> Vec<ValidationError> {
+        self.errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::RenderBuilder;
+
+    fn locations(scene: &str) -> Vec<String> {
+        let builder: RenderBuilder = serde_json::from_str(scene).unwrap();
+        builder
+            .validate()
+            .into_iter()
+            .map(|error| error.location)
+            .collect()
+    }
+
+    #[test]
+    fn every_invalid_field_is_located() {
+        let found = locations(
+            r#"{
+                "settings": { "image_width": 1, "aspect_ratio": 1.0, "samples": 0, "path": "out.nope" },
+                "world": {
+                    "objects": [
+                        { "Sphere": { "center": [0.0, 0.0, 0.0], "radius": 0.0, "material": 3 } },
+                        { "XyRect": { "x0": 1.0, "x1": 0.0, "y0": 0.0, "y1": 1.0, "k": 0.0, "material_index": "missing" } },
+                        { "Translate": { "offset": [0.0, 0.0, 0.0], "object": { "Sphere": { "center": [0.0, 0.0, 0.0], "radius": -1.0, "material_index": 0 } } } }
+                    ]
+                },
+                "camera": {
+                    "look_from": [0.0, 0.0, 1.0],
+                    "look_at": [0.0, 0.0, 1.0],
+                    "vfov": 180.0,
+                    "aspect_ratio": 1.0,
+                    "aperture": -1.0
+                },
+                "materials": [
+                    { "name": "red", "Lambertian": { "texture": 5 } },
+                    { "name": "red", "Metal": { "texture_index": 0, "fuzz": -1.0, "emitted": "glow" } },
+                    { "Glossy": { "texture": 0, "roughness": 1.5 } }
+                ],
+                "textures": [{ "Image": { "path": "does/not/exist.png" } }]
+            }"#,
+        );
+        let expected = [
+            "materials[1].name",
+            "settings.image_width",
+            "settings.samples",
+            "settings.path",
+            "camera.look_at",
+            "camera.vup",
+            "camera.vfov",
+            "camera.aperture",
+            "world.objects[0].Sphere.radius",
+            "world.objects[0].Sphere.material_index (or material)",
+            "world.objects[1].XyRect.x1",
+            "world.objects[1].XyRect.material_index (or material)",
+            "world.objects[2].Translate.object.Sphere.radius",
+            "materials[0].Lambertian.texture_index (or texture)",
+            "materials[1].Metal.fuzz",
+            "materials[1].Metal.emitted",
+            "materials[2].Glossy.roughness",
+            "textures[0].Image.path",
+        ];
+        for location in expected {
+            assert!(
+                found.iter().any(|l| l == location),
+                "{location} not in {found:?}"
+            );
+        }
+        assert_eq!(found.len(), expected.len(), "{found:?}");
+    }
+
+    #[test]
+    fn valid_scene_has_no_errors() {
+        let found = locations(
+            r#"{
+                "settings": { "image_width": 4, "aspect_ratio": 1.0, "samples": 1, "path": "out.png" },
+                "world": { "objects": [{ "Sphere": { "center": [0.0, 0.0, 0.0], "radius": 1.0, "material": "red" } }] },
+                "camera": { "look_from": [0.0, 0.0, 5.0], "look_at": [0.0, 0.0, 0.0], "vfov": 40.0, "aspect_ratio": 1.0, "aperture": 0.0 },
+                "materials": [{ "name": "red", "Lambertian": { "texture": 0 } }],
+                "textures": [{ "Solid": { "color": [1.0, 0.0, 0.0] } }]
+            }"#,
+        );
+        assert!(found.is_empty(), "{found:?}");
+    }
+}
@@ -1,14 +1,14 @@
+use crate::random::TracerRng;
 use crate::vec3;
-use glam::Vec3A;
+use glam::{Vec2, Vec3A};
 use rand::Rng;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
 
-pub fn random_range(min: f32, max: f32) -> Vec3A {
-    let mut rng = rand::thread_rng(); // TODO: move once threading
+pub fn random_range(rng: &mut TracerRng, min: f32, max: f32) -> Vec3A {
     rng.gen::<Vec3A>() * (max - min) + min
 }
 
-pub fn random_in_unit_sphere() -> Vec3A {
-    let mut rng = rand::thread_rng(); // TODO: move once threading
+pub fn random_in_unit_sphere(rng: &mut TracerRng) -> Vec3A {
     loop {
         let p = rng.gen::<Vec3A>() * 2.0 - 1.0;
         if p.length_squared() < 1.0 {
@@ -17,22 +17,40 @@ pub fn random_in_unit_sphere() -> Vec3A {
     }
 }
 
-pub fn random_in_hemisphere(value: Vec3A) -> Vec3A {
-    let in_unit_sphere = random_in_unit_sphere();
+pub fn random_in_hemisphere(rng: &mut TracerRng, value: Vec3A) -> Vec3A {
+    let in_unit_sphere = random_in_unit_sphere(rng);
     if in_unit_sphere.dot(value) > 0.0 {
         in_unit_sphere
     } else {
         -in_unit_sphere
     }
 }
-pub fn random_in_unit_disk() -> Vec3A {
-    let mut rng = rand::thread_rng(); // TODO: move once threading
-    loop {
-        let p = Vec3A::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
-        if p.length_squared() < 1.0 {
-            return p;
-        }
+/// Maps a point of the unit square onto the unit disk in the xy plane, keeping strata intact
+/// (Shirley and Chiu concentric mapping).
+pub fn sample_unit_disk(u: Vec2) -> Vec3A {
+    let offset = u * 2.0 - 1.0;
+    if offset == Vec2::ZERO {
+        return Vec3A::ZERO;
     }
+    let (r, theta) = if offset.x.abs() > offset.y.abs() {
+        (offset.x, FRAC_PI_4 * (offset.y / offset.x))
+    } else {
+        (offset.y, FRAC_PI_2 - FRAC_PI_4 * (offset.x / offset.y))
+    };
+    Vec3A::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+/// Maps a point of the unit square onto a uniformly distributed unit vector.
+pub fn sample_unit_sphere(u: Vec2) -> Vec3A {
+    let z = 1.0 - 2.0 * u.x;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.y;
+    Vec3A::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Uniform point inside the unit sphere, `u` picks the direction and `radius` the distance.
+pub fn sample_unit_ball(u: Vec2, radius: f32) -> Vec3A {
+    sample_unit_sphere(u) * radius.cbrt()
 }
 
 pub fn reflect(value: Vec3A, normal: Vec3A) -> Vec3A {
@@ -59,3 +77,8 @@ pub fn sqrt(value: Vec3A) -> Vec3A {
 pub fn unit(value: Vec3A) -> Vec3A {
     value / value.length()
 }
+
+/// Relative luminance of a linear Rec. 709 color.
+pub fn luminance(value: Vec3A) -> f32 {
+    value.dot(Vec3A::new(0.2126, 0.7152, 0.0722))
+}
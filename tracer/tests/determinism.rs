@@ -0,0 +1,43 @@
+use tracer::framebuffer::Framebuffer;
+use tracer::renderer::RenderBuilder;
+use tracer::tile::TileOrder;
+
+fn render(seed: u64, threads: usize, tile_order: TileOrder) -> Framebuffer {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/cornell_box.json");
+    let mut builder = RenderBuilder::from_path(path).unwrap();
+    builder.settings.image_width = 40;
+    builder.settings.samples = 4;
+    builder.settings.tile_size = 8;
+    builder.settings.roulette_depth = Some(2);
+    builder.settings.seed = seed;
+    builder.settings.threads = Some(threads);
+    builder.settings.tile_order = tile_order;
+    builder.build().unwrap().render_image()
+}
+
+fn bits(image: &Framebuffer) -> Vec<[u32; 3]> {
+    image
+        .pixels
+        .iter()
+        .map(|pixel| pixel.to_array().map(f32::to_bits))
+        .collect()
+}
+
+#[test]
+fn seeded_renders_do_not_depend_on_threads_or_tile_order() {
+    let reference = bits(&render(11, 1, TileOrder::Scanline));
+    for threads in [1, 4] {
+        for tile_order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            assert!(
+                bits(&render(11, threads, tile_order)) == reference,
+                "{threads} threads in {tile_order:?} order changed the image"
+            );
+        }
+    }
+}
+
+#[test]
+fn seeds_change_the_image() {
+    let image = bits(&render(11, 2, TileOrder::Scanline));
+    assert!(bits(&render(12, 2, TileOrder::Scanline)) != image);
+}